@@ -0,0 +1,368 @@
+//! 印章式底框 - 绘制在水印文字后方的圆角矩形或双线边框
+//!
+//! 底框与文字位于同一个文本 Form XObject 中，尺寸由实测文本包围盒外扩留白得到，
+//! 使用独立的 ExtGState（`GS2`）设置透明度，填充与描边颜色各自独立。
+
+use crate::color::{BlendMode, WatermarkColor};
+use crate::TextBounds;
+use lopdf::content::Operation;
+use lopdf::{dictionary, Object};
+
+/// 底框透明度 ExtGState 的资源名
+pub(crate) const BADGE_GRAPHICS_STATE: &str = "GS2";
+
+/// 底框专色填充颜色的资源名
+const BADGE_FILL_COLOR_SPACE: &str = "CS3";
+
+/// 底框专色描边颜色的资源名
+const BADGE_STROKE_COLOR_SPACE: &str = "CS4";
+
+/// 以三次贝塞尔曲线近似四分之一圆弧时控制点的系数
+const ARC_KAPPA: f32 = 0.552_284_8;
+
+/// 水印文字后方的底框（如 "APPROVED"、"VOID" 印章）
+///
+/// # 说明
+/// - 外框为文本包围盒（含文字描边）四周外扩 `padding` 的矩形，`corner_radius` 大于 0 时为圆角
+/// - `double_gap` 为 `Some` 时在外框内侧再画一道边框，形成双线边框
+/// - 填充与描边均可省略；二者都省略时不绘制底框
+/// - 留白较大时相邻水印的底框可能重叠，可配合较大的网格间距使用
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{Badge, WatermarkColor, Watermarker};
+///
+/// // 红色双线圆角印章，浅红底色
+/// let marker = Watermarker::from_file("font.otf")?.with_badge(Badge {
+///     corner_radius: 6.0,
+///     double_gap: Some(2.5),
+///     fill: Some("#ffe0e0".parse()?),
+///     stroke: Some(WatermarkColor::Rgb([0.8, 0.0, 0.0])),
+///     opacity: 0.25,
+///     ..Badge::default()
+/// });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    /// 文本包围盒到外框的留白（点数）
+    pub padding: f32,
+    /// 圆角半径（点数，0 为直角；超过边长一半时按一半处理）
+    pub corner_radius: f32,
+    /// 双线边框内外两道框的间距（点数），`None` 为单线边框
+    pub double_gap: Option<f32>,
+    /// 底框填充颜色，`None` 不填充
+    pub fill: Option<WatermarkColor>,
+    /// 边框颜色，`None` 不画边框
+    pub stroke: Option<WatermarkColor>,
+    /// 边框线宽（点数）
+    pub line_width: f32,
+    /// 底框透明度（0.0-1.0），与文字透明度相互独立
+    pub opacity: f32,
+}
+
+impl Default for Badge {
+    /// 单线圆角边框：留白 6、圆角 4、线宽 1.5，深灰色，透明度与文字相同（0.1）
+    fn default() -> Self {
+        Badge {
+            padding: 6.0,
+            corner_radius: 4.0,
+            double_gap: None,
+            fill: None,
+            stroke: Some(WatermarkColor::default()),
+            line_width: 1.5,
+            opacity: 0.1,
+        }
+    }
+}
+
+impl Badge {
+    /// 是否有可绘制的内容
+    fn is_visible(&self) -> bool {
+        self.fill.is_some() || (self.stroke.is_some() && self.line_width > 0.0)
+    }
+
+    /// 外框矩形 (x_min, y_min, x_max, y_max)：文本范围四周外扩留白
+    fn outer_rect(&self, text: &TextBounds) -> (f32, f32, f32, f32) {
+        let pad = self.padding.max(0.0);
+        (text.x_min - pad, text.y_min - pad, text.x_max + pad, text.y_max + pad)
+    }
+
+    /// 底框绘制后的范围（外框加半个线宽）；不绘制底框时返回 `text` 本身
+    ///
+    /// # 参数
+    /// - `text`: 文字绘制范围（含文字描边）
+    pub(crate) fn painted_bounds(&self, text: &TextBounds) -> TextBounds {
+        if !self.is_visible() {
+            return *text;
+        }
+        let half = if self.stroke.is_some() { self.line_width / 2.0 } else { 0.0 };
+        let (x_min, y_min, x_max, y_max) = self.outer_rect(text);
+        TextBounds {
+            x_min: text.x_min.min(x_min - half),
+            y_min: text.y_min.min(y_min - half),
+            x_max: text.x_max.max(x_max + half),
+            y_max: text.y_max.max(y_max + half),
+            ..*text
+        }
+    }
+
+    /// 底框的内容流操作（自带 `q ... Q`，绘制在文字之前）
+    ///
+    /// # 参数
+    /// - `text`: 文字绘制范围（含文字描边）
+    pub(crate) fn ops(&self, text: &TextBounds) -> Vec<Operation> {
+        if !self.is_visible() {
+            return Vec::new();
+        }
+        let mut ops = vec![
+            Operation::new("q", vec![]),
+            Operation::new("gs", vec![BADGE_GRAPHICS_STATE.into()]),
+        ];
+        if let Some(fill) = &self.fill {
+            ops.extend(fill.set_ops(false, BADGE_FILL_COLOR_SPACE));
+        }
+        let stroke = self.stroke.as_ref().filter(|_| self.line_width > 0.0);
+        if let Some(stroke) = stroke {
+            ops.extend(stroke.set_ops(true, BADGE_STROKE_COLOR_SPACE));
+            ops.push(Operation::new("w", vec![self.line_width.into()]));
+        }
+
+        let outer = self.outer_rect(text);
+        push_rounded_rect(&mut ops, outer, self.corner_radius);
+        let paint = match (self.fill.is_some(), stroke.is_some()) {
+            (true, true) => "B",
+            (true, false) => "f",
+            _ => "S",
+        };
+        ops.push(Operation::new(paint, vec![]));
+
+        // 双线边框：在外框内侧按间距收缩出第二道框，圆角同心
+        if let (Some(gap), Some(_)) = (self.double_gap, stroke) {
+            let inset = gap.max(0.0) + self.line_width;
+            let (x_min, y_min, x_max, y_max) = outer;
+            if x_max - x_min > 2.0 * inset && y_max - y_min > 2.0 * inset {
+                push_rounded_rect(
+                    &mut ops,
+                    (x_min + inset, y_min + inset, x_max - inset, y_max - inset),
+                    self.corner_radius - inset,
+                );
+                ops.push(Operation::new("S", vec![]));
+            }
+        }
+        ops.push(Operation::new("Q", vec![]));
+        ops
+    }
+
+    /// 底框的透明度 ExtGState（与文字使用相同的混合模式）
+    pub(crate) fn graphics_state(&self, blend_mode: BlendMode) -> lopdf::Dictionary {
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        let mut gs = dictionary! {
+            "Type" => "ExtGState",
+            "ca" => opacity,
+            "CA" => opacity,
+        };
+        if blend_mode != BlendMode::Normal {
+            gs.set("BM", Object::Name(blend_mode.pdf_name().as_bytes().to_vec()));
+        }
+        gs
+    }
+
+    /// 底框使用的专色颜色空间，写入 `spaces`（Resources > ColorSpace）
+    pub(crate) fn add_color_spaces(&self, spaces: &mut lopdf::Dictionary) {
+        if let Some(space) = self.fill.as_ref().and_then(WatermarkColor::color_space) {
+            spaces.set(BADGE_FILL_COLOR_SPACE, space);
+        }
+        if let Some(space) = self.stroke.as_ref().and_then(WatermarkColor::color_space) {
+            spaces.set(BADGE_STROKE_COLOR_SPACE, space);
+        }
+    }
+}
+
+/// 追加（圆角）矩形路径
+///
+/// # 说明
+/// 圆角以四段三次贝塞尔曲线近似；半径不超过短边的一半，不大于 0 时为直角矩形（`re`）。
+fn push_rounded_rect(ops: &mut Vec<Operation>, rect: (f32, f32, f32, f32), radius: f32) {
+    let (x0, y0, x1, y1) = rect;
+    let r = radius.min((x1 - x0) / 2.0).min((y1 - y0) / 2.0);
+    if r <= 0.0 {
+        ops.push(Operation::new(
+            "re",
+            vec![x0.into(), y0.into(), (x1 - x0).into(), (y1 - y0).into()],
+        ));
+        return;
+    }
+    let k = r * ARC_KAPPA;
+    let point = |op: &str, pts: &[f32]| Operation::new(op, pts.iter().map(|&v| v.into()).collect());
+    ops.push(point("m", &[x0 + r, y0]));
+    ops.push(point("l", &[x1 - r, y0]));
+    ops.push(point("c", &[x1 - r + k, y0, x1, y0 + r - k, x1, y0 + r]));
+    ops.push(point("l", &[x1, y1 - r]));
+    ops.push(point("c", &[x1, y1 - r + k, x1 - r + k, y1, x1 - r, y1]));
+    ops.push(point("l", &[x0 + r, y1]));
+    ops.push(point("c", &[x0 + r - k, y1, x0, y1 - r + k, x0, y1 - r]));
+    ops.push(point("l", &[x0, y0 + r]));
+    ops.push(point("c", &[x0, y0 + r - k, x0 + r - k, y0, x0 + r, y0]));
+    ops.push(Operation::new("h", vec![]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text() -> TextBounds {
+        TextBounds {
+            advance: 100.0,
+            x_min: 0.0,
+            y_min: -2.0,
+            x_max: 100.0,
+            y_max: 10.0,
+            vertical: false,
+        }
+    }
+
+    fn rect(bounds: &TextBounds) -> [f32; 4] {
+        [bounds.x_min, bounds.y_min, bounds.x_max, bounds.y_max]
+    }
+
+    fn operators(ops: &[Operation]) -> Vec<&str> {
+        ops.iter().map(|op| op.operator.as_str()).collect()
+    }
+
+    fn operands(op: &Operation) -> Vec<f32> {
+        op.operands.iter().map(|o| o.as_float().unwrap()).collect()
+    }
+
+    /// 只有描边的直角边框
+    fn stroked() -> Badge {
+        Badge {
+            corner_radius: 0.0,
+            stroke: Some(WatermarkColor::Gray(0.2)),
+            ..Badge::default()
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // 绘制范围
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn painted_bounds_add_padding_and_half_line_width() {
+        // 留白 6，线宽 1.5
+        assert_eq!(rect(&Badge::default().painted_bounds(&text())), [-6.75, -8.75, 106.75, 16.75]);
+        let filled = Badge {
+            fill: Some(WatermarkColor::Gray(0.9)),
+            stroke: None,
+            ..Badge::default()
+        };
+        assert_eq!(rect(&filled.painted_bounds(&text())), [-6.0, -8.0, 106.0, 16.0]);
+        // 负的留白按 0 处理
+        let tight = Badge { padding: -3.0, ..Badge::default() };
+        assert_eq!(rect(&tight.painted_bounds(&text())), [-0.75, -2.75, 100.75, 10.75]);
+        let bounds = Badge::default().painted_bounds(&text());
+        assert_eq!((bounds.advance, bounds.vertical), (100.0, false));
+    }
+
+    #[test]
+    fn painted_bounds_unchanged_when_nothing_is_visible() {
+        let hidden = [
+            Badge { stroke: None, ..Badge::default() },
+            Badge { line_width: 0.0, ..Badge::default() },
+        ];
+        for badge in hidden {
+            assert_eq!(badge.painted_bounds(&text()), text());
+            assert!(badge.ops(&text()).is_empty());
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // 内容流
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn paint_operator_follows_fill_and_stroke() {
+        let fill = Some(WatermarkColor::Rgb([1.0, 0.9, 0.9]));
+        let both = Badge { fill: fill.clone(), ..stroked() };
+        assert_eq!(operators(&both.ops(&text())), ["q", "gs", "rg", "G", "w", "re", "B", "Q"]);
+
+        let fill_only = Badge { fill: fill.clone(), stroke: None, ..stroked() };
+        assert_eq!(operators(&fill_only.ops(&text())), ["q", "gs", "rg", "re", "f", "Q"]);
+
+        // 线宽为 0 时不描边
+        let zero_width = Badge { fill, line_width: 0.0, ..stroked() };
+        assert_eq!(operators(&zero_width.ops(&text())), ["q", "gs", "rg", "re", "f", "Q"]);
+
+        let ops = stroked().ops(&text());
+        assert_eq!(operators(&ops), ["q", "gs", "G", "w", "re", "S", "Q"]);
+        assert_eq!(ops[1].operands, [Object::Name(b"GS2".to_vec())]);
+        assert_eq!(operands(&ops[3]), [1.5]);
+        assert_eq!(operands(&ops[4]), [-6.0, -8.0, 112.0, 24.0]);
+    }
+
+    #[test]
+    fn double_border_insets_by_gap_plus_line_width() {
+        let badge = Badge { double_gap: Some(2.5), ..stroked() };
+        let ops = badge.ops(&text());
+        assert_eq!(operators(&ops), ["q", "gs", "G", "w", "re", "S", "re", "S", "Q"]);
+        // 外框 (-6, -8)-(106, 16)，内缩 2.5 + 1.5 = 4
+        assert_eq!(operands(&ops[6]), [-2.0, -4.0, 104.0, 16.0]);
+
+        // 圆角同心：内框半径减去内缩量
+        let rounded = Badge { corner_radius: 6.0, ..badge.clone() };
+        let ops = rounded.ops(&text());
+        let moves: Vec<Vec<f32>> = ops.iter().filter(|op| op.operator == "m").map(operands).collect();
+        assert_eq!(moves, [vec![0.0, -8.0], vec![0.0, -4.0]]);
+
+        // 负的间距按 0 处理
+        let no_gap = Badge { double_gap: Some(-1.0), ..stroked() };
+        assert_eq!(operands(&no_gap.ops(&text())[6]), [-4.5, -6.5, 109.0, 21.0]);
+    }
+
+    #[test]
+    fn double_border_skipped_when_too_small_or_unstroked() {
+        let tiny = TextBounds { x_min: 0.0, y_min: 0.0, x_max: 20.0, y_max: 1.0, ..text() };
+        // 外框高 1，内缩 8 后没有空间
+        let badge = Badge { padding: 0.0, double_gap: Some(2.5), ..stroked() };
+        assert_eq!(operators(&badge.ops(&tiny)), ["q", "gs", "G", "w", "re", "S", "Q"]);
+        // 刚好等于两倍内缩量时也跳过
+        let exact = TextBounds { y_max: 8.0, ..tiny };
+        assert_eq!(operators(&badge.ops(&exact)), ["q", "gs", "G", "w", "re", "S", "Q"]);
+
+        let fill_only = Badge {
+            fill: Some(WatermarkColor::Gray(0.9)),
+            stroke: None,
+            double_gap: Some(2.5),
+            ..Badge::default()
+        };
+        assert_eq!(operators(&fill_only.ops(&text())), ["q", "gs", "g", "m", "l", "c", "l", "c", "l", "c", "l", "c", "h", "f", "Q"]);
+    }
+
+    // ------------------------------------------------------------------
+    // 圆角矩形路径
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn rounded_rect_clamps_radius_to_half_short_side() {
+        let mut ops = Vec::new();
+        push_rounded_rect(&mut ops, (0.0, 0.0, 40.0, 10.0), 100.0);
+        assert_eq!(operators(&ops), ["m", "l", "c", "l", "c", "l", "c", "l", "c", "h"]);
+        // 半径按 5 处理
+        let k = 5.0 * ARC_KAPPA;
+        assert_eq!(operands(&ops[0]), [5.0, 0.0]);
+        assert_eq!(operands(&ops[1]), [35.0, 0.0]);
+        assert_eq!(operands(&ops[2]), [35.0 + k, 0.0, 40.0, 5.0 - k, 40.0, 5.0]);
+        assert_eq!(operands(&ops[3]), [40.0, 5.0]);
+        assert_eq!(operands(&ops[8]), [0.0, 5.0 - k, 5.0 - k, 0.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn rounded_rect_without_radius_uses_re() {
+        for radius in [0.0, -2.0] {
+            let mut ops = Vec::new();
+            push_rounded_rect(&mut ops, (1.0, 2.0, 41.0, 12.0), radius);
+            assert_eq!(operators(&ops), ["re"]);
+            assert_eq!(operands(&ops[0]), [1.0, 2.0, 40.0, 10.0]);
+        }
+    }
+}
@@ -0,0 +1,518 @@
+//! 水印颜色 - 灰度、RGB、CMYK 与专色（Separation），以及渐变、颜色循环与透明混合模式
+//!
+//! 设备颜色直接写入内容流（`g`/`rg`/`k` 及对应的描边操作符）；
+//! 专色需要在 XObject 的 Resources 中定义 `/Separation` 颜色空间，
+//! 印刷时单独出一块印版，不支持专色的设备使用 CMYK 替代色。
+//! 渐变为裁剪到字形轮廓的 Shading（`W n sh`），混合模式写入水印 ExtGState 的 `/BM`。
+
+use lopdf::content::Operation;
+use lopdf::{dictionary, Object};
+use std::str::FromStr;
+
+/// 水印颜色
+///
+/// # 示例
+/// ```
+/// use water_mark::WatermarkColor;
+///
+/// let red: WatermarkColor = "#c00".parse()?;
+/// assert_eq!(red, WatermarkColor::Rgb([0.8, 0.0, 0.0]));
+///
+/// let plate: WatermarkColor = "spot:PANTONE 185 C:0,0.91,0.76,0".parse()?;
+/// assert!(matches!(plate, WatermarkColor::Spot { .. }));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatermarkColor {
+    /// 灰度（0.0 黑 - 1.0 白），`DeviceGray`
+    Gray(f32),
+    /// RGB（各分量 0.0-1.0），`DeviceRGB`
+    Rgb([f32; 3]),
+    /// CMYK（各分量 0.0-1.0），`DeviceCMYK`
+    Cmyk([f32; 4]),
+    /// 专色：以 100% 色调绘制到名为 `name` 的分色印版
+    Spot {
+        /// 专色名称（如 `PANTONE 185 C`），印刷厂据此匹配油墨
+        name: String,
+        /// 不支持该专色的设备（屏幕、普通打印机）使用的 CMYK 替代色
+        alternate: [f32; 4],
+    },
+}
+
+impl Default for WatermarkColor {
+    /// 深灰色 RGB(0.1, 0.1, 0.1)
+    fn default() -> Self {
+        WatermarkColor::Rgb([0.1, 0.1, 0.1])
+    }
+}
+
+impl WatermarkColor {
+    /// 解析十六进制 RGB 颜色
+    ///
+    /// # 参数
+    /// - `hex`: `#RRGGBB` 或 `#RGB`（`#` 可省略，大小写不限）
+    ///
+    /// # 返回
+    /// [`WatermarkColor::Rgb`]，格式不合法时返回错误
+    pub fn from_hex(hex: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let digits = hex.trim().trim_start_matches('#');
+        let invalid = || format!("无效的十六进制颜色 '{}'，应为 #RRGGBB 或 #RGB", hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid().into());
+        }
+        let channel = |s: &str| u8::from_str_radix(s, 16).map(|v| v as f32 / 255.0);
+        let rgb = match digits.len() {
+            6 => [channel(&digits[0..2])?, channel(&digits[2..4])?, channel(&digits[4..6])?],
+            // #RGB 每位重复一次：#c00 == #cc0000
+            3 => {
+                let mut rgb = [0.0; 3];
+                for (v, i) in rgb.iter_mut().zip(0..3) {
+                    *v = channel(&digits[i..i + 1].repeat(2))?;
+                }
+                rgb
+            }
+            _ => return Err(invalid().into()),
+        };
+        Ok(WatermarkColor::Rgb(rgb))
+    }
+
+    /// 设置当前颜色的内容流操作
+    ///
+    /// # 参数
+    /// - `stroke`: `true` 设置描边颜色，`false` 设置填充颜色
+    /// - `color_space`: 专色在 Resources > ColorSpace 中的资源名（设备颜色忽略）
+    pub(crate) fn set_ops(&self, stroke: bool, color_space: &str) -> Vec<Operation> {
+        let op = |fill: &str, stroke_op: &str, operands: Vec<Object>| {
+            Operation::new(if stroke { stroke_op } else { fill }, operands)
+        };
+        match self {
+            WatermarkColor::Gray(g) => vec![op("g", "G", vec![(*g).into()])],
+            WatermarkColor::Rgb(rgb) => vec![op("rg", "RG", reals(rgb))],
+            WatermarkColor::Cmyk(cmyk) => vec![op("k", "K", reals(cmyk))],
+            WatermarkColor::Spot { .. } => vec![
+                op("cs", "CS", vec![Object::Name(color_space.as_bytes().to_vec())]),
+                op("scn", "SCN", vec![1.0f32.into()]), // 100% 色调
+            ],
+        }
+    }
+
+    /// 设备颜色空间名称与颜色分量（专色返回 `None`）
+    fn device_components(&self) -> Option<(&'static str, &[f32])> {
+        match self {
+            WatermarkColor::Gray(g) => Some(("DeviceGray", std::slice::from_ref(g))),
+            WatermarkColor::Rgb(rgb) => Some(("DeviceRGB", rgb)),
+            WatermarkColor::Cmyk(cmyk) => Some(("DeviceCMYK", cmyk)),
+            WatermarkColor::Spot { .. } => None,
+        }
+    }
+
+    /// 专色对应的 Separation 颜色空间（设备颜色返回 `None`）
+    ///
+    /// # 说明
+    /// `[/Separation /名称 /DeviceCMYK 色调转换函数]`，转换函数为线性插值
+    /// （FunctionType 2），色调 0 映射为无墨、色调 1 映射为替代色。
+    pub(crate) fn color_space(&self) -> Option<Object> {
+        let WatermarkColor::Spot { name, alternate } = self else {
+            return None;
+        };
+        let tint_transform = dictionary! {
+            "FunctionType" => 2,
+            "Domain" => vec![0.into(), 1.into()],
+            "C0" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+            "C1" => reals(alternate),
+            "N" => 1,
+        };
+        Some(Object::Array(vec![
+            Object::Name(b"Separation".to_vec()),
+            Object::Name(name.as_bytes().to_vec()),
+            Object::Name(b"DeviceCMYK".to_vec()),
+            tint_transform.into(),
+        ]))
+    }
+}
+
+impl FromStr for WatermarkColor {
+    type Err = Box<dyn std::error::Error>;
+
+    /// 从字符串解析颜色
+    ///
+    /// # 说明
+    /// 支持的格式（分量均为 0.0-1.0）：
+    /// - `#RRGGBB` / `#RGB`：RGB
+    /// - `gray:0.5`：灰度
+    /// - `rgb:0.8,0,0`：RGB
+    /// - `cmyk:0,0.8,1,0`：CMYK
+    /// - `spot:名称:c,m,y,k`：专色，`c,m,y,k` 为 CMYK 替代色
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some((kind, rest)) = s.split_once(':') else {
+            return WatermarkColor::from_hex(s);
+        };
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "gray" | "grey" => {
+                let [g] = components(rest, s)?;
+                Ok(WatermarkColor::Gray(g))
+            }
+            "rgb" => Ok(WatermarkColor::Rgb(components(rest, s)?)),
+            "cmyk" => Ok(WatermarkColor::Cmyk(components(rest, s)?)),
+            "spot" => {
+                // 名称中可能含有冒号，替代色取最后一段
+                let (name, alternate) = rest
+                    .rsplit_once(':')
+                    .ok_or_else(|| format!("专色 '{}' 缺少 CMYK 替代色，应为 spot:名称:c,m,y,k", s))?;
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(format!("专色 '{}' 的名称为空", s).into());
+                }
+                Ok(WatermarkColor::Spot {
+                    name: name.to_string(),
+                    alternate: components(alternate, s)?,
+                })
+            }
+            other => Err(format!(
+                "未知的颜色类型 '{}'（支持 #RRGGBB、gray:、rgb:、cmyk:、spot:）",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+/// 渐变填充
+///
+/// # 说明
+/// 渐变的几何范围取自文本轮廓包围盒，按书写方向铺满整段文字；
+/// 两端颜色必须属于同一设备颜色空间（同为灰度、RGB 或 CMYK），不支持专色。
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{Gradient, WatermarkColor, Watermarker};
+///
+/// // 红色到橙色的 "机密" 印章
+/// let marker = Watermarker::from_file("font.otf")?.with_gradient(Gradient::Axial {
+///     start: "#d00000".parse()?,
+///     end: "#ff8c00".parse()?,
+/// })?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gradient {
+    /// 线性渐变：沿书写方向从文本起点（横排左端、竖排顶端）到终点
+    Axial {
+        start: WatermarkColor,
+        end: WatermarkColor,
+    },
+    /// 径向渐变：从文本包围盒中心到四角
+    Radial {
+        center: WatermarkColor,
+        edge: WatermarkColor,
+    },
+}
+
+impl Gradient {
+    /// 起止颜色
+    fn colors(&self) -> (&WatermarkColor, &WatermarkColor) {
+        match self {
+            Gradient::Axial { start, end } => (start, end),
+            Gradient::Radial { center, edge } => (center, edge),
+        }
+    }
+
+    /// 校验两端颜色可以组成同一个 Shading
+    pub(crate) fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (from, to) = self.colors();
+        match (from.device_components(), to.device_components()) {
+            (Some((a, _)), Some((b, _))) if a == b => Ok(()),
+            (Some((a, _)), Some((b, _))) => {
+                Err(format!("渐变两端的颜色空间不一致：{} 与 {}", a, b).into())
+            }
+            _ => Err("渐变不支持专色，请使用灰度、RGB 或 CMYK 颜色".into()),
+        }
+    }
+
+    /// 生成覆盖指定文本包围盒的 Shading 字典
+    ///
+    /// # 参数
+    /// - `bounds`: 文本轮廓包围盒 (x_min, y_min, x_max, y_max)
+    /// - `vertical`: 是否竖排（线性渐变改为自上而下）
+    ///
+    /// # 说明
+    /// 线性渐变为 ShadingType 2、径向渐变为 ShadingType 3，颜色以 FunctionType 2
+    /// 线性插值；两端均延伸（`Extend [true true]`），描边外扩部分使用端点颜色。
+    pub(crate) fn shading(&self, bounds: (f32, f32, f32, f32), vertical: bool) -> lopdf::Dictionary {
+        let (x_min, y_min, x_max, y_max) = bounds;
+        let (cx, cy) = ((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+        let (from, to) = self.colors();
+        let (space, c0) = from.device_components().unwrap_or(("DeviceGray", &[0.0]));
+        let (_, c1) = to.device_components().unwrap_or(("DeviceGray", &[0.0]));
+
+        let (shading_type, coords) = match self {
+            Gradient::Axial { .. } if vertical => (2, vec![cx, y_max, cx, y_min]),
+            Gradient::Axial { .. } => (2, vec![x_min, cy, x_max, cy]),
+            Gradient::Radial { .. } => {
+                let radius = (x_max - x_min).hypot(y_max - y_min) / 2.0;
+                (3, vec![cx, cy, 0.0, cx, cy, radius])
+            }
+        };
+        dictionary! {
+            "ShadingType" => shading_type,
+            "ColorSpace" => Object::Name(space.as_bytes().to_vec()),
+            "Coords" => reals(&coords),
+            "Function" => dictionary! {
+                "FunctionType" => 2,
+                "Domain" => vec![0.into(), 1.into()],
+                "C0" => reals(c0),
+                "C1" => reals(c1),
+                "N" => 1,
+            },
+            "Extend" => vec![true.into(), true.into()],
+        }
+    }
+}
+
+/// 网格颜色循环：相邻的行（或列）依次使用列表中的颜色
+///
+/// # 说明
+/// 行、列按文本自身坐标系划分：一行为沿书写方向排成一线的水印，
+/// 列为垂直于书写方向的一组水印。页面中心所在的行（列）使用第一个颜色。
+/// 每个颜色生成一个文本 XObject，网格单元格按行（列）号循环引用。
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorCycle {
+    /// 逐行循环
+    Rows(Vec<WatermarkColor>),
+    /// 逐列循环
+    Columns(Vec<WatermarkColor>),
+}
+
+impl ColorCycle {
+    /// 循环使用的颜色
+    pub fn colors(&self) -> &[WatermarkColor] {
+        match self {
+            ColorCycle::Rows(colors) | ColorCycle::Columns(colors) => colors,
+        }
+    }
+
+    /// 是否逐行循环
+    pub(crate) fn by_rows(&self) -> bool {
+        matches!(self, ColorCycle::Rows(_))
+    }
+}
+
+/// 解析逗号分隔的 N 个颜色分量，并校验取值范围
+fn components<const N: usize>(list: &str, source: &str) -> Result<[f32; N], Box<dyn std::error::Error>> {
+    let values: Vec<&str> = list.split(',').map(str::trim).collect();
+    if values.len() != N {
+        return Err(format!("颜色 '{}' 应有 {} 个分量，实际为 {} 个", source, N, values.len()).into());
+    }
+    let mut out = [0.0; N];
+    for (slot, value) in out.iter_mut().zip(values) {
+        let v: f32 = value
+            .parse()
+            .map_err(|_| format!("颜色 '{}' 的分量 '{}' 不是数字", source, value))?;
+        if !(0.0..=1.0).contains(&v) {
+            return Err(format!("颜色 '{}' 的分量 {} 超出范围 0.0-1.0", source, v).into());
+        }
+        *slot = v;
+    }
+    Ok(out)
+}
+
+/// 将颜色分量转换为 PDF 实数操作数
+fn reals(values: &[f32]) -> Vec<Object> {
+    values.iter().map(|&v| v.into()).collect()
+}
+
+/// 透明混合模式（ExtGState `/BM`）
+///
+/// # 说明
+/// 决定水印颜色与页面已有内容的合成方式。深色扫描件上常用 [`BlendMode::Screen`]
+/// 或 [`BlendMode::Overlay`]，浅色文档上 [`BlendMode::Multiply`] 可让水印不遮挡文字。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// 普通覆盖（默认，不写入 `/BM`）
+    #[default]
+    Normal,
+    /// 正片叠底：结果不会比任一颜色更亮，白色背景不受影响
+    Multiply,
+    /// 滤色：结果不会比任一颜色更暗，适合深色背景
+    Screen,
+    /// 叠加：按背景明暗分别做正片叠底或滤色
+    Overlay,
+    /// 变暗：逐分量取较暗者
+    Darken,
+    /// 变亮：逐分量取较亮者
+    Lighten,
+    /// 颜色减淡
+    ColorDodge,
+    /// 颜色加深
+    ColorBurn,
+    /// 强光
+    HardLight,
+    /// 柔光
+    SoftLight,
+    /// 差值
+    Difference,
+    /// 排除
+    Exclusion,
+    /// 色相
+    Hue,
+    /// 饱和度
+    Saturation,
+    /// 颜色
+    Color,
+    /// 明度
+    Luminosity,
+}
+
+impl BlendMode {
+    /// 所有混合模式
+    const ALL: [BlendMode; 16] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+        BlendMode::ColorDodge,
+        BlendMode::ColorBurn,
+        BlendMode::HardLight,
+        BlendMode::SoftLight,
+        BlendMode::Difference,
+        BlendMode::Exclusion,
+        BlendMode::Hue,
+        BlendMode::Saturation,
+        BlendMode::Color,
+        BlendMode::Luminosity,
+    ];
+
+    /// PDF 中的混合模式名称（如 `Multiply`）
+    pub fn pdf_name(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Screen => "Screen",
+            BlendMode::Overlay => "Overlay",
+            BlendMode::Darken => "Darken",
+            BlendMode::Lighten => "Lighten",
+            BlendMode::ColorDodge => "ColorDodge",
+            BlendMode::ColorBurn => "ColorBurn",
+            BlendMode::HardLight => "HardLight",
+            BlendMode::SoftLight => "SoftLight",
+            BlendMode::Difference => "Difference",
+            BlendMode::Exclusion => "Exclusion",
+            BlendMode::Hue => "Hue",
+            BlendMode::Saturation => "Saturation",
+            BlendMode::Color => "Color",
+            BlendMode::Luminosity => "Luminosity",
+        }
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = Box<dyn std::error::Error>;
+
+    /// 按 PDF 名称解析（不区分大小写，可含 `-`/`_`，如 `multiply`、`color-dodge`）
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key: String = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        BlendMode::ALL
+            .into_iter()
+            .find(|mode| mode.pdf_name().eq_ignore_ascii_case(&key))
+            .ok_or_else(|| {
+                let names: Vec<&str> = BlendMode::ALL.iter().map(|m| m.pdf_name()).collect();
+                format!("未知的混合模式 '{}'（支持 {}）", s, names.join("、")).into()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(s: &str) -> String {
+        s.parse::<WatermarkColor>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!("#cc0000".parse::<WatermarkColor>().unwrap(), WatermarkColor::Rgb([0.8, 0.0, 0.0]));
+        assert_eq!("#C00".parse::<WatermarkColor>().unwrap(), WatermarkColor::Rgb([0.8, 0.0, 0.0]));
+        assert_eq!(" 00ff33 ".parse::<WatermarkColor>().unwrap(), WatermarkColor::Rgb([0.0, 1.0, 0.2]));
+        assert_eq!(WatermarkColor::from_hex("#FFFFFF").unwrap(), WatermarkColor::Rgb([1.0; 3]));
+    }
+
+    #[test]
+    fn rejects_bad_hex() {
+        for s in ["#zz", "#ccc0", "#", "", "#1234567", "#ｃｃｃ"] {
+            assert_eq!(parse_err(s), format!("无效的十六进制颜色 '{}'，应为 #RRGGBB 或 #RGB", s));
+        }
+    }
+
+    #[test]
+    fn parses_prefixed_colors() {
+        assert_eq!("gray:0.5".parse::<WatermarkColor>().unwrap(), WatermarkColor::Gray(0.5));
+        assert_eq!("GREY: 1".parse::<WatermarkColor>().unwrap(), WatermarkColor::Gray(1.0));
+        assert_eq!(
+            "rgb:0.8, 0 ,0.25".parse::<WatermarkColor>().unwrap(),
+            WatermarkColor::Rgb([0.8, 0.0, 0.25])
+        );
+        assert_eq!(
+            "cmyk:0,0.8,1,0".parse::<WatermarkColor>().unwrap(),
+            WatermarkColor::Cmyk([0.0, 0.8, 1.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn parses_spot_names_with_colons() {
+        assert_eq!(
+            "spot:PANTONE 185 C:0,0.91,0.76,0".parse::<WatermarkColor>().unwrap(),
+            WatermarkColor::Spot {
+                name: "PANTONE 185 C".to_string(),
+                alternate: [0.0, 0.91, 0.76, 0.0],
+            }
+        );
+        assert_eq!(
+            "spot:Brand:Red:0,1,1,0".parse::<WatermarkColor>().unwrap(),
+            WatermarkColor::Spot {
+                name: "Brand:Red".to_string(),
+                alternate: [0.0, 1.0, 1.0, 0.0],
+            }
+        );
+    }
+
+    #[test]
+    fn reports_component_errors() {
+        assert_eq!(parse_err("rgb:1,0"), "颜色 'rgb:1,0' 应有 3 个分量，实际为 2 个");
+        assert_eq!(parse_err("cmyk:0,0,0,0,0"), "颜色 'cmyk:0,0,0,0,0' 应有 4 个分量，实际为 5 个");
+        assert_eq!(parse_err("gray:half"), "颜色 'gray:half' 的分量 'half' 不是数字");
+        assert_eq!(parse_err("rgb:1,1.5,0"), "颜色 'rgb:1,1.5,0' 的分量 1.5 超出范围 0.0-1.0");
+        assert_eq!(parse_err("gray:-0.1"), "颜色 'gray:-0.1' 的分量 -0.1 超出范围 0.0-1.0");
+        assert_eq!(parse_err("gray:NaN"), "颜色 'gray:NaN' 的分量 NaN 超出范围 0.0-1.0");
+    }
+
+    #[test]
+    fn reports_spot_and_kind_errors() {
+        assert_eq!(
+            parse_err("spot:PANTONE 185 C"),
+            "专色 'spot:PANTONE 185 C' 缺少 CMYK 替代色，应为 spot:名称:c,m,y,k"
+        );
+        assert_eq!(parse_err("spot: :0,0,0,1"), "专色 'spot: :0,0,0,1' 的名称为空");
+        assert_eq!(
+            parse_err("hsl:0,1,0.5"),
+            "未知的颜色类型 'hsl'（支持 #RRGGBB、gray:、rgb:、cmyk:、spot:）"
+        );
+    }
+
+    #[test]
+    fn parses_blend_modes() {
+        assert_eq!("multiply".parse::<BlendMode>().unwrap(), BlendMode::Multiply);
+        assert_eq!("Color-Dodge".parse::<BlendMode>().unwrap(), BlendMode::ColorDodge);
+        assert_eq!("soft_light".parse::<BlendMode>().unwrap(), BlendMode::SoftLight);
+        assert!(
+            "glow".parse::<BlendMode>().unwrap_err().to_string().starts_with("未知的混合模式 'glow'（支持 Normal、")
+        );
+    }
+}
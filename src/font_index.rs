@@ -0,0 +1,761 @@
+//! 系统字体发现 - 按字体族/样式名查找字体文件
+//!
+//! 扫描标准字体目录（`/usr/share/fonts`、`~/.fonts`、fontconfig 配置中的 `<dir>` 等），
+//! 读取每个字体（含 `.ttc`/`.otc` 集合中的每个字面）的 `name` 表，
+//! 使部署配置可以直接写 `"Noto Sans CJK SC Bold"` 而不是具体文件路径。
+//!
+//! 扫描结果按 (路径, 修改时间, 大小) 缓存到磁盘，之后只重新解析新增或变化的文件。
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+// ============================================================================
+// 常量定义 - Constants
+// ============================================================================
+
+/// 索引缓存文件的格式标识（格式变化时递增，旧缓存自动失效）
+const CACHE_HEADER: &str = "water_mark-font-index v1";
+
+/// 缓存文件中多个名称之间的分隔符
+const NAME_SEPARATOR: char = '\u{1f}';
+
+/// 参与扫描的字体文件扩展名（小写）
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// fontconfig 主配置文件
+const FONTCONFIG_FILE: &str = "/etc/fonts/fonts.conf";
+
+/// fontconfig 附加配置目录
+const FONTCONFIG_CONF_DIR: &str = "/etc/fonts/conf.d";
+
+/// 进程内共享的系统字体索引
+static SYSTEM_INDEX: Mutex<Option<Arc<FontIndex>>> = Mutex::new(None);
+
+// ============================================================================
+// 字体条目与索引 - FontEntry / FontIndex
+// ============================================================================
+
+/// 索引中的单个字面
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontEntry {
+    /// 字体文件路径
+    pub path: PathBuf,
+    /// 字面在字体集合（`.ttc`/`.otc`）中的索引，普通字体为 0
+    pub index: u32,
+    /// 字体族名（优先英文的排版族名，如 `Noto Sans CJK SC`）
+    pub family: String,
+    /// 样式名（如 `Bold`、`Regular`）
+    pub style: String,
+    /// 字重（100-900，400 为常规）
+    pub weight: u16,
+    /// 是否为斜体
+    pub italic: bool,
+    /// 所有语言下的字体族名（用于按族名匹配，含本地化名称如 `思源黑体`）
+    families: Vec<String>,
+    /// 所有可完整匹配的名称（全名、PostScript 名、族名 + 样式名）
+    names: Vec<String>,
+}
+
+/// 字体索引
+///
+/// # 说明
+/// 一般通过 [`FontIndex::system`] 获取进程内共享的系统字体索引；
+/// 也可以用 [`FontIndex::scan`] 扫描自定义目录。
+#[derive(Debug, Clone, Default)]
+pub struct FontIndex {
+    entries: Vec<FontEntry>,
+}
+
+impl FontIndex {
+    /// 获取系统字体索引（首次调用时扫描，之后复用）
+    pub fn system() -> Arc<FontIndex> {
+        let mut guard = SYSTEM_INDEX.lock().unwrap_or_else(|e| e.into_inner());
+        guard
+            .get_or_insert_with(|| Arc::new(Self::scan_with_cache(&font_search_dirs(), cache_path())))
+            .clone()
+    }
+
+    /// 重新扫描系统字体目录（安装新字体后调用）
+    pub fn refresh_system() -> Arc<FontIndex> {
+        let index = Arc::new(Self::scan_with_cache(&font_search_dirs(), cache_path()));
+        *SYSTEM_INDEX.lock().unwrap_or_else(|e| e.into_inner()) = Some(index.clone());
+        index
+    }
+
+    /// 扫描指定目录（递归），不使用磁盘缓存
+    pub fn scan<P: AsRef<Path>>(dirs: &[P]) -> FontIndex {
+        let dirs: Vec<PathBuf> = dirs.iter().map(|d| d.as_ref().to_path_buf()).collect();
+        Self::scan_with_cache(&dirs, None)
+    }
+
+    /// 扫描目录；提供缓存路径时复用未变化文件的解析结果并回写缓存
+    fn scan_with_cache(dirs: &[PathBuf], cache: Option<PathBuf>) -> FontIndex {
+        let cached = cache.as_deref().map(read_cache).unwrap_or_default();
+
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        for dir in dirs {
+            collect_font_files(dir, &mut visited, &mut files);
+        }
+
+        let mut records = Vec::with_capacity(files.len());
+        for (path, stamp) in files {
+            let entries = match cached.get(&path) {
+                Some((cached_stamp, entries)) if *cached_stamp == stamp => entries.clone(),
+                _ => match std::fs::read(&path) {
+                    Ok(data) => parse_font_entries(&path, &data),
+                    Err(_) => continue,
+                },
+            };
+            records.push((path, stamp, entries));
+        }
+
+        if let Some(cache) = cache {
+            // 缓存只是加速手段，写入失败（如只读文件系统）不影响查找
+            let _ = write_cache(&cache, &records);
+        }
+
+        FontIndex {
+            entries: records.into_iter().flat_map(|(_, _, entries)| entries).collect(),
+        }
+    }
+
+    /// 所有已索引的字面
+    pub fn entries(&self) -> &[FontEntry] {
+        &self.entries
+    }
+
+    /// 按名称查找字体
+    ///
+    /// # 参数
+    /// - `name`: 全名（`Noto Sans CJK SC Bold`）、PostScript 名（`NotoSansCJKsc-Bold`）
+    ///   或字体族名（`Noto Sans CJK SC`）；忽略大小写、空格、`-` 与 `_`
+    ///
+    /// # 说明
+    /// 优先完整匹配；只给出族名时选择该族中最接近常规（字重 400、非斜体）的字面。
+    pub fn find(&self, name: &str) -> Option<&FontEntry> {
+        find_entry(&self.entries, name)
+    }
+}
+
+/// 列出字体文件中的所有字面
+///
+/// # 说明
+/// 普通字体返回一个条目；字体集合（`.ttc`/`.otc`）中每个字面各返回一个条目，
+/// 可用于确定 [`crate::Watermarker::from_collection`] 需要的字面索引。
+pub fn collection_faces<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<FontEntry>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+    Ok(parse_font_entries(path, &data))
+}
+
+/// 在条目列表中按名称查找字面（规则见 [`FontIndex::find`]）
+pub(crate) fn find_entry<'a>(entries: &'a [FontEntry], name: &str) -> Option<&'a FontEntry> {
+    let query = normalize_name(name);
+    if query.is_empty() {
+        return None;
+    }
+    if let Some(entry) = entries
+        .iter()
+        .find(|e| e.names.iter().any(|n| normalize_name(n) == query))
+    {
+        return Some(entry);
+    }
+    entries
+        .iter()
+        .filter(|e| e.families.iter().any(|f| normalize_name(f) == query))
+        .min_by_key(|e| (e.italic, (i32::from(e.weight) - 400).abs()))
+}
+
+// ============================================================================
+// 字体文件解析 - Name Table
+// ============================================================================
+
+/// 解析字体文件中的所有字面（字体集合中的每个字面各占一个条目）
+pub(crate) fn parse_font_entries(path: &Path, data: &[u8]) -> Vec<FontEntry> {
+    let face_count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+    (0..face_count)
+        .filter_map(|index| {
+            let face = ttf_parser::Face::parse(data, index).ok()?;
+            face_entry(path, index, &face)
+        })
+        .collect()
+}
+
+/// 从字面的 `name` 表提取名称信息
+fn face_entry(path: &Path, index: u32, face: &ttf_parser::Face) -> Option<FontEntry> {
+    use ttf_parser::name_id;
+
+    // 按 name ID 收集各语言的名称，英文（Windows 0x0409 / Mac 0）排在最前
+    let mut by_id: HashMap<u16, Vec<String>> = HashMap::new();
+    for record in face.names() {
+        let Some(value) = record.to_string() else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+        let list = by_id.entry(record.name_id).or_default();
+        if list.contains(&value) {
+            continue;
+        }
+        if matches!(record.language_id, 0x0409 | 0) {
+            list.insert(0, value);
+        } else {
+            list.push(value);
+        }
+    }
+    let get = |id: u16| by_id.get(&id).cloned().unwrap_or_default();
+
+    // 排版族名 / 样式名（ID 16/17）优先，缺失时使用旧式族名 / 样式名（ID 1/2）
+    let typographic_families = get(name_id::TYPOGRAPHIC_FAMILY);
+    let legacy_families = get(name_id::FAMILY);
+    let typographic_styles = get(name_id::TYPOGRAPHIC_SUBFAMILY);
+    let legacy_styles = get(name_id::SUBFAMILY);
+
+    let family = typographic_families
+        .first()
+        .or(legacy_families.first())?
+        .clone();
+    let style = typographic_styles
+        .first()
+        .or(legacy_styles.first())
+        .cloned()
+        .unwrap_or_else(|| "Regular".to_string());
+
+    let mut families: Vec<String> = typographic_families.clone();
+    families.extend(legacy_families.iter().cloned());
+    dedup_names(&mut families);
+
+    let mut names = get(name_id::FULL_NAME);
+    names.extend(get(name_id::POST_SCRIPT_NAME));
+    for (fams, styles) in [
+        (&typographic_families, &typographic_styles),
+        (&legacy_families, &legacy_styles),
+    ] {
+        for f in fams {
+            for s in styles {
+                names.push(format!("{} {}", f, s));
+            }
+        }
+    }
+    dedup_names(&mut names);
+
+    Some(FontEntry {
+        path: path.to_path_buf(),
+        index,
+        family,
+        style,
+        weight: face.weight().to_number(),
+        italic: face.is_italic(),
+        families,
+        names,
+    })
+}
+
+/// 去除重复名称（保留首次出现的顺序）
+fn dedup_names(names: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    names.retain(|n| seen.insert(n.clone()));
+}
+
+/// 名称归一化：忽略大小写、空白、`-` 与 `_`
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// ============================================================================
+// 字体目录 - Search Directories
+// ============================================================================
+
+/// 默认的字体搜索目录
+///
+/// # 说明
+/// 依次为 `/usr/share/fonts`、`/usr/local/share/fonts`、`$XDG_DATA_DIRS/fonts`、
+/// `~/.fonts`、`~/.local/share/fonts`，以及 fontconfig 配置中声明的 `<dir>`。
+/// 不存在的目录会在扫描时被跳过。
+pub fn font_search_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+    ];
+    if let Some(data_dirs) = std::env::var_os("XDG_DATA_DIRS") {
+        dirs.extend(std::env::split_paths(&data_dirs).map(|d| d.join("fonts")));
+    }
+    if let Some(home) = &home {
+        dirs.push(home.join(".fonts"));
+    }
+    dirs.push(xdg_data_home(home.as_deref()).join("fonts"));
+    dirs.extend(fontconfig_dirs(home.as_deref()));
+
+    let mut seen = HashSet::new();
+    dirs.retain(|d| seen.insert(d.clone()));
+    dirs
+}
+
+/// `$XDG_DATA_HOME`，缺省为 `~/.local/share`
+fn xdg_data_home(home: Option<&Path>) -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.map(|h| h.join(".local/share")))
+        .unwrap_or_default()
+}
+
+/// 读取 fontconfig 配置（主配置文件与 conf.d）中的 `<dir>` 声明
+fn fontconfig_dirs(home: Option<&Path>) -> Vec<PathBuf> {
+    let mut conf_files = vec![PathBuf::from(FONTCONFIG_FILE)];
+    if let Ok(read_dir) = std::fs::read_dir(FONTCONFIG_CONF_DIR) {
+        let mut extra: Vec<PathBuf> = read_dir
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "conf"))
+            .collect();
+        extra.sort();
+        conf_files.extend(extra);
+    }
+
+    let data_home = xdg_data_home(home);
+    conf_files
+        .iter()
+        .filter_map(|conf| std::fs::read_to_string(conf).ok())
+        .flat_map(|xml| parse_fontconfig_dirs(&xml, home, &data_home))
+        .collect()
+}
+
+/// 从一个 fontconfig 配置文件的内容中提取 `<dir>` 声明
+///
+/// # 说明
+/// `prefix="xdg"` 的目录相对于 `data_home`；`~/` 开头的目录相对于 `home`（未知时跳过）。
+fn parse_fontconfig_dirs(xml: &str, home: Option<&Path>, data_home: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<dir") {
+        rest = &rest[start + 4..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attrs = &rest[..tag_end];
+        // 跳过 <dirname> 之类的其他元素与自闭合标签
+        if !(attrs.is_empty() || attrs.starts_with(char::is_whitespace)) || attrs.ends_with('/') {
+            continue;
+        }
+        let Some(close) = rest.find("</dir>") else {
+            break;
+        };
+        let value = rest[tag_end + 1..close].trim();
+        rest = &rest[close..];
+
+        let dir = if attrs.contains("prefix=\"xdg\"") {
+            data_home.join(value)
+        } else if let Some(stripped) = value.strip_prefix("~/") {
+            match home {
+                Some(h) => h.join(stripped),
+                None => continue,
+            }
+        } else {
+            PathBuf::from(value)
+        };
+        if !value.is_empty() {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// 文件的变化标识：(修改时间秒数, 文件大小)
+type FileStamp = (u64, u64);
+
+/// 递归收集目录下的字体文件（跟随符号链接，规避循环）
+fn collect_font_files(dir: &Path, visited: &mut HashSet<PathBuf>, out: &mut Vec<(PathBuf, FileStamp)>) {
+    let Ok(canonical) = dir.canonicalize() else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<PathBuf> = read_dir.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    children.sort();
+    for path in children {
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            collect_font_files(&path, visited, out);
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| FONT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if !is_font {
+            continue;
+        }
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        out.push((path, (mtime, meta.len())));
+    }
+}
+
+// ============================================================================
+// 磁盘缓存 - Index Cache
+// ============================================================================
+
+/// 缓存文件位置：`$XDG_CACHE_HOME/water_mark/font-index.tsv`（缺省为 `~/.cache`）
+fn cache_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(base.join("water_mark").join("font-index.tsv"))
+}
+
+/// 读取缓存：路径 -> (变化标识, 字面列表)
+///
+/// # 说明
+/// 缓存每行一个字面，字段以制表符分隔：
+/// `路径  修改时间  大小  索引  字重  斜体  族名  样式名  所有族名  所有名称`。
+/// 格式不符或损坏的行直接忽略（对应文件会被重新解析）。
+fn read_cache(path: &Path) -> HashMap<PathBuf, (FileStamp, Vec<FontEntry>)> {
+    let mut cached: HashMap<PathBuf, (FileStamp, Vec<FontEntry>)> = HashMap::new();
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return cached;
+    };
+    let mut lines = content.lines();
+    if lines.next() != Some(CACHE_HEADER) {
+        return cached;
+    }
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [path, mtime, size, index, weight, italic, family, style, families, names] = fields[..] else {
+            continue;
+        };
+        let (Ok(mtime), Ok(size), Ok(index), Ok(weight)) =
+            (mtime.parse(), size.parse(), index.parse(), weight.parse())
+        else {
+            continue;
+        };
+        let split = |s: &str| -> Vec<String> {
+            s.split(NAME_SEPARATOR)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let path = PathBuf::from(path);
+        let record = cached
+            .entry(path.clone())
+            .or_insert_with(|| ((mtime, size), Vec::new()));
+        record.1.push(FontEntry {
+            path,
+            index,
+            family: family.to_string(),
+            style: style.to_string(),
+            weight,
+            italic: italic == "1",
+            families: split(families),
+            names: split(names),
+        });
+    }
+    cached
+}
+
+/// 写入缓存（先写临时文件再重命名，避免并发进程读到半个文件）
+fn write_cache(path: &Path, records: &[(PathBuf, FileStamp, Vec<FontEntry>)]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let clean = |s: &str| s.replace(['\t', '\n', '\r', NAME_SEPARATOR], " ");
+    let join = |list: &[String]| {
+        list.iter()
+            .map(|n| clean(n))
+            .collect::<Vec<_>>()
+            .join(&NAME_SEPARATOR.to_string())
+    };
+
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        writeln!(file, "{}", CACHE_HEADER)?;
+        for (font_path, (mtime, size), entries) in records {
+            // 路径含制表符或换行的文件不写入缓存（下次重新解析即可）
+            let Some(font_path) = font_path.to_str().filter(|p| !p.contains(['\t', '\n', '\r'])) else {
+                continue;
+            };
+            for e in entries {
+                writeln!(
+                    file,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    font_path,
+                    mtime,
+                    size,
+                    e.index,
+                    e.weight,
+                    if e.italic { 1 } else { 0 },
+                    clean(&e.family),
+                    clean(&e.style),
+                    join(&e.families),
+                    join(&e.names),
+                )?;
+            }
+        }
+        file.flush()?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_font::{collection, TestFont};
+
+    fn entry(index: u32, family: &str, style: &str, weight: u16, italic: bool) -> FontEntry {
+        let full = format!("{} {}", family, style);
+        FontEntry {
+            path: PathBuf::from("/fonts/test.ttc"),
+            index,
+            family: family.to_string(),
+            style: style.to_string(),
+            weight,
+            italic,
+            families: vec![family.to_string()],
+            names: vec![full.clone(), full.replace(' ', "")],
+        }
+    }
+
+    fn found(entries: &[FontEntry], name: &str) -> Option<u32> {
+        find_entry(entries, name).map(|e| e.index)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("water_mark_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // ------------------------------------------------------------------
+    // 名称匹配
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn normalizes_names() {
+        assert_eq!(normalize_name("Noto Sans CJK-SC_Bold"), "notosanscjkscbold");
+        assert_eq!(normalize_name(" 思源 黑体 "), "思源黑体");
+        assert_eq!(normalize_name("ÄB"), "äb");
+        assert_eq!(normalize_name(" -_ "), "");
+    }
+
+    #[test]
+    fn find_prefers_full_name_match() {
+        let mut entries = vec![
+            entry(0, "Noto Sans CJK SC", "Regular", 400, false),
+            entry(1, "Noto Sans CJK SC", "Bold", 700, false),
+            entry(2, "Noto Sans CJK SC Bold", "Regular", 400, false),
+        ];
+        entries[1].names.push("NotoSansCJKsc-Bold".to_string());
+        // 全名匹配优先于族名匹配，多个全名匹配时取第一个
+        assert_eq!(found(&entries, "Noto Sans CJK SC Bold"), Some(1));
+        assert_eq!(found(&entries, "notosans-cjk_sc bold"), Some(1));
+        assert_eq!(found(&entries, "NotoSansCJKsc-Bold"), Some(1));
+        assert_eq!(found(&entries, "Noto Sans CJK SC Regular"), Some(0));
+    }
+
+    #[test]
+    fn find_by_family_picks_closest_to_regular() {
+        let mut entries = vec![
+            entry(0, "Serif", "Bold", 700, false),
+            entry(1, "Serif", "Italic", 400, true),
+            entry(2, "Serif", "Medium", 500, false),
+            entry(3, "Serif", "Light", 300, false),
+        ];
+        // 非斜体优先；字重与 400 的差距相同时取先出现的
+        assert_eq!(found(&entries, "serif"), Some(2));
+        entries.push(entry(4, "Serif", "Regular", 400, false));
+        assert_eq!(found(&entries, "Serif"), Some(4));
+        // 只有斜体时也能按族名找到
+        assert_eq!(found(&entries[1..2], "Serif"), Some(1));
+    }
+
+    #[test]
+    fn find_matches_localized_families() {
+        let mut entries = vec![entry(0, "Source Han Sans SC", "Regular", 400, false)];
+        entries[0].families.push("思源黑体".to_string());
+        assert_eq!(found(&entries, "思源 黑体"), Some(0));
+        assert_eq!(found(&entries, "思源宋体"), None);
+        assert_eq!(found(&entries, ""), None);
+        assert_eq!(found(&entries, " - "), None);
+        assert_eq!(found(&[], "Source Han Sans SC"), None);
+    }
+
+    #[test]
+    fn parses_name_tables_of_collection_faces() {
+        let data = collection(&[
+            TestFont::new("a").named("Test Serif", "Regular"),
+            TestFont::new("a").named("Test Serif", "Bold"),
+        ]);
+        let path = Path::new("/fonts/serif.ttc");
+        let entries = parse_font_entries(path, &data);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[1].index, entries[1].family.as_str(), entries[1].style.as_str()), (1, "Test Serif", "Bold"));
+        assert_eq!(entries[1].path, path);
+        assert_eq!(entries[1].families, ["Test Serif"]);
+        assert_eq!(entries[1].names, ["Test Serif Bold", "TestSerif-Bold"]);
+        assert_eq!(found(&entries, "TestSerif-Bold"), Some(1));
+        assert_eq!(found(&entries, "Test Serif"), Some(0));
+        assert!(parse_font_entries(path, b"not a font").is_empty());
+    }
+
+    // ------------------------------------------------------------------
+    // fontconfig
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn parses_fontconfig_dirs() {
+        let xml = r#"<?xml version="1.0"?>
+<fontconfig>
+    <dir>/usr/share/fonts</dir>
+    <dir prefix="xdg">fonts</dir>
+    <dir prefix="default"> /opt/fonts </dir>
+    <dir>~/.myfonts</dir>
+    <dir/>
+    <dir></dir>
+    <dirname>/not/a/dir</dirname>
+    <cachedir>/var/cache/fontconfig</cachedir>
+</fontconfig>"#;
+        let home = Path::new("/home/u");
+        let data_home = Path::new("/home/u/.local/share");
+        assert_eq!(
+            parse_fontconfig_dirs(xml, Some(home), data_home),
+            [
+                PathBuf::from("/usr/share/fonts"),
+                PathBuf::from("/home/u/.local/share/fonts"),
+                PathBuf::from("/opt/fonts"),
+                PathBuf::from("/home/u/.myfonts"),
+            ]
+        );
+        // 不知道主目录时跳过 ~/ 开头的目录
+        assert_eq!(
+            parse_fontconfig_dirs(xml, None, data_home),
+            [
+                PathBuf::from("/usr/share/fonts"),
+                PathBuf::from("/home/u/.local/share/fonts"),
+                PathBuf::from("/opt/fonts"),
+            ]
+        );
+        // 未闭合的元素
+        assert_eq!(parse_fontconfig_dirs("<dir>/a</dir><dir>/b", None, data_home), [PathBuf::from("/a")]);
+        assert!(parse_fontconfig_dirs("<dir", None, data_home).is_empty());
+    }
+
+    // ------------------------------------------------------------------
+    // 磁盘缓存
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn cache_round_trip() {
+        let dir = temp_dir("font_cache");
+        let cache = dir.join("sub/font-index.tsv");
+        let mut bold = entry(1, "Noto Sans CJK SC", "Bold", 700, true);
+        bold.families.push("思源黑体".to_string());
+        let records = vec![
+            (
+                PathBuf::from("/fonts/test.ttc"),
+                (1_700_000_000, 12345),
+                vec![entry(0, "Noto Sans CJK SC", "Regular", 400, false), bold],
+            ),
+            (PathBuf::from("/fonts/a.ttf"), (1, 2), vec![entry(0, "A", "Regular", 400, false)]),
+        ];
+        write_cache(&cache, &records).unwrap();
+
+        let cached = read_cache(&cache);
+        assert_eq!(cached.len(), 2);
+        for (path, stamp, entries) in &records {
+            let mut expected = entries.clone();
+            for e in &mut expected {
+                e.path = path.clone();
+            }
+            assert_eq!(cached.get(path), Some(&(*stamp, expected)));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_cleans_and_skips_unsafe_values() {
+        let dir = temp_dir("font_cache_clean");
+        let cache = dir.join("font-index.tsv");
+        let mut odd = entry(0, "Tab\tFamily", "Line\nStyle", 400, false);
+        odd.names = vec!["a\u{1f}b".to_string(), String::new()];
+        let records = vec![
+            (PathBuf::from("/fonts/odd.ttf"), (1, 1), vec![odd]),
+            (PathBuf::from("/fonts/tab\tname.ttf"), (1, 1), vec![entry(0, "B", "Regular", 400, false)]),
+        ];
+        write_cache(&cache, &records).unwrap();
+
+        let cached = read_cache(&cache);
+        assert_eq!(cached.len(), 1);
+        let (_, entries) = &cached[Path::new("/fonts/odd.ttf")];
+        assert_eq!((entries[0].family.as_str(), entries[0].style.as_str()), ("Tab Family", "Line Style"));
+        assert_eq!(entries[0].names, ["a b"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_ignores_foreign_headers_and_broken_lines() {
+        let dir = temp_dir("font_cache_broken");
+        let cache = dir.join("font-index.tsv");
+        std::fs::write(&cache, "water_mark-font-index v0\n/a.ttf\t1\t1\t0\t400\t0\tA\tRegular\tA\tA Regular\n").unwrap();
+        assert!(read_cache(&cache).is_empty());
+
+        let content = format!(
+            "{}\n/a.ttf\t1\t1\t0\t400\t0\tA\tRegular\tA\tA Regular\n/b.ttf\tx\t1\t0\t400\t0\tB\tRegular\tB\tB\n/c.ttf\t1\t1\n",
+            CACHE_HEADER
+        );
+        std::fs::write(&cache, content).unwrap();
+        let cached = read_cache(&cache);
+        assert_eq!(cached.keys().collect::<Vec<_>>(), [Path::new("/a.ttf")]);
+        assert!(read_cache(&dir.join("missing.tsv")).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scan_reuses_cache_only_for_unchanged_files() {
+        let dir = temp_dir("font_scan");
+        let fonts = dir.join("fonts");
+        std::fs::create_dir_all(fonts.join("nested")).unwrap();
+        std::fs::write(fonts.join("nested/test.TTF"), TestFont::new("a").named("Test Sans", "Bold").build()).unwrap();
+        std::fs::write(fonts.join("notes.txt"), b"not a font").unwrap();
+        let cache = dir.join("font-index.tsv");
+        let scan = || FontIndex::scan_with_cache(std::slice::from_ref(&fonts), Some(cache.clone()));
+        let families = |index: &FontIndex| index.entries().iter().map(|e| e.family.clone()).collect::<Vec<_>>();
+
+        let index = scan();
+        assert_eq!(families(&index), ["Test Sans"]);
+        assert_eq!(index.find("Test Sans Bold").map(|e| e.path.clone()), Some(fonts.join("nested/test.TTF")));
+
+        // 变化标识相同：使用缓存中的条目（改名以区分来源）
+        let mut cached = read_cache(&cache);
+        assert_eq!(cached.len(), 1);
+        let (path, (stamp, mut entries)) = cached.drain().next().unwrap();
+        entries[0].family = "Cached Sans".to_string();
+        write_cache(&cache, &[(path.clone(), stamp, entries.clone())]).unwrap();
+        assert_eq!(families(&scan()), ["Cached Sans"]);
+
+        // 变化标识不同：重新解析文件，并回写缓存
+        let stale = (stamp.0, stamp.1 + 1);
+        write_cache(&cache, &[(path.clone(), stale, entries)]).unwrap();
+        assert_eq!(families(&scan()), ["Test Sans"]);
+        assert_eq!(read_cache(&cache)[&path].0, stamp);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,36 @@
-use ab_glyph::{Font, FontRef, PxScale, OutlineCurve, Point, ScaleFont};
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, OutlineCurve, Point, ScaleFont};
 use lopdf::content::{Content, Operation};
 use lopdf::{Document, Object, ObjectId, Stream};
 use lopdf::dictionary;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
 use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+
+mod badge;
+mod color;
+mod font_index;
+mod manifest;
+mod pages;
+mod profile;
+mod shaping;
+#[cfg(test)]
+mod test_font;
+pub use badge::Badge;
+pub use color::{BlendMode, ColorCycle, Gradient, WatermarkColor};
+pub use font_index::{collection_faces, font_search_dirs, FontEntry, FontIndex};
+pub use manifest::{parse_manifest, read_manifest, ManifestFormat};
+pub use pages::PageSelection;
+pub use profile::{ConfigFormat, Profile, ProfileConfig};
+pub use shaping::{TextShaping, WritingMode};
+use shaping::{missing_chars, shape_text, LoadedFont, ShapedText};
 
 // ============================================================================
 // 常量定义 - Constants
@@ -22,9 +48,12 @@ const GRID_HORIZONTAL_GAP: f32 = 30.0;
 /// 注意：这个值故意设置得比水平间距大，以避免垂直方向的水印过于密集
 const GRID_VERTICAL_MULTIPLIER: f32 = 6.0;
 
-/// 水印旋转角度（度数）
+/// 默认水印旋转角度（度数）
 const WATERMARK_ANGLE_DEG: f32 = 60.0;
 
+/// 默认水印透明度（填充与描边）
+const DEFAULT_OPACITY: f32 = 0.1;
+
 /// 水印网格中心在页面X轴的偏移（用于视觉居中调整）
 /// 根据字体和角度微调，使水印视觉上更居中
 const CENTER_X_OFFSET: f32 = 0.0;
@@ -32,15 +61,6 @@ const CENTER_X_OFFSET: f32 = 0.0;
 /// 水印网格中心在页面Y轴的偏移（用于视觉居中调整）
 const CENTER_Y_OFFSET: f32 = 0.0;
 
-/// 覆盖范围倍数（相对于页面对角线长度）
-/// 较大的值能确保页面各个角落都被水印覆盖，但也会增加计算量
-/// 建议范围：1.5 ~ 2.5
-const COVERAGE_MULTIPLIER: f32 = 1.5;
-
-/// 可见性裁剪边界（单位：点数）
-/// 超出此边界外的水印将被忽略，以避免渲染页面外的内容
-const VISIBILITY_MARGIN: f32 = 50.0;
-
 /// 单个PDF允许的最大水印数量
 /// 防止极端情况（极小的页面或间距）导致生成过多水印对象
 const MAX_ALLOWED_WATERMARKS: usize = 1_000_000;
@@ -49,6 +69,49 @@ const MAX_ALLOWED_WATERMARKS: usize = 1_000_000;
 /// 如果水平或垂直间距小于此值，拒绝生成以避免过度计算
 const MIN_GRID_STEP_SIZE: f32 = 0.1;
 
+/// 默认水印模板，`{name}` 与 `{date}` 为占位符
+///
+/// 模板还支持时间占位符（见 [`Watermarker::apply`]）与页码占位符 `{page}` / `{pages}`。
+pub const DEFAULT_TEMPLATE: &str = "致{name}-{date}:高度保密";
+
+/// 时间占位符（`{now}` / `{file_mtime}`）未指定格式时使用的 strftime 格式
+const DEFAULT_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// 已轮廓化文本缓存的最大条目数
+/// 批量处理大量不同收件人时防止缓存无限增长，超出后整体清空
+const MAX_TEXT_CACHE_ENTRIES: usize = 256;
+
+/// 字形轮廓缓存的最大条目数
+/// 常用 CJK 字符集约数千字，足以覆盖长期运行服务中的绝大多数字形
+const MAX_GLYPH_CACHE_ENTRIES: usize = 20_000;
+
+/// XObject BBox 在实测轮廓包围盒之外的留白（点数）
+const BBOX_PADDING: f32 = 1.0;
+
+/// 旋转矩阵系数（`cm` 的前四个参数）的最低保留小数位数
+/// 坐标可以大胆舍入，但旋转系数舍入过多会导致水印整体变形
+const MATRIX_MIN_PRECISION: u8 = 4;
+
+/// 专色填充颜色在文本 XObject Resources > ColorSpace 中的资源名
+const FILL_COLOR_SPACE: &str = "CS1";
+
+/// 专色描边颜色的资源名
+const STROKE_COLOR_SPACE: &str = "CS2";
+
+/// 渐变填充在文本 XObject Resources > Shading 中的资源名
+const GRADIENT_SHADING: &str = "Sh1";
+
+/// 平铺图案模式下水印图案在页面 Resources > Pattern 中的资源名
+const PATTERN_NAME: &str = "WatermarkPattern1";
+
+/// 尖角连接描边的斜接限制（PDF `M`）
+/// 限制锐角处尖端的长度，同时决定 BBox 需要为描边预留的最大外扩
+const STROKE_MITER_LIMIT: f32 = 4.0;
+
+/// 页数达到此阈值时才并行生成各页的水印网格
+/// 页数较少时线程调度开销大于收益
+const PARALLEL_PAGE_THRESHOLD: usize = 16;
+
 // ============================================================================
 // FFI 接口 - C语言互操作
 // ============================================================================
@@ -64,7 +127,7 @@ const MIN_GRID_STEP_SIZE: f32 = 0.1;
 /// # 参数
 /// - `input_path`: 输入PDF文件路径
 /// - `output_path`: 输出PDF文件路径
-/// - `font_path`: 字体文件路径
+/// - `font_path`: 字体文件路径或系统字体名称；多个字体用换行符 `\n` 连接时依次作为回退字体
 /// - `user_name`: 用户名
 /// - `date_str`: 日期字符串
 ///
@@ -93,76 +156,329 @@ pub unsafe extern "C" fn add_pdf_watermark(
     }
 
     // 将 CStr 转为 &str 并校验 UTF-8
-    let input = unsafe {
-        match CStr::from_ptr(input_path).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                eprintln!("ERROR: Invalid UTF-8 in input_path");
-                return -3;
-            }
+    let (input, output, font_p, name, date) = match unsafe {
+        (
+            cstr_arg(input_path, "input_path"),
+            cstr_arg(output_path, "output_path"),
+            cstr_arg(font_path, "font_path"),
+            cstr_arg(user_name, "user_name"),
+            cstr_arg(date_str, "date_str"),
+        )
+    } {
+        (Ok(i), Ok(o), Ok(f), Ok(n), Ok(d)) => (i, o, f, n, d),
+        _ => return -3,
+    };
+
+    let vars = TemplateVars::from([
+        ("name".to_string(), name),
+        ("date".to_string(), date),
+    ]);
+
+    let result = Watermarker::from_specs(&font_spec_list(&font_p))
+        .and_then(|marker| marker.apply(&input, &output, &vars));
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("ERROR: add_pdf_watermark failed: {:?}", e);
+            -1
         }
+    }
+}
+
+/// 将 FFI 传入的字体参数拆分为回退链
+///
+/// # 说明
+/// 多个字体用换行符 `\n` 连接（兼容 `\r\n`），主字体在前，空行忽略；
+/// 每一项可以是文件路径、`路径#字面` 或系统字体名称。
+/// 不使用 `:` / `;` 分隔，因为它们都可能出现在合法的字体路径中。
+fn font_spec_list(font_path: &str) -> Vec<String> {
+    font_path
+        .lines()
+        .filter(|spec| !spec.trim().is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 创建可复用的水印器句柄（FFI）
+///
+/// # 说明
+/// 字体只加载一次，文本与字形轮廓缓存在多次 [`pdf_watermarker_apply`] 调用之间共享。
+/// 适合 PHP-FPM / 常驻服务进程长期持有一个句柄。
+/// 使用完毕后必须调用 [`pdf_watermarker_free`] 释放。
+/// `font_path` 的格式与 [`add_pdf_watermark`] 相同（多个回退字体用 `\n` 连接）。
+///
+/// # Safety
+///
+/// `font_path` 必须是有效的、以空字符结尾的 C 字符串。
+///
+/// # 返回值
+/// - 非空指针：水印器句柄
+/// - `NULL`：参数无效或字体加载失败
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_new(font_path: *const c_char) -> *mut Watermarker {
+    if font_path.is_null() {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_new");
+        return std::ptr::null_mut();
+    }
+    let Ok(font_p) = (unsafe { cstr_arg(font_path, "font_path") }) else {
+        return std::ptr::null_mut();
     };
-    let output = unsafe {
-        match CStr::from_ptr(output_path).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                eprintln!("ERROR: Invalid UTF-8 in output_path");
-                return -3;
-            }
+    match Watermarker::from_specs(&font_spec_list(&font_p)) {
+        Ok(marker) => Box::into_raw(Box::new(marker)),
+        Err(e) => {
+            eprintln!("ERROR: pdf_watermarker_new failed: {:?}", e);
+            std::ptr::null_mut()
         }
+    }
+}
+
+/// 使用字体集合中指定索引的字面创建水印器句柄（FFI）
+///
+/// # 说明
+/// 与 [`pdf_watermarker_new`] 相同，但使用 `.ttc`/`.otc` 字体集合中第 `face_index` 个字面
+/// （如 `msyh.ttc` 的 0 为微软雅黑、1 为 Microsoft YaHei UI）。
+/// 也可以在 [`pdf_watermarker_new`] 的 `font_path` 中写作 `路径#索引` 或 `路径#字面名称`。
+///
+/// # Safety
+///
+/// `font_path` 必须是有效的、以空字符结尾的 C 字符串（单个字体文件路径）。
+///
+/// # 返回值
+/// - 非空指针：水印器句柄
+/// - `NULL`：参数无效、索引越界或字体加载失败
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_new_face(
+    font_path: *const c_char,
+    face_index: u32,
+) -> *mut Watermarker {
+    if font_path.is_null() {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_new_face");
+        return std::ptr::null_mut();
+    }
+    let Ok(font_p) = (unsafe { cstr_arg(font_path, "font_path") }) else {
+        return std::ptr::null_mut();
     };
-    let font_p = unsafe {
-        match CStr::from_ptr(font_path).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                eprintln!("ERROR: Invalid UTF-8 in font_path");
-                return -3;
-            }
+    match Watermarker::from_collection(&font_p, face_index) {
+        Ok(marker) => Box::into_raw(Box::new(marker)),
+        Err(e) => {
+            eprintln!("ERROR: pdf_watermarker_new_face failed: {:?}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// 按配置文件中的命名方案创建水印器句柄（FFI）
+///
+/// # 说明
+/// 配置文件格式见 [`ProfileConfig`]。字体、模板、透明度等均取自方案，
+/// 方案中的 `vars` 作为模板默认变量，被 [`pdf_watermarker_apply`] 传入的 `name` / `date` 覆盖。
+/// 使用完毕后必须调用 [`pdf_watermarker_free`] 释放。
+///
+/// # Safety
+///
+/// `config_path` 与 `profile` 必须是有效的、以空字符结尾的 C 字符串。
+///
+/// # 返回值
+/// - 非空指针：水印器句柄
+/// - `NULL`：参数无效、配置有误（错误信息指出出错的键）或字体加载失败
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_from_profile(
+    config_path: *const c_char,
+    profile: *const c_char,
+) -> *mut Watermarker {
+    if config_path.is_null() || profile.is_null() {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_from_profile");
+        return std::ptr::null_mut();
+    }
+    let (Ok(config), Ok(profile)) = (unsafe {
+        (cstr_arg(config_path, "config_path"), cstr_arg(profile, "profile"))
+    }) else {
+        return std::ptr::null_mut();
+    };
+    match Watermarker::from_profile(&config, &profile) {
+        Ok(marker) => Box::into_raw(Box::new(marker)),
+        Err(e) => {
+            eprintln!("ERROR: pdf_watermarker_from_profile failed: {:?}", e);
+            std::ptr::null_mut()
         }
+    }
+}
+
+/// 设置水印器句柄的水印颜色（FFI）
+///
+/// # 说明
+/// `color` 的格式见 [`WatermarkColor`] 的字符串解析：`#RRGGBB`、`gray:0.5`、
+/// `cmyk:0,0.8,1,0`、`spot:名称:c,m,y,k` 等。设置后已缓存的文本会重新生成。
+///
+/// # Safety
+///
+/// - `handle` 必须是 [`pdf_watermarker_new`] / [`pdf_watermarker_new_face`] / [`pdf_watermarker_from_profile`] 返回且尚未释放的句柄
+/// - 设置期间不得有其他线程使用该句柄
+/// - `color` 必须是有效的、以空字符结尾的 C 字符串
+///
+/// # 返回值
+/// - `0`: 成功
+/// - `-1`: 颜色格式无效
+/// - `-2`: 空指针参数
+/// - `-3`: UTF-8编码错误
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_set_color(
+    handle: *mut Watermarker,
+    color: *const c_char,
+) -> i32 {
+    if handle.is_null() || color.is_null() {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_set_color");
+        return -2;
+    }
+    let Ok(color) = (unsafe { cstr_arg(color, "color") }) else {
+        return -3;
     };
-    let name = unsafe {
-        match CStr::from_ptr(user_name).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                eprintln!("ERROR: Invalid UTF-8 in user_name");
-                return -3;
-            }
+    match color.parse::<WatermarkColor>() {
+        Ok(color) => {
+            let marker = unsafe { &mut *handle };
+            marker.color = color;
+            marker.clear_caches();
+            0
+        }
+        Err(e) => {
+            eprintln!("ERROR: pdf_watermarker_set_color failed: {:?}", e);
+            -1
         }
+    }
+}
+
+/// 设置水印器句柄的混合模式（FFI）
+///
+/// # 说明
+/// `blend_mode` 为 PDF 混合模式名称（不区分大小写），如 `Multiply`、`Screen`、`Overlay`，
+/// 见 [`BlendMode`]。设置后已缓存的文本会重新生成。
+///
+/// # Safety
+///
+/// 与 [`pdf_watermarker_set_color`] 相同（`blend_mode` 替代 `color`）。
+///
+/// # 返回值
+/// - `0`: 成功
+/// - `-1`: 未知的混合模式
+/// - `-2`: 空指针参数
+/// - `-3`: UTF-8编码错误
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_set_blend_mode(
+    handle: *mut Watermarker,
+    blend_mode: *const c_char,
+) -> i32 {
+    if handle.is_null() || blend_mode.is_null() {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_set_blend_mode");
+        return -2;
+    }
+    let Ok(blend_mode) = (unsafe { cstr_arg(blend_mode, "blend_mode") }) else {
+        return -3;
     };
-    let date = unsafe {
-        match CStr::from_ptr(date_str).to_str() {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                eprintln!("ERROR: Invalid UTF-8 in date_str");
-                return -3;
-            }
+    match blend_mode.parse::<BlendMode>() {
+        Ok(mode) => {
+            let marker = unsafe { &mut *handle };
+            marker.blend_mode = mode;
+            marker.clear_caches();
+            0
+        }
+        Err(e) => {
+            eprintln!("ERROR: pdf_watermarker_set_blend_mode failed: {:?}", e);
+            -1
         }
+    }
+}
+
+/// 使用水印器句柄为单个文件添加水印（FFI）
+///
+/// # Safety
+///
+/// - `handle` 必须是 [`pdf_watermarker_new`] / [`pdf_watermarker_new_face`] / [`pdf_watermarker_from_profile`] 返回且尚未释放的句柄
+/// - 其余指针必须是有效的、以空字符结尾的 C 字符串
+/// - 同一句柄可被多个线程同时使用
+///
+/// # 返回值
+/// 与 [`add_pdf_watermark`] 相同
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_apply(
+    handle: *const Watermarker,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    user_name: *const c_char,
+    date_str: *const c_char,
+) -> i32 {
+    if handle.is_null()
+        || input_path.is_null()
+        || output_path.is_null()
+        || user_name.is_null()
+        || date_str.is_null()
+    {
+        eprintln!("ERROR: NULL pointer passed to pdf_watermarker_apply");
+        return -2;
+    }
+
+    let (input, output, name, date) = match unsafe {
+        (
+            cstr_arg(input_path, "input_path"),
+            cstr_arg(output_path, "output_path"),
+            cstr_arg(user_name, "user_name"),
+            cstr_arg(date_str, "date_str"),
+        )
+    } {
+        (Ok(i), Ok(o), Ok(n), Ok(d)) => (i, o, n, d),
+        _ => return -3,
     };
 
-    let text = format!("致{}-{}:高度保密", name, date);
+    let vars = TemplateVars::from([
+        ("name".to_string(), name),
+        ("date".to_string(), date),
+    ]);
 
-    match run_watermark_process(&input, &output, &font_p, &text) {
+    let marker = unsafe { &*handle };
+    match marker.apply(&input, &output, &vars) {
         Ok(_) => 0,
         Err(e) => {
-            eprintln!("ERROR: add_pdf_watermark failed: {:?}", e);
+            eprintln!("ERROR: pdf_watermarker_apply failed: {:?}", e);
             -1
         }
     }
 }
 
+/// 释放水印器句柄（FFI）
+///
+/// # Safety
+///
+/// `handle` 必须是 [`pdf_watermarker_new`] / [`pdf_watermarker_new_face`] / [`pdf_watermarker_from_profile`] 返回的句柄（或 `NULL`），且只能释放一次。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pdf_watermarker_free(handle: *mut Watermarker) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// 将 C 字符串参数转换为 Rust 字符串并校验 UTF-8
+///
+/// # Safety
+///
+/// `ptr` 必须非空且指向以空字符结尾的 C 字符串。
+unsafe fn cstr_arg(ptr: *const c_char, name: &str) -> Result<String, ()> {
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Ok(s.to_string()),
+        Err(_) => {
+            eprintln!("ERROR: Invalid UTF-8 in {}", name);
+            Err(())
+        }
+    }
+}
+
 // ============================================================================
 // 公共处理函数 - 供 main.rs 和 FFI 调用
 // ============================================================================
 
 /// 执行水印处理的主函数
 ///
-/// # 流程
-/// 1. 加载PDF文档
-/// 2. 读取并解析字体（只做一次）
-/// 3. 预计算文本矢量路径（只做一次）
-/// 4. 将文本作为XObject流对象嵌入PDF
-/// 5. 遍历所有页面，生成水印网格（考虑页面旋转）
-/// 6. 保存处理后的PDF
+/// # 说明
+/// 单次调用的便捷入口，内部创建一个临时 [`Watermarker`]。
+/// 需要处理大量文件时请直接复用 [`Watermarker`]，避免重复加载字体。
 ///
 /// # 参数
 /// - `input_path`: 输入PDF路径
@@ -171,251 +487,2405 @@ pub unsafe extern "C" fn add_pdf_watermark(
 /// - `text`: 水印文本
 ///
 /// # 返回
-/// - `Ok(String)`: 输出文件路径
+/// - `Ok(WatermarkReport)`: 处理结果（页数、处理前后文件大小）
 /// - `Err`: 处理过程中的错误信息
 pub fn run_watermark_process(
     input_path: &str,
     output_path: &str,
     font_path: &str,
     text: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // 加载 PDF
-    let mut doc = Document::load(input_path)?;
+) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+    Watermarker::from_file(font_path)?.apply_text(input_path, output_path, text)
+}
 
-    // 读取并解析字体（一次性）
-    let font_data = std::fs::read(font_path)?;
-    let font = FontRef::try_from_slice(&font_data)?;
+/// 检查PDF是否已由本库添加过水印
+///
+/// # 说明
+/// 任一页面的资源中含有本库使用的文本 XObject（`Watermark1`）或平铺图案（`WatermarkPattern1`）
+/// 即视为已添加。需要完整解析文档，大文件的耗时与一次加载相当。
+///
+/// # 返回
+/// - `Ok(true)`: 已有水印
+/// - `Err`: 文件无法读取或不是有效的PDF
+pub fn is_watermarked(path: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(document_is_watermarked(&Document::load(path)?))
+}
 
-    // 预计算文本矢量（只做一次）
-    let watermark_ops = text_to_pdf_paths(&font, text, 0.0, 0.0, DEFAULT_FONT_SIZE);
-    let watermark_content = Content {
-        operations: watermark_ops,
-    };
-    let encoded = watermark_content
-        .encode()
-        .map_err(|e| format!("encode watermark content failed: {:?}", e))?;
-    let watermark_stream = Stream::new(
-        dictionary! {
-            "Type" => "XObject",
-            "Subtype" => "Form",
-            // 使用常量而不是魔数（基于字体大小的 bbox）
-            "BBox" => vec![(-10).into(), (-50).into(), 2000.into(), 200.into()],
-            "Matrix" => vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), 0.into()],
-            "Resources" => dictionary! {
-                "ExtGState" => dictionary! {
-                    "GS1" => dictionary! {
-                        "Type" => "ExtGState",
-                        "ca" => 0.1f32, // fill alpha
-                        "CA" => 0.1f32, // stroke alpha
-                    }
-                }
-            },
-        },
-        encoded,
-    );
+/// 文档中是否有页面引用了本库的水印资源（见 [`is_watermarked`]）
+fn document_is_watermarked(doc: &Document) -> bool {
+    for page_id in doc.get_pages().into_values() {
+        let Ok(resources) = effective_resources(doc, page_id) else {
+            continue;
+        };
+        for (category, name) in [("XObject", "Watermark1"), ("Pattern", PATTERN_NAME)] {
+            let entries = match resources.get(category.as_bytes()) {
+                Ok(Object::Dictionary(d)) => Some(d),
+                Ok(Object::Reference(id)) => doc.get_dictionary(*id).ok(),
+                _ => None,
+            };
+            if entries.is_some_and(|d| d.has(name.as_bytes())) {
+                return true;
+            }
+        }
+    }
+    false
+}
 
-    let xobject_id = doc.add_object(watermark_stream);
-    let xobject_name = "Watermark1";
+// ============================================================================
+// 可复用水印器 - Watermarker
+// ============================================================================
 
-    // 预计算文本宽度，避免重复计算
-    let text_w = measure_text_width(&font, text, DEFAULT_FONT_SIZE);
+/// 模板变量表（占位符名 -> 替换值）
+pub type TemplateVars = HashMap<String, String>;
 
-    // 遍历页面并注入资源与内容
-    for (page_num, object_id) in doc.get_pages() {
-        let (w, h) = page_size(&doc, object_id).unwrap_or((595.0, 842.0));
+/// 批量处理中的单个任务
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    /// 输入PDF路径
+    pub input: String,
+    /// 输出PDF路径
+    pub output: String,
+    /// 用于渲染模板的变量
+    pub vars: TemplateVars,
+}
 
-        // 获取页面旋转角度（支持旋转PDF）
-        let page_rotation = get_page_rotation(&doc, object_id);
+/// 已解析的源PDF，可重复用于生成多份水印副本
+///
+/// # 说明
+/// 解析（含对象流解压）只在 [`SourceDocument::load`] 时进行一次，
+/// 每次 [`Watermarker::apply_source`] 从内存中的文档克隆生成副本，源文档本身不会被修改。
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{SourceDocument, TemplateVars, Watermarker};
+///
+/// let marker = Watermarker::from_file("font.otf")?;
+/// let source = SourceDocument::load("dataroom.pdf")?;
+/// for name in ["张三", "李四"] {
+///     let vars = TemplateVars::from([("name".to_string(), name.to_string())]);
+///     marker.apply_source(&source, &format!("out/{}.pdf", name), &vars)?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SourceDocument {
+    path: String,
+    doc: Document,
+    input_bytes: u64,
+}
 
-        // 添加XObject资源到页面
-        if let Err(e) = add_xobject_to_page(&mut doc, object_id, xobject_name, xobject_id) {
-            eprintln!(
-                "WARN: 第 {} 页结构非标准，无法注入资源。错误：{:?}",
-                page_num, e
-            );
-            continue;
-        }
+impl SourceDocument {
+    /// 读取并解析PDF文件
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let input_bytes = std::fs::metadata(path)?.len();
+        let doc = Document::load(path)?;
+        Ok(SourceDocument {
+            path: path.to_string(),
+            doc,
+            input_bytes,
+        })
+    }
 
-        // 生成水印网格操作（传入页面旋转角度）
-        let ops = match build_watermark_grid_ops_xobject_optimized(
-            xobject_name,
-            DEFAULT_FONT_SIZE,
-            WATERMARK_ANGLE_DEG,
-            w,
-            h,
-            text_w,
-            page_rotation,
-        ) {
-            Ok(v) => v,
-            Err(e) => {
-                eprintln!("WARN: 生成水印网格失败，跳过第 {} 页：{:?}", page_num, e);
-                continue;
-            }
-        };
+    /// 源文件路径
+    pub fn path(&self) -> &str {
+        &self.path
+    }
 
-        let content_ops = Content { operations: ops };
+    /// 页数
+    pub fn page_count(&self) -> usize {
+        self.doc.get_pages().len()
+    }
 
-        // 将水印内容添加到页面
-        if let Err(e) = doc.add_to_page_content(object_id, content_ops) {
-            eprintln!("WARN: 添加页面内容失败，跳过第 {} 页：{:?}", page_num, e);
-            continue;
-        }
+    /// 文档是否已由本库添加过水印（判断方式见 [`is_watermarked`]）
+    pub fn is_watermarked(&self) -> bool {
+        document_is_watermarked(&self.doc)
     }
+}
 
-    doc.save(output_path)?;
+/// 水印文本的实测尺寸（文本自身坐标系，原点为基线起点，单位：点数）
+///
+/// # 说明
+/// - `advance` 为各字形 advance 之和，决定网格沿书写方向的间距
+/// - 包围盒由实际字形轮廓计算，可能超出 `[0, advance]`（如斜体、悬挂标点）
+/// - 没有可见轮廓的文本（空串、纯空格）包围盒为 `[0, 0, advance, 0]`
+/// - 竖排时原点为第一个字顶部的列中心点，文本沿 -y 方向延伸 `advance`，
+///   无可见轮廓时包围盒为 `[0, -advance, 0, 0]`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextBounds {
+    /// 沿书写方向的 advance 总长度（横排为宽度，竖排为高度）
+    pub advance: f32,
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+    /// 是否为竖排文本
+    pub vertical: bool,
+}
 
-    // 验证文件确实保存
-    if !Path::new(output_path).exists() {
-        return Err("输出文件保存失败".into());
+impl TextBounds {
+    /// 包围盒宽度
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
     }
 
-    Ok(())
+    /// 包围盒高度
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
 }
 
-// ============================================================================
-// 内部算法逻辑 (私有函数)
-// ============================================================================
+/// 已轮廓化并编码好的水印文本
+struct PreparedText {
+    /// 文本 XObject 流对象（已编码，按配置压缩），颜色循环时每个颜色一个
+    streams: Vec<Stream>,
+    /// 文本尺寸
+    bounds: TextBounds,
+}
 
-/// 将文本转换为PDF路径操作序列
-///
-/// # 功能
-/// - 遍历文本中的每个字符
-/// - 从字体中提取字形轮廓
-/// - 将轮廓曲线转换为PDF图形操作指令
-///
-/// # 参数
-/// - `font`: 字体引用
-/// - `text`: 要转换的文本
-/// - `x_start`: 水平起始位置
-/// - `y_start`: 垂直起始位置
-/// - `size`: 字体大小（点数）
-///
-/// # 返回
-/// PDF操作向量（包括移动、线段、贝塞尔曲线等）
-fn text_to_pdf_paths(
-    font: &FontRef,
-    text: &str,
-    x_start: f32,
-    y_start: f32,
-    size: f32,
-) -> Vec<Operation> {
-    let scale = PxScale::from(size);
-    let scaled_font = font.as_scaled(scale);
-    let h_factor = scaled_font.h_scale_factor();
-    let v_factor = scaled_font.v_scale_factor();
+/// 单个文件的水印处理结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatermarkReport {
+    /// 成功添加水印的页数
+    pub pages: usize,
+    /// 输入文件大小（字节）
+    pub input_bytes: u64,
+    /// 输出文件大小（字节）
+    pub output_bytes: u64,
+    /// 字体回退链中所有字体都无法渲染的字符（去重，按出现顺序）
+    pub missing_chars: Vec<char>,
+    /// 处理耗时（加载、生成水印与保存）
+    pub elapsed: Duration,
+}
 
-    let mut ops = vec![
-        Operation::new("q", vec![]),
-        Operation::new("gs", vec!["GS1".into()]),
-        Operation::new("rg", vec![0.1.into(), 0.1.into(), 0.1.into()]),
-    ];
+impl WatermarkReport {
+    /// 输出相对输入增加的字节数（可能为负）
+    pub fn size_delta(&self) -> i64 {
+        self.output_bytes as i64 - self.input_bytes as i64
+    }
+}
 
-    let mut x_cursor = x_start;
-    for c in text.chars() {
-        let glyph_id = font.glyph_id(c);
-        if let Some(outline) = font.outline(glyph_id) {
-            // 使用 Option<Point> 替代 NaN 作为轮廓分界的标记
-            let mut last_point: Option<Point> = None;
-            for curve in outline.curves {
-                let p0 = match curve {
-                    OutlineCurve::Line(p0, _) => p0,
-                    OutlineCurve::Quad(p0, _, _) => p0,
-                    OutlineCurve::Cubic(p0, _, _, _) => p0,
-                };
+/// 水印网格的绘制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GridMode {
+    /// 逐个单元格输出 `q cm Do Q`（默认，所有阅读器都能正确显示）
+    #[default]
+    Explicit,
+    /// 定义平铺图案（`/PatternType 1`）并一次性填充整页
+    ///
+    /// 页面内容体积大幅减小、渲染更快；少数阅读器对图案支持不佳，
+    /// 遇到显示问题时请改用 [`GridMode::Explicit`]。
+    Pattern,
+    /// 页面中心只绘制一个水印（文本包围盒中心与页面中心重合）
+    Center,
+}
 
-                // 判断是否为新轮廓（新的子轮廓起点）
-                let is_new_contour = match last_point {
-                    None => true,
-                    Some(lp) => ((p0.x - lp.x).abs() > 0.001) || ((p0.y - lp.y).abs() > 0.001),
-                };
+impl std::str::FromStr for GridMode {
+    type Err = Box<dyn std::error::Error>;
 
-                if is_new_contour {
-                    if last_point.is_some() {
-                        ops.push(Operation::new("h", vec![])); // 闭合上一个轮廓
-                    }
-                    ops.push(Operation::new(
-                        "m",
-                        vec![
-                            (x_cursor + p0.x * h_factor).into(),
-                            (y_start + p0.y * v_factor).into(),
-                        ],
-                    )); // 移动到新起点
-                }
+    /// 解析布局名称：`tile`（或 `explicit`）、`pattern`、`center`（不区分大小写）
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tile" | "explicit" => Ok(GridMode::Explicit),
+            "pattern" => Ok(GridMode::Pattern),
+            "center" => Ok(GridMode::Center),
+            _ => Err(format!("未知的布局 '{}'，可选 tile、pattern、center", s).into()),
+        }
+    }
+}
 
-                match curve {
-                    OutlineCurve::Line(_, p1) => {
-                        ops.push(Operation::new(
-                            "l",
-                            vec![
-                                (x_cursor + p1.x * h_factor).into(),
-                                (y_start + p1.y * v_factor).into(),
-                            ],
-                        ));
-                        last_point = Some(p1);
-                    }
-                    OutlineCurve::Quad(_, p1, p2) => {
-                        // 将二次贝塞尔转换为三次贝塞尔（PDF只支持三次）
-                        let q1_x = p0.x + (2.0 / 3.0) * (p1.x - p0.x);
-                        let q1_y = p0.y + (2.0 / 3.0) * (p1.y - p0.y);
-                        let q2_x = p2.x + (2.0 / 3.0) * (p1.x - p2.x);
-                        let q2_y = p2.y + (2.0 / 3.0) * (p1.y - p2.y);
-                        ops.push(Operation::new(
-                            "c",
-                            vec![
-                                (x_cursor + q1_x * h_factor).into(),
-                                (y_start + q1_y * v_factor).into(),
-                                (x_cursor + q2_x * h_factor).into(),
-                                (y_start + q2_y * v_factor).into(),
-                                (x_cursor + p2.x * h_factor).into(),
-                                (y_start + p2.y * v_factor).into(),
-                            ],
-                        ));
-                        last_point = Some(p2);
-                    }
-                    OutlineCurve::Cubic(_, p1, p2, p3) => {
-                        ops.push(Operation::new(
-                            "c",
-                            vec![
-                                (x_cursor + p1.x * h_factor).into(),
-                                (y_start + p1.y * v_factor).into(),
-                                (x_cursor + p2.x * h_factor).into(),
-                                (y_start + p2.y * v_factor).into(),
-                                (x_cursor + p3.x * h_factor).into(),
-                                (y_start + p3.y * v_factor).into(),
-                            ],
-                        ));
-                        last_point = Some(p3);
-                    }
-                }
-            }
-            if last_point.is_some() {
-                ops.push(Operation::new("h", vec![])); // 闭合最后一个轮廓
-            }
+/// 描边线段的连接方式（PDF `j` 操作符）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// 尖角连接
+    Miter,
+    /// 圆角连接（默认，字形拐角处最平滑）
+    #[default]
+    Round,
+    /// 斜角连接
+    Bevel,
+}
+
+impl LineJoin {
+    /// PDF `j` 操作数
+    fn pdf_value(self) -> i64 {
+        match self {
+            LineJoin::Miter => 0,
+            LineJoin::Round => 1,
+            LineJoin::Bevel => 2,
         }
-        x_cursor += scaled_font.h_advance(glyph_id);
     }
-    ops.push(Operation::new("f", vec![])); // 填充路径
-    ops.push(Operation::new("Q", vec![])); // 恢复图形状态
+}
+
+/// 水印文字的绘制样式
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum TextStyle {
+    /// 实心填充（默认）
+    #[default]
+    Fill,
+    /// 空心字：只描边不填充，对底层内容的遮挡最少
+    Outline {
+        /// 描边宽度（点数）
+        width: f32,
+        join: LineJoin,
+    },
+    /// 填充并描边，描边可使用与填充不同的颜色
+    FillStroke {
+        /// 描边宽度（点数）
+        width: f32,
+        join: LineJoin,
+        /// 描边颜色
+        stroke: WatermarkColor,
+    },
+    /// 伪粗体：以与填充同色的圆角描边向外扩展字形轮廓
+    FauxBold {
+        /// 每侧加粗量（点数），描边宽度为其两倍
+        amount: f32,
+    },
+}
+
+impl TextStyle {
+    /// 描边宽度（不描边时为 0）
+    fn stroke_width(&self) -> f32 {
+        match *self {
+            TextStyle::Fill => 0.0,
+            TextStyle::Outline { width, .. } | TextStyle::FillStroke { width, .. } => width,
+            TextStyle::FauxBold { amount } => amount * 2.0,
+        }
+    }
+
+    /// 线段连接方式
+    fn join(&self) -> LineJoin {
+        match *self {
+            TextStyle::Outline { join, .. } | TextStyle::FillStroke { join, .. } => join,
+            TextStyle::Fill | TextStyle::FauxBold { .. } => LineJoin::Round,
+        }
+    }
+
+    /// 描边超出轮廓的最大距离（点数）
+    ///
+    /// # 说明
+    /// 描边以路径为中心向两侧扩展半个线宽；尖角连接的尖端最远可达
+    /// 半个线宽乘以 [`STROKE_MITER_LIMIT`]。
+    fn stroke_extent(&self) -> f32 {
+        let half = self.stroke_width() / 2.0;
+        match self.join() {
+            LineJoin::Miter => half * STROKE_MITER_LIMIT,
+            LineJoin::Round | LineJoin::Bevel => half,
+        }
+    }
+
+    /// 是否填充字形内部（空心字只描边）
+    fn fills(&self) -> bool {
+        !matches!(self, TextStyle::Outline { .. })
+    }
+
+    /// 路径绘制操作符：`f` 填充、`S` 描边、`B` 填充并描边
+    fn paint_operator(&self) -> &'static str {
+        match self {
+            TextStyle::Fill => "f",
+            TextStyle::Outline { .. } => "S",
+            TextStyle::FillStroke { .. } | TextStyle::FauxBold { .. } => "B",
+        }
+    }
+
+    /// 描边颜色（不描边时为 `None`）：除 [`TextStyle::FillStroke`] 外与填充同色
+    fn stroke_color<'a>(&'a self, fill: &'a WatermarkColor) -> Option<&'a WatermarkColor> {
+        match self {
+            TextStyle::Fill => None,
+            _ if self.stroke_width() <= 0.0 => None,
+            TextStyle::FillStroke { stroke, .. } => Some(stroke),
+            TextStyle::Outline { .. } | TextStyle::FauxBold { .. } => Some(fill),
+        }
+    }
+
+    /// 设置颜色与线型的图形状态操作（在 `gs` 之后、路径之前）
+    ///
+    /// # 参数
+    /// - `fill`: 填充颜色
+    fn state_ops(&self, fill: &WatermarkColor) -> Vec<Operation> {
+        let mut ops = fill.set_ops(false, FILL_COLOR_SPACE);
+        let Some(stroke) = self.stroke_color(fill) else {
+            return ops;
+        };
+        ops.extend(stroke.set_ops(true, STROKE_COLOR_SPACE));
+        ops.push(Operation::new("w", vec![self.stroke_width().into()]));
+        ops.push(Operation::new("j", vec![self.join().pdf_value().into()]));
+        if self.join() == LineJoin::Miter {
+            ops.push(Operation::new("M", vec![STROKE_MITER_LIMIT.into()]));
+        }
+        ops
+    }
+
+    /// [`TextStyle::state_ops`] 引用的专色颜色空间（Resources > ColorSpace）
+    fn color_spaces(&self, fill: &WatermarkColor) -> lopdf::Dictionary {
+        let mut spaces = lopdf::Dictionary::new();
+        if let Some(space) = fill.color_space() {
+            spaces.set(FILL_COLOR_SPACE, space);
+        }
+        if let Some(space) = self.stroke_color(fill).and_then(WatermarkColor::color_space) {
+            spaces.set(STROKE_COLOR_SPACE, space);
+        }
+        spaces
+    }
+}
+
+/// 可复用的水印处理器
+///
+/// # 说明
+/// - 持有已解析的字体数据，字体只读取、解析一次
+/// - 缓存已轮廓化的文本，相同文本不再重复计算矢量路径
+/// - `apply` 只需 `&self`，可在多次调用之间长期保留
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{TemplateVars, Watermarker};
+///
+/// let marker = Watermarker::from_file("STSongStd-Light-Acro.otf")?;
+/// let vars = TemplateVars::from([
+///     ("name".to_string(), "张三".to_string()),
+///     ("date".to_string(), "2026-02-05".to_string()),
+/// ]);
+/// marker.apply("in.pdf", "out.pdf", &vars)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Watermarker {
+    /// 字体回退链：逐字符选用第一个包含该字形的字体
+    fonts: Vec<LoadedFont>,
+    /// 文本塑形方式
+    shaping: TextShaping,
+    /// 书写方向
+    writing_mode: WritingMode,
+    /// 绘制样式（填充 / 描边）
+    style: TextStyle,
+    /// 填充颜色
+    color: WatermarkColor,
+    /// 渐变填充（替代纯色填充）
+    gradient: Option<Gradient>,
+    /// 网格逐行 / 逐列颜色循环
+    color_cycle: Option<ColorCycle>,
+    /// 文字后方的印章式底框
+    badge: Option<Badge>,
+    /// 与页面内容的混合模式
+    blend_mode: BlendMode,
+    /// 文本 XObject 是否声明为透明组
+    transparency_group: bool,
+    /// 字体大小（点数）
+    font_size: f32,
+    /// 旋转角度（度数，逆时针）
+    angle: f32,
+    /// 文字透明度（0.0-1.0）
+    opacity: f32,
+    /// 只为这些页面添加水印，`None` 为全部页面
+    pages: Option<PageSelection>,
+    template: String,
+    /// 模板默认变量，被调用时传入的同名变量覆盖
+    vars: TemplateVars,
+    /// 时间占位符使用的 UTC 偏移量，`None` 为本机时区
+    utc_offset: Option<FixedOffset>,
+    workers: usize,
+    precision: Option<u8>,
+    compress: bool,
+    grid_mode: GridMode,
+    text_cache: Mutex<HashMap<String, Arc<PreparedText>>>,
+    glyph_cache: GlyphCache,
+}
+
+/// 待注入水印的页面信息（串行收集，供并行生成网格使用）
+#[derive(Clone)]
+struct PageJob {
+    page_num: u32,
+    object_id: ObjectId,
+    width: f32,
+    height: f32,
+    rotation: f32,
+    /// 该页水印文本在文档文本列表中的序号
+    text_idx: usize,
+    /// 该页水印文本尺寸
+    text: TextBounds,
+}
+
+/// 网格缓存键：(页面宽, 页面高, 页面旋转, 文本尺寸) 的位表示
+///
+/// 字号、角度与资源名在同一文档内固定，键相同的页面网格内容完全一致。
+type GridKey = [u32; 8];
+
+/// 已嵌入文档的文本：各颜色变体的文本 XObject 对象ID（与调色板一一对应）及文本尺寸
+type EmbeddedText = (Vec<ObjectId>, TextBounds);
+
+impl PageJob {
+    fn grid_key(&self) -> GridKey {
+        [
+            self.width.to_bits(),
+            self.height.to_bits(),
+            self.rotation.to_bits(),
+            self.text.advance.to_bits(),
+            self.text.x_min.to_bits(),
+            self.text.y_min.to_bits(),
+            self.text.x_max.to_bits(),
+            self.text.y_max.to_bits(),
+        ]
+    }
+}
+
+impl Watermarker {
+    /// 从字体文件创建水印器
+    pub fn from_file(font_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_files(&[font_path])
+    }
+
+    /// 从有序的字体文件列表创建水印器
+    ///
+    /// # 说明
+    /// 第一个为主字体，其余为回退字体：每个字符使用列表中第一个包含该字形的字体，
+    /// 所有字体都缺失的字符会在处理结果中报告（见 [`WatermarkReport::missing_chars`]）。
+    pub fn from_files<P: AsRef<Path>>(font_paths: &[P]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut fonts_data = Vec::with_capacity(font_paths.len());
+        for path in font_paths {
+            let path = path.as_ref();
+            fonts_data.push(
+                std::fs::read(path)
+                    .map_err(|e| format!("read font {} failed: {}", path.display(), e))?,
+            );
+        }
+        Self::from_fonts(fonts_data)
+    }
+
+    /// 使用字体集合（`.ttc`/`.otc`）中指定索引的字面创建水印器
+    ///
+    /// # 说明
+    /// 字面索引可通过 [`collection_faces`] 查询；普通字体只有索引 0。
+    pub fn from_collection(font_path: &str, face_index: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_loaded(vec![load_font_face(Path::new(font_path), face_index)?])
+    }
+
+    /// 从内存中的字体集合数据创建水印器，使用指定索引的字面
+    pub fn from_bytes_index(font_data: Vec<u8>, face_index: u32) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_loaded(vec![font_from_data(font_data, face_index)?])
+    }
+
+    /// 按字体文件路径或字体名称创建水印器
+    ///
+    /// # 参数
+    /// - `specs`: 有序的字体列表（主字体在前，其余为回退字体），每项可以是
+    ///   存在的文件路径，也可以是系统字体名称（如 `"Noto Sans CJK SC Bold"`，见 [`FontIndex::find`]）；
+    ///   字体集合可写作 `路径#索引` 或 `路径#字面名称`（如 `"NotoSansCJK.ttc#Noto Sans CJK SC"`）
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::Watermarker;
+    ///
+    /// let marker = Watermarker::from_specs(&["Noto Sans CJK SC Bold", "DejaVu Sans"])?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_specs<S: AsRef<str>>(specs: &[S]) -> Result<Self, Box<dyn std::error::Error>> {
+        let fonts = specs
+            .iter()
+            .map(|spec| load_font_spec(spec.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_loaded(fonts)
+    }
+
+    /// 按配置文件中的命名方案创建水印器
+    ///
+    /// # 参数
+    /// - `config_path`: TOML 或 JSON 配置文件路径，格式见 [`ProfileConfig`]
+    /// - `profile`: 方案名称，如 `confidential`
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::Watermarker;
+    ///
+    /// let marker = Watermarker::from_profile("water_mark.toml", "confidential")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_profile(config_path: &str, profile: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        ProfileConfig::load(config_path)?.profile(profile)?.watermarker()
+    }
+
+    /// 按系统字体名称创建水印器（在标准字体目录中查找）
+    pub fn from_family(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_specs(&[name])
+    }
+
+    /// 从内存中的字体数据创建水印器
+    pub fn from_bytes(font_data: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_fonts(vec![font_data])
+    }
+
+    /// 从内存中的有序字体数据列表（主字体在前，回退字体在后）创建水印器
+    pub fn from_fonts(fonts_data: Vec<Vec<u8>>) -> Result<Self, Box<dyn std::error::Error>> {
+        let fonts = fonts_data
+            .into_iter()
+            .map(|data| FontVec::try_from_vec(data).map(|font| LoadedFont::new(font, 0)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_loaded(fonts)
+    }
+
+    /// 由已解析的字体回退链创建水印器
+    fn from_loaded(fonts: Vec<LoadedFont>) -> Result<Self, Box<dyn std::error::Error>> {
+        if fonts.is_empty() {
+            return Err("at least one font is required".into());
+        }
+        Ok(Self {
+            fonts,
+            shaping: TextShaping::default(),
+            writing_mode: WritingMode::Horizontal,
+            style: TextStyle::Fill,
+            color: WatermarkColor::default(),
+            gradient: None,
+            color_cycle: None,
+            badge: None,
+            blend_mode: BlendMode::Normal,
+            transparency_group: false,
+            font_size: DEFAULT_FONT_SIZE,
+            angle: WATERMARK_ANGLE_DEG,
+            opacity: DEFAULT_OPACITY,
+            pages: None,
+            template: DEFAULT_TEMPLATE.to_string(),
+            vars: TemplateVars::new(),
+            utc_offset: None,
+            workers: default_worker_count(),
+            precision: None,
+            compress: true,
+            grid_mode: GridMode::Explicit,
+            text_cache: Mutex::new(HashMap::new()),
+            glyph_cache: GlyphCache::new(),
+        })
+    }
+
+    /// 在回退链末尾追加一个回退字体文件
+    pub fn with_fallback_file(mut self, font_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let data = std::fs::read(font_path)
+            .map_err(|e| format!("read font {} failed: {}", font_path, e))?;
+        self.fonts.push(LoadedFont::new(FontVec::try_from_vec(data)?, 0));
+        self.clear_caches();
+        Ok(self)
+    }
+
+    /// 返回文本中所有字体都无法渲染的字符（去重，按出现顺序）
+    pub fn missing_chars(&self, text: &str) -> Vec<char> {
+        missing_chars(&self.fonts, text)
+    }
+
+    /// 设置文本塑形方式（默认为 [`TextShaping::default`]）
+    ///
+    /// # 说明
+    /// 默认应用字偶距；以 `shaping` 特性编译时默认使用完整塑形，
+    /// 可正确处理阿拉伯文、天城文、泰文等需要连字与上下文变形的文字。
+    pub fn with_shaping(mut self, shaping: TextShaping) -> Self {
+        self.shaping = shaping;
+        self.clear_caches();
+        self
+    }
+
+    /// 设置书写方向（默认横排）
+    ///
+    /// # 说明
+    /// 竖排适用于繁体中文、日文文档：使用字体的竖排度量与竖排标点，
+    /// 网格沿文本列方向的间距由竖排文本的实测高度决定。
+    pub fn with_writing_mode(mut self, mode: WritingMode) -> Self {
+        self.writing_mode = mode;
+        self.clear_caches();
+        self
+    }
+
+    /// 设置绘制样式（默认实心填充）
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::{LineJoin, TextStyle, Watermarker};
+    ///
+    /// // 空心字：对底层内容遮挡更少，同时保持可读
+    /// let marker = Watermarker::from_file("font.otf")?
+    ///     .with_style(TextStyle::Outline { width: 0.6, join: LineJoin::Round });
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_style(mut self, style: TextStyle) -> Self {
+        self.style = style;
+        self.clear_caches();
+        self
+    }
+
+    /// 设置水印颜色（默认深灰 RGB(0.1, 0.1, 0.1)）
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::{WatermarkColor, Watermarker};
+    ///
+    /// // 印刷用 CMYK，或单独出版的专色
+    /// let cmyk = Watermarker::from_file("font.otf")?
+    ///     .with_color(WatermarkColor::Cmyk([0.0, 0.8, 1.0, 0.0]));
+    /// let spot = Watermarker::from_file("font.otf")?
+    ///     .with_color("spot:PANTONE 185 C:0,0.91,0.76,0".parse()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_color(mut self, color: WatermarkColor) -> Self {
+        self.color = color;
+        self.clear_caches();
+        self
+    }
+
+    /// 设置渐变填充（裁剪到字形轮廓），替代纯色填充
+    ///
+    /// # 说明
+    /// - 渐变只作用于填充：空心字（[`TextStyle::Outline`]）不填充，因此不受影响；
+    ///   描边仍使用纯色（见 [`TextStyle`]）
+    /// - 文档中有多个不同文本时，每个文本单独轮廓化（不复用字形 XObject），
+    ///   以便在文本 XObject 内裁剪
+    ///
+    /// # 返回
+    /// 两端颜色空间不一致或使用专色时返回错误
+    pub fn with_gradient(mut self, gradient: Gradient) -> Result<Self, Box<dyn std::error::Error>> {
+        gradient.validate()?;
+        self.gradient = Some(gradient);
+        self.clear_caches();
+        Ok(self)
+    }
+
+    /// 设置网格逐行 / 逐列的颜色循环，替代单一填充颜色
+    ///
+    /// # 说明
+    /// 颜色列表为空时等同于不循环；同时设置渐变时，循环颜色只用于与填充同色的描边。
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::{ColorCycle, WatermarkColor, Watermarker};
+    ///
+    /// let marker = Watermarker::from_file("font.otf")?.with_color_cycle(ColorCycle::Rows(vec![
+    ///     WatermarkColor::Rgb([0.8, 0.0, 0.0]),
+    ///     WatermarkColor::Rgb([0.0, 0.0, 0.8]),
+    /// ]));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_color_cycle(mut self, cycle: ColorCycle) -> Self {
+        self.color_cycle = Some(cycle).filter(|c| !c.colors().is_empty());
+        self.clear_caches();
+        self
+    }
+
+    /// 在文字后方绘制印章式底框（圆角矩形或双线边框，见 [`Badge`]）
+    ///
+    /// # 说明
+    /// 底框与文字位于同一个文本 XObject 中，尺寸随文本实测包围盒变化，
+    /// 网格裁剪与 XObject BBox 会把底框计算在内。
+    pub fn with_badge(mut self, badge: Badge) -> Self {
+        self.badge = Some(badge);
+        self.clear_caches();
+        self
+    }
+
+    /// 设置与页面内容的混合模式（默认 [`BlendMode::Normal`]）
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::{BlendMode, Watermarker};
+    ///
+    /// // 深色扫描件：滤色让水印在暗背景上仍然可见，又不遮盖原有内容
+    /// let marker = Watermarker::from_file("font.otf")?.with_blend_mode(BlendMode::Screen);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_blend_mode(mut self, mode: BlendMode) -> Self {
+        self.blend_mode = mode;
+        self.clear_caches();
+        self
+    }
+
+    /// 将文本 XObject 声明为透明组（默认不声明）
+    ///
+    /// # 说明
+    /// 写入 `/Group << /S /Transparency /I false /K false >>`：非隔离（混合模式仍相对页面背景计算）、
+    /// 非挖空（同一水印内重叠的字形与描边依次合成，而不是互相挖空）。
+    /// 部分阅读器只有在 Form 声明透明组时才按组合成带混合模式的内容。
+    pub fn with_transparency_group(mut self, enabled: bool) -> Self {
+        self.transparency_group = enabled;
+        self.clear_caches();
+        self
+    }
+
+    /// 设置字体大小（点数，默认 26）
+    ///
+    /// # 说明
+    /// 网格垂直间距随字号等比变化；非正数或非有限值时保持原设置。
+    pub fn with_font_size(mut self, size: f32) -> Self {
+        if size.is_finite() && size > 0.0 {
+            self.font_size = size;
+            self.clear_caches();
+        }
+        self
+    }
+
+    /// 设置水印旋转角度（度数，逆时针，默认 60）
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        if angle.is_finite() {
+            self.angle = angle;
+        }
+        self
+    }
+
+    /// 设置文字透明度（0.0-1.0，默认 0.1；超出范围时截断）
+    ///
+    /// # 说明
+    /// 只作用于文字，底框透明度见 [`Badge::opacity`]。
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        if !opacity.is_nan() {
+            self.opacity = opacity.clamp(0.0, 1.0);
+            self.clear_caches();
+        }
+        self
+    }
+
+    /// 只为选中的页面添加水印（默认全部页面）
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::Watermarker;
+    ///
+    /// // 只处理前 3 页和最后一页
+    /// let marker = Watermarker::from_file("font.otf")?.with_pages("1-3,last".parse()?);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_pages(mut self, pages: PageSelection) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// 设置水印模板（默认为 [`DEFAULT_TEMPLATE`]）
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+        self
+    }
+
+    /// 设置模板默认变量（默认为空）
+    ///
+    /// # 说明
+    /// 渲染时先取默认变量，再用 [`Watermarker::apply`] 等传入的同名变量覆盖。
+    /// 适合在配置中固定部门名、密级等不随收件人变化的内容。
+    pub fn with_vars(mut self, vars: TemplateVars) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    /// 设置并行处理使用的工作线程数（默认为 CPU 核心数，最小为 1）
+    ///
+    /// # 说明
+    /// - 作用于 [`Watermarker::batch_parallel`] 的文档级并行
+    /// - 也作用于单个大文档内各页水印网格的并行生成
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// 当前配置的工作线程数
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// 设置路径坐标保留的小数位数（默认 `None`，保留完整精度）
+    ///
+    /// # 说明
+    /// 例如 `Some(2)` 时坐标精确到 0.01pt，肉眼不可分辨，但能明显减小内容流体积。
+    /// 旋转矩阵系数至少保留 4 位小数，避免水印变形。
+    pub fn with_precision(mut self, precision: Option<u8>) -> Self {
+        self.precision = precision;
+        self.lock_text_cache().clear();
+        self
+    }
+
+    /// 设置是否对新增的 XObject 与页面内容流进行 Flate 压缩（默认开启）
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self.lock_text_cache().clear();
+        self
+    }
+
+    /// 设置水印网格的绘制方式（默认 [`GridMode::Explicit`]）
+    pub fn with_grid_mode(mut self, mode: GridMode) -> Self {
+        self.grid_mode = mode;
+        self
+    }
+
+    /// 设置时间占位符（`{now}` / `{file_mtime}`）使用的 UTC 偏移量（默认本机时区）
+    ///
+    /// # 参数
+    /// - `offset`: 如 `+08:00`、`-0530`、`Z`
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::Watermarker;
+    ///
+    /// // 服务器在 UTC，水印按北京时间显示
+    /// let marker = Watermarker::from_file("font.otf")?
+    ///     .with_template("{name} 于 {now:%Y-%m-%d %H:%M} 下载")
+    ///     .with_utc_offset("+08:00")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_utc_offset(mut self, offset: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        self.utc_offset = Some(parse_utc_offset(offset)?);
+        Ok(self)
+    }
+
+    /// 当前使用的水印模板
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// 使用给定变量渲染模板，得到最终水印文本
+    ///
+    /// # 说明
+    /// 只替换 `vars` 中的变量；时间与页码占位符在 [`Watermarker::apply`] 处理文件时渲染。
+    pub fn render_text(&self, vars: &TemplateVars) -> String {
+        if self.vars.is_empty() {
+            return render_template(&self.template, vars);
+        }
+        let mut merged = self.vars.clone();
+        merged.extend(vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        render_template(&self.template, &merged)
+    }
+
+    /// 渲染模板并为单个文件添加水印
+    ///
+    /// # 说明
+    /// 依次渲染三类占位符：
+    /// 1. 模板变量 `{key}`（`vars` 中的值也可以含有下面的占位符，如 `date = "{now:%Y-%m-%d}"`）
+    /// 2. 时间：`{now}` 为处理时刻，`{file_mtime}` 为输入文件的修改时间；
+    ///    可写作 `{now:%Y-%m-%d %H:%M}` 指定 strftime 格式（默认 `%Y-%m-%d %H:%M`），
+    ///    按 [`Watermarker::with_utc_offset`] 设置的时区显示
+    /// 3. 页码：`{page}`（当前页，从 1 开始）与 `{pages}`（总页数），
+    ///    此时每页的水印文本不同，共用字形会被提取为独立的 Form XObject 复用；
+    ///    `vars` 中名为 `page` / `pages` 的变量优先，此时不再按页渲染
+    ///
+    /// 格式串无效或无法读取文件修改时间时，对应占位符原样保留。
+    ///
+    /// # 参数
+    /// - `input_path`: 输入PDF路径
+    /// - `output_path`: 输出PDF路径
+    /// - `vars`: 模板变量
+    pub fn apply(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        vars: &TemplateVars,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        self.apply_with_workers(input_path, output_path, vars, self.workers)
+    }
+
+    /// 批量处理多个文件
+    ///
+    /// # 说明
+    /// 依次处理每个任务，单个任务失败不会中断后续任务。
+    ///
+    /// # 返回
+    /// 与输入任务一一对应的处理结果
+    pub fn batch<I>(&self, jobs: I) -> Vec<Result<WatermarkReport, Box<dyn std::error::Error>>>
+    where
+        I: IntoIterator<Item = BatchJob>,
+    {
+        jobs.into_iter()
+            .map(|job| self.apply(&job.input, &job.output, &job.vars))
+            .collect()
+    }
+
+    /// 并行批量处理多个文件
+    ///
+    /// # 说明
+    /// - 使用 [`Watermarker::with_workers`] 配置的线程数同时处理多个文档
+    /// - 文档级已并行，单个文档内部不再并行生成网格，避免线程过度订阅
+    /// - 单个任务失败不会中断其他任务
+    ///
+    /// # 返回
+    /// 与输入任务一一对应（保持原顺序）的处理结果
+    pub fn batch_parallel<I>(
+        &self,
+        jobs: I,
+    ) -> Vec<Result<WatermarkReport, Box<dyn std::error::Error>>>
+    where
+        I: IntoIterator<Item = BatchJob>,
+    {
+        let jobs: Vec<BatchJob> = jobs.into_iter().collect();
+        run_parallel(self.workers, &jobs, |job| {
+            self.apply_with_workers(&job.input, &job.output, &job.vars, 1)
+        })
+    }
+
+    /// 并行批量处理多个文件，跳过已由本库添加过水印的文档
+    ///
+    /// # 说明
+    /// 与 [`Watermarker::batch_parallel`] 相同，但每个文档解析后先检查是否已有水印
+    /// （见 [`is_watermarked`]），检查与加水印共用同一次解析。
+    ///
+    /// # 返回
+    /// 与输入任务一一对应的处理结果；已有水印的文档为 `Ok(None)`，不写出输出文件
+    pub fn batch_parallel_unmarked<I>(
+        &self,
+        jobs: I,
+    ) -> Vec<Result<Option<WatermarkReport>, Box<dyn std::error::Error>>>
+    where
+        I: IntoIterator<Item = BatchJob>,
+    {
+        let jobs: Vec<BatchJob> = jobs.into_iter().collect();
+        run_parallel(self.workers, &jobs, |job| {
+            let start_time = Instant::now();
+            let source = SourceDocument::load(&job.input)?;
+            if source.is_watermarked() {
+                return Ok(None);
+            }
+            let text_for_page = self.page_text_renderer(&job.vars, &source.path);
+            self.watermark_document(
+                source.doc,
+                source.input_bytes,
+                start_time,
+                &job.output,
+                &text_for_page,
+                1,
+            )
+            .map(Some)
+        })
+    }
+
+    /// 为同一源文档生成多份副本（如按收件人分发），每个任务一份
+    ///
+    /// # 说明
+    /// - 相同输入路径的任务共用一次解析结果（见 [`SourceDocument`]），每份副本从其克隆生成
+    /// - 与 [`Watermarker::batch_parallel`] 相同，按配置的线程数并行处理，单个任务失败不影响其他任务
+    /// - 源文档在处理期间常驻内存
+    ///
+    /// # 返回
+    /// 与输入任务一一对应（保持原顺序）的处理结果
+    ///
+    /// # 示例
+    /// ```no_run
+    /// use water_mark::{BatchJob, TemplateVars, Watermarker};
+    ///
+    /// let marker = Watermarker::from_file("font.otf")?.with_template("致{name}:高度保密");
+    /// let jobs = ["张三", "李四"].iter().map(|name| BatchJob {
+    ///     input: "dataroom.pdf".to_string(),
+    ///     output: format!("out/{}.pdf", name),
+    ///     vars: TemplateVars::from([("name".to_string(), name.to_string())]),
+    /// });
+    /// let results = marker.distribute(jobs);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn distribute<I>(&self, jobs: I) -> Vec<Result<WatermarkReport, Box<dyn std::error::Error>>>
+    where
+        I: IntoIterator<Item = BatchJob>,
+    {
+        let jobs: Vec<BatchJob> = jobs.into_iter().collect();
+
+        // 每个输入只解析一次；解析失败时该输入的所有任务都返回同一错误
+        let mut sources: HashMap<&str, Result<SourceDocument, String>> = HashMap::new();
+        for job in &jobs {
+            sources
+                .entry(job.input.as_str())
+                .or_insert_with(|| SourceDocument::load(&job.input).map_err(|e| e.to_string()));
+        }
+
+        run_parallel(self.workers, &jobs, |job| match &sources[job.input.as_str()] {
+            Ok(source) => self.apply_source_with_workers(source, &job.output, &job.vars, 1),
+            Err(e) => Err(e.clone().into()),
+        })
+    }
+
+    /// 使用已确定的水印文本为单个文件添加水印
+    ///
+    /// # 说明
+    /// `text` 按字面使用，不做任何占位符替换。
+    pub fn apply_text(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        text: &str,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        self.watermark_file(input_path, output_path, &|_, _| text.to_string(), self.workers)
+    }
+
+    /// 渲染模板，为已解析的源文档生成一份水印副本
+    ///
+    /// # 说明
+    /// 与 [`Watermarker::apply`] 相同，但跳过PDF解析：源文档只需 [`SourceDocument::load`] 一次，
+    /// 之后每份副本从其克隆生成，适合同一文件按收件人分发多份的场景。
+    /// `{file_mtime}` 取源文件的修改时间。
+    pub fn apply_source(
+        &self,
+        source: &SourceDocument,
+        output_path: &str,
+        vars: &TemplateVars,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        self.apply_source_with_workers(source, output_path, vars, self.workers)
+    }
+
+    /// [`Watermarker::apply`] 的实现，`page_workers` 为页面网格生成的线程数
+    fn apply_with_workers(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        vars: &TemplateVars,
+        page_workers: usize,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        let text_for_page = self.page_text_renderer(vars, input_path);
+        self.watermark_file(input_path, output_path, &text_for_page, page_workers)
+    }
+
+    /// [`Watermarker::apply_source`] 的实现，`page_workers` 为页面网格生成的线程数
+    fn apply_source_with_workers(
+        &self,
+        source: &SourceDocument,
+        output_path: &str,
+        vars: &TemplateVars,
+        page_workers: usize,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        let start_time = Instant::now();
+        let text_for_page = self.page_text_renderer(vars, &source.path);
+        self.watermark_document(
+            source.doc.clone(),
+            source.input_bytes,
+            start_time,
+            output_path,
+            &text_for_page,
+            page_workers,
+        )
+    }
+
+    /// 渲染模板变量与时间占位符，返回根据 (页码, 总页数) 生成该页水印文本的函数
+    fn page_text_renderer(
+        &self,
+        vars: &TemplateVars,
+        input_path: &str,
+    ) -> impl Fn(u32, usize) -> String {
+        let text = self.render_time(&self.render_text(vars), input_path);
+        let per_page = has_page_placeholders(&text);
+        move |page_num: u32, page_count: usize| {
+            if !per_page {
+                return text.clone();
+            }
+            let page_vars = TemplateVars::from([
+                ("page".to_string(), page_num.to_string()),
+                ("pages".to_string(), page_count.to_string()),
+            ]);
+            render_template(&text, &page_vars)
+        }
+    }
+
+    /// 渲染文本中的时间占位符（`{now}` / `{file_mtime}`，可带 `:格式`）
+    fn render_time(&self, text: &str, input_path: &str) -> String {
+        if !text.contains("{now") && !text.contains("{file_mtime") {
+            return text.to_string();
+        }
+        let now = Utc::now();
+        let mtime = std::fs::metadata(input_path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        replace_placeholders(text, |key| {
+            let (name, format) = key.split_once(':').unwrap_or((key, DEFAULT_DATETIME_FORMAT));
+            let time = match name {
+                "now" => now,
+                "file_mtime" => mtime?,
+                _ => return None,
+            };
+            let formatted = match self.utc_offset {
+                Some(offset) => format_datetime(&time.with_timezone(&offset), format),
+                None => format_datetime(&time.with_timezone(&Local), format),
+            };
+            if formatted.is_none() {
+                eprintln!("WARN: 无效的时间格式 '{}'，占位符保持原样", format);
+            }
+            formatted
+        })
+    }
+
+    /// 加载PDF文件并添加水印（见 [`Watermarker::watermark_document`]）
+    fn watermark_file(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        text_for_page: &dyn Fn(u32, usize) -> String,
+        page_workers: usize,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+        let start_time = Instant::now();
+        let input_bytes = std::fs::metadata(input_path)?.len();
+        let doc = Document::load(input_path)?;
+        self.watermark_document(doc, input_bytes, start_time, output_path, text_for_page, page_workers)
+    }
+
+    /// 为已加载的文档添加水印并保存的核心流程
+    ///
+    /// # 流程
+    /// 1. 确定每页的水印文本
+    /// 2. 将文本作为XObject流对象嵌入PDF（单一文本整体轮廓化，多文本按字形复用）
+    /// 3. 遍历所有页面，生成水印网格（考虑页面旋转，大文档并行生成）
+    /// 4. 串行将网格内容写入各页并保存
+    ///
+    /// # 参数
+    /// - `input_bytes`: 源文件大小（用于报告）
+    /// - `start_time`: 计时起点（报告中的耗时从此开始计算）
+    /// - `text_for_page`: 根据 (页码, 总页数) 返回该页水印文本
+    /// - `page_workers`: 页面网格生成的线程数
+    fn watermark_document(
+        &self,
+        mut doc: Document,
+        input_bytes: u64,
+        start_time: Instant,
+        output_path: &str,
+        text_for_page: &dyn Fn(u32, usize) -> String,
+        page_workers: usize,
+    ) -> Result<WatermarkReport, Box<dyn std::error::Error>> {
+
+        // 串行收集页面尺寸、旋转角度与文本（只读访问文档）
+        let page_map = doc.get_pages();
+        let page_count = page_map.len();
+        let mut texts: Vec<String> = Vec::new();
+        let mut text_index: HashMap<String, usize> = HashMap::new();
+        let mut pages: Vec<PageJob> = page_map
+            .into_iter()
+            .filter(|(page_num, _)| {
+                self.pages
+                    .as_ref()
+                    .is_none_or(|selection| selection.contains(*page_num, page_count))
+            })
+            .map(|(page_num, object_id)| {
+                let (width, height) = page_size(&doc, object_id).unwrap_or((595.0, 842.0));
+                let text = text_for_page(page_num, page_count);
+                let text_idx = *text_index.entry(text).or_insert_with_key(|t| {
+                    texts.push(t.clone());
+                    texts.len() - 1
+                });
+                PageJob {
+                    page_num,
+                    object_id,
+                    width,
+                    height,
+                    // 获取页面旋转角度（支持旋转PDF）
+                    rotation: get_page_rotation(&doc, object_id),
+                    text_idx,
+                    text: TextBounds::default(),
+                }
+            })
+            .collect();
+
+        // 将文本嵌入为 XObject：单一文本整体轮廓化（同一文本只计算一次），
+        // 多个不同文本时共用字形各自成为独立的 Form XObject（渐变需要整体裁剪，不复用字形）
+        let text_xobjects: Vec<EmbeddedText> =
+            if texts.len() <= 1 || self.gradient.is_some() {
+                let single = [String::new()];
+                let texts = if texts.is_empty() { &single[..] } else { &texts[..] };
+                let mut embedded = Vec::with_capacity(texts.len());
+                for text in texts {
+                    let prepared = self.prepare_text(text)?;
+                    let ids = prepared
+                        .streams
+                        .iter()
+                        .map(|stream| doc.add_object(stream.clone()))
+                        .collect();
+                    embedded.push((ids, prepared.bounds));
+                }
+                embedded
+            } else {
+                self.add_glyph_text_xobjects(&mut doc, &texts)?
+            };
+        for page in &mut pages {
+            page.text = text_xobjects[page.text_idx].1;
+        }
+
+        // 报告无法渲染的字符（这些字符以 .notdef 输出，通常显示为空白或方框）
+        let mut missing = Vec::new();
+        for text in &texts {
+            for c in missing_chars(&self.fonts, text) {
+                if !missing.contains(&c) {
+                    missing.push(c);
+                }
+            }
+        }
+        if !missing.is_empty() {
+            eprintln!(
+                "WARN: 以下字符在所有字体中都不存在，无法渲染: {}",
+                missing.iter().collect::<String>()
+            );
+        }
+
+        // 遍历页面并注入资源与内容（修改文档，必须串行）
+        let pages_done = match self.grid_mode {
+            GridMode::Explicit | GridMode::Center => {
+                self.inject_explicit_grids(&mut doc, &pages, &text_xobjects, page_workers)
+            }
+            GridMode::Pattern => self.inject_pattern_fills(&mut doc, &pages, &text_xobjects),
+        };
+
+        doc.save(output_path)?;
+
+        // 验证文件确实保存
+        if !Path::new(output_path).exists() {
+            return Err("输出文件保存失败".into());
+        }
+
+        Ok(WatermarkReport {
+            pages: pages_done,
+            input_bytes,
+            output_bytes: std::fs::metadata(output_path)?.len(),
+            missing_chars: missing,
+            elapsed: start_time.elapsed(),
+        })
+    }
+
+    /// 以显式网格（逐单元格 `q cm Do Q`）方式为各页注入水印
+    ///
+    /// # 返回
+    /// 成功添加水印的页数
+    fn inject_explicit_grids(
+        &self,
+        doc: &mut Document,
+        pages: &[PageJob],
+        text_xobjects: &[EmbeddedText],
+        page_workers: usize,
+    ) -> usize {
+        let xobject_names = self.text_xobject_names();
+        let cells = self.cell_names(&xobject_names);
+
+        // 相同尺寸、旋转与文本宽度的页面共用同一网格，只为每种组合生成一次（大文档并行）
+        let mut seen: HashSet<GridKey> = HashSet::new();
+        let distinct: Vec<PageJob> = pages
+            .iter()
+            .filter(|page| seen.insert(page.grid_key()))
+            .cloned()
+            .collect();
+        let mut grids: HashMap<GridKey, Result<Vec<Operation>, String>> = distinct
+            .iter()
+            .map(PageJob::grid_key)
+            .zip(self.build_page_grids(&distinct, &cells, page_workers))
+            .collect();
+        // 已写入文档的共享网格内容流
+        let mut grid_streams: HashMap<GridKey, ObjectId> = HashMap::new();
+
+        let mut pages_done = 0;
+        'pages: for page in pages {
+            let (page_num, object_id) = (page.page_num, page.object_id);
+            let key = page.grid_key();
+            let xobject_ids = &text_xobjects[page.text_idx].0;
+
+            // 添加XObject资源到页面（颜色循环时每个颜色一个）
+            for (name, xobject_id) in xobject_names.iter().zip(xobject_ids) {
+                if let Err(e) = add_page_resource(doc, object_id, "XObject", name, *xobject_id) {
+                    eprintln!(
+                        "WARN: 第 {} 页结构非标准，无法注入资源。错误：{:?}",
+                        page_num, e
+                    );
+                    continue 'pages;
+                }
+            }
+
+            // 首次使用时将网格编码为独立的流对象，之后的同类页面直接引用
+            let stream_id = match grid_streams.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let ops = match grids.get(&key) {
+                        Some(Ok(ops)) => ops.clone(),
+                        Some(Err(e)) => {
+                            eprintln!("WARN: 生成水印网格失败，跳过第 {} 页：{:?}", page_num, e);
+                            continue;
+                        }
+                        None => continue,
+                    };
+                    let stream = match self.encode_stream(lopdf::Dictionary::new(), ops) {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            eprintln!("WARN: 添加页面内容失败，跳过第 {} 页：{:?}", page_num, e);
+                            continue;
+                        }
+                    };
+                    grids.remove(&key); // 网格已编码，释放操作序列
+                    let id = doc.add_object(stream);
+                    grid_streams.insert(key, id);
+                    id
+                }
+            };
+
+            // 将共享的水印内容流引用追加到页面
+            if let Err(e) = append_page_content(doc, object_id, stream_id) {
+                eprintln!("WARN: 添加页面内容失败，跳过第 {} 页：{:?}", page_num, e);
+                continue;
+            }
+            pages_done += 1;
+        }
+        pages_done
+    }
+
+    /// 以平铺图案（Tiling Pattern）方式为各页注入水印
+    ///
+    /// # 说明
+    /// - 图案单元格只包含一次文本 XObject 调用，由阅读器负责平铺
+    /// - 页面内容仅为一个填充整页的矩形，体积与单元格数量无关
+    /// - 图案按 (网格键, 文本) 缓存，填充内容流按页面尺寸缓存，均在页面间共享
+    ///
+    /// # 返回
+    /// 成功添加水印的页数
+    fn inject_pattern_fills(
+        &self,
+        doc: &mut Document,
+        pages: &[PageJob],
+        text_xobjects: &[EmbeddedText],
+    ) -> usize {
+        let xobject_names = self.text_xobject_names();
+        let cells = self.cell_names(&xobject_names);
+        let pattern_name = PATTERN_NAME;
+        let mut patterns: HashMap<(GridKey, usize), ObjectId> = HashMap::new();
+        let mut fills: HashMap<(u32, u32), ObjectId> = HashMap::new();
+
+        let mut pages_done = 0;
+        for page in pages {
+            let (page_num, object_id) = (page.page_num, page.object_id);
+
+            let pattern_id = match patterns.get(&(page.grid_key(), page.text_idx)) {
+                Some(id) => *id,
+                None => {
+                    let built = build_watermark_pattern(
+                        &cells,
+                        &text_xobjects[page.text_idx].0,
+                        self.font_size,
+                        self.angle,
+                        page.width,
+                        page.height,
+                        &page.text,
+                        page.rotation,
+                    )
+                    .and_then(|(dict, ops)| self.encode_stream(dict, ops));
+                    match built {
+                        Ok(stream) => {
+                            let id = doc.add_object(stream);
+                            patterns.insert((page.grid_key(), page.text_idx), id);
+                            id
+                        }
+                        Err(e) => {
+                            eprintln!("WARN: 生成水印图案失败，跳过第 {} 页：{:?}", page_num, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // 添加Pattern资源到页面
+            if let Err(e) = add_page_resource(doc, object_id, "Pattern", pattern_name, pattern_id) {
+                eprintln!(
+                    "WARN: 第 {} 页结构非标准，无法注入资源。错误：{:?}",
+                    page_num, e
+                );
+                continue;
+            }
+
+            let fill_key = (page.width.to_bits(), page.height.to_bits());
+            let fill_id = match fills.get(&fill_key) {
+                Some(id) => *id,
+                None => {
+                    let ops = build_pattern_fill_ops(pattern_name, page.width, page.height);
+                    match self.encode_stream(lopdf::Dictionary::new(), ops) {
+                        Ok(stream) => {
+                            let id = doc.add_object(stream);
+                            fills.insert(fill_key, id);
+                            id
+                        }
+                        Err(e) => {
+                            eprintln!("WARN: 添加页面内容失败，跳过第 {} 页：{:?}", page_num, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if let Err(e) = append_page_content(doc, object_id, fill_id) {
+                eprintln!("WARN: 添加页面内容失败，跳过第 {} 页：{:?}", page_num, e);
+                continue;
+            }
+            pages_done += 1;
+        }
+        pages_done
+    }
+
+    /// 以字形复用方式为多个文本创建 XObject
+    ///
+    /// # 说明
+    /// 同一文档中各页水印文本不同（模板含页码占位符 `{page}` / `{pages}`）时使用：
+    /// - 每个不同的字形只轮廓化、嵌入一次，成为独立的小型 Form XObject
+    /// - 文本 XObject 由一系列 `q cm Do Q` 调用组成，引用这些字形对象
+    /// - 填充颜色与透明度在文本 XObject 中设置，字形 XObject 继承图形状态
+    ///
+    /// # 返回
+    /// 与 `texts` 一一对应的 (文本 XObject 对象ID（每个循环颜色一个）, 文本尺寸)
+    fn add_glyph_text_xobjects(
+        &self,
+        doc: &mut Document,
+        texts: &[String],
+    ) -> Result<Vec<EmbeddedText>, Box<dyn std::error::Error>> {
+        // 字形 -> (资源名, 对象ID)；空白字形没有轮廓，不生成对象
+        let mut glyph_forms: HashMap<(usize, GlyphId), Option<(String, ObjectId)>> =
+            HashMap::new();
+        let mut result = Vec::with_capacity(texts.len());
+
+        for text in texts {
+            let mut ops = Vec::new();
+            let mut glyph_resources = lopdf::Dictionary::new();
+
+            let shaped = self.shape(text);
+            for placed in &shaped.glyphs {
+                let form = match glyph_forms.entry((placed.font_slot, placed.glyph_id)) {
+                    std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(e) => {
+                        let form = self.add_glyph_xobject(doc, placed.font_slot, placed.glyph_id)?;
+                        e.insert(form)
+                    }
+                };
+                let Some((name, form_id)) = form else {
+                    continue;
+                };
+                ops.push(Operation::new("q", vec![]));
+                ops.push(Operation::new(
+                    "cm",
+                    vec![
+                        1.into(),
+                        0.into(),
+                        0.into(),
+                        1.into(),
+                        placed.x.into(),
+                        placed.y.into(),
+                    ],
+                ));
+                ops.push(Operation::new("Do", vec![name.as_str().into()]));
+                ops.push(Operation::new("Q", vec![]));
+                glyph_resources.set(name.as_bytes().to_vec(), Object::Reference(*form_id));
+            }
+
+            let bounds = self.painted_bounds(&shaped);
+            let badge_ops = self.badge_ops(&shaped);
+            let mut ids = Vec::new();
+            for color in self.palette() {
+                let mut content = badge_ops.clone();
+                content.extend(self.text_state_ops(color));
+                content.extend(ops.iter().cloned());
+                content.push(Operation::new("Q", vec![]));
+                let mut dict = self.text_xobject_dict(&bounds, color);
+                dict.get_mut(b"Resources")?
+                    .as_dict_mut()?
+                    .set("XObject", glyph_resources.clone());
+                ids.push(doc.add_object(self.encode_stream(dict, content)?));
+            }
+            result.push((ids, bounds));
+        }
+
+        Ok(result)
+    }
+
+    /// 将单个字形嵌入为 Form XObject
+    ///
+    /// # 返回
+    /// - `Some((资源名, 对象ID))`: 字形 XObject
+    /// - `None`: 字形没有轮廓（如空格）
+    fn add_glyph_xobject(
+        &self,
+        doc: &mut Document,
+        font_slot: usize,
+        glyph_id: GlyphId,
+    ) -> Result<Option<(String, ObjectId)>, Box<dyn std::error::Error>> {
+        let path = self
+            .glyph_cache
+            .get_or_insert(&self.fonts[font_slot].font, font_slot, glyph_id, self.font_size);
+        let Some((x_min, y_min, x_max, y_max)) = path_bounds(&path) else {
+            return Ok(None);
+        };
+        let pad = self.style.stroke_extent();
+        let (x_min, y_min, x_max, y_max) = (x_min - pad, y_min - pad, x_max + pad, y_max + pad);
+
+        let mut ops = Vec::with_capacity(path.len() + 1);
+        push_path_ops(&mut ops, &path, 0.0, 0.0);
+        // 绘制路径（颜色与线型继承自调用方）
+        ops.push(Operation::new(self.style.paint_operator(), vec![]));
+
+        let stream = self.encode_stream(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![
+                    x_min.floor().into(),
+                    y_min.floor().into(),
+                    x_max.ceil().into(),
+                    y_max.ceil().into(),
+                ],
+            },
+            ops,
+        )?;
+        let name = format!("G{}_{}", font_slot, glyph_id.0);
+        Ok(Some((name, doc.add_object(stream))))
+    }
+
+    /// 当前缓存的 (已轮廓化文本数, 字形数)
+    pub fn cache_stats(&self) -> (usize, usize) {
+        (self.lock_text_cache().len(), self.glyph_cache.len())
+    }
+
+    /// 清空文本与字形缓存
+    pub fn clear_caches(&self) {
+        self.lock_text_cache().clear();
+        self.glyph_cache.clear();
+    }
+
+    /// 获取文本的轮廓化结果，优先读取缓存
+    fn prepare_text(&self, text: &str) -> Result<Arc<PreparedText>, Box<dyn std::error::Error>> {
+        if let Some(hit) = self.lock_text_cache().get(text) {
+            return Ok(Arc::clone(hit));
+        }
+
+        let shaped = self.shape(text);
+        let paths = text_to_pdf_paths(
+            &self.fonts,
+            &self.glyph_cache,
+            &shaped,
+            0.0,
+            0.0,
+            self.font_size,
+        );
+        let outline = self.shaped_bounds(&shaped);
+        // 渐变只用于有填充、且有轮廓的文本
+        let gradient = self
+            .gradient
+            .as_ref()
+            .filter(|_| self.style.fills() && outline.x_max > outline.x_min && outline.y_max > outline.y_min);
+        let bounds = self.painted_bounds(&shaped);
+        let badge_ops = self.badge_ops(&shaped);
+
+        let mut streams = Vec::new();
+        for color in self.palette() {
+            let mut watermark_ops = badge_ops.clone();
+            watermark_ops.extend(self.text_state_ops(color));
+            if gradient.is_some() {
+                // 以字形轮廓为裁剪路径绘制渐变，描边（如有）在裁剪之外另行绘制
+                watermark_ops.push(Operation::new("q", vec![]));
+                watermark_ops.extend(paths.iter().cloned());
+                watermark_ops.push(Operation::new("W", vec![]));
+                watermark_ops.push(Operation::new("n", vec![]));
+                watermark_ops.push(Operation::new("sh", vec![GRADIENT_SHADING.into()]));
+                watermark_ops.push(Operation::new("Q", vec![]));
+                if self.style.stroke_width() > 0.0 {
+                    watermark_ops.extend(paths.iter().cloned());
+                    watermark_ops.push(Operation::new("S", vec![]));
+                }
+            } else {
+                watermark_ops.extend(paths.iter().cloned());
+                watermark_ops.push(Operation::new(self.style.paint_operator(), vec![])); // 填充 / 描边路径
+            }
+            watermark_ops.push(Operation::new("Q", vec![])); // 恢复图形状态
+
+            let mut dict = self.text_xobject_dict(&bounds, color);
+            if let Some(gradient) = gradient {
+                let shading = gradient.shading(
+                    (outline.x_min, outline.y_min, outline.x_max, outline.y_max),
+                    outline.vertical,
+                );
+                dict.get_mut(b"Resources")?
+                    .as_dict_mut()?
+                    .set("Shading", dictionary! { GRADIENT_SHADING => shading });
+            }
+            streams.push(self.encode_stream(dict, watermark_ops)?);
+        }
+        let prepared = Arc::new(PreparedText { streams, bounds });
+
+        let mut cache = self.lock_text_cache();
+        if cache.len() >= MAX_TEXT_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(text.to_string(), Arc::clone(&prepared));
+        Ok(prepared)
+    }
+
+    /// 测量水印文本的实际尺寸
+    ///
+    /// # 说明
+    /// 包围盒为各字形轮廓（经字形缓存）平移到排版位置后的并集，
+    /// 与嵌入 PDF 的文本 XObject 完全一致，可用于调用方自行排版。
+    /// 不含描边：使用描边样式时，实际绘制范围再向外扩展描边宽度的一半
+    /// （尖角连接时最多为其 4 倍，即斜接限制）。
+    pub fn measure(&self, text: &str) -> TextBounds {
+        self.shaped_bounds(&self.shape(text))
+    }
+
+    /// 按当前塑形方式排版文本
+    fn shape(&self, text: &str) -> ShapedText {
+        shape_text(&self.fonts, text, self.font_size, self.shaping, self.writing_mode)
+    }
+
+    /// 文本 XObject 的基础字典（Form 类型、BBox、水印 ExtGState 与专色颜色空间）
+    ///
+    /// # 说明
+    /// - BBox 由实测文本包围盒外扩得到，不会裁掉长文本或大字号的字形
+    /// - `GS1` 设置文字透明度、`GS2` 设置底框透明度，非 [`BlendMode::Normal`] 时写入 `/BM`
+    /// - 仅在使用专色时写入 ColorSpace 资源，启用透明组时写入 `/Group`
+    fn text_xobject_dict(&self, bounds: &TextBounds, color: &WatermarkColor) -> lopdf::Dictionary {
+        let mut gs = dictionary! {
+            "Type" => "ExtGState",
+            "ca" => self.opacity, // fill alpha
+            "CA" => self.opacity, // stroke alpha
+        };
+        if self.blend_mode != BlendMode::Normal {
+            gs.set("BM", Object::Name(self.blend_mode.pdf_name().as_bytes().to_vec()));
+        }
+        let mut graphics_states = dictionary! { "GS1" => gs };
+        let mut color_spaces = self.style.color_spaces(color);
+        if let Some(badge) = &self.badge {
+            graphics_states.set(badge::BADGE_GRAPHICS_STATE, badge.graphics_state(self.blend_mode));
+            badge.add_color_spaces(&mut color_spaces);
+        }
+        let mut resources = dictionary! {
+            "ExtGState" => graphics_states,
+        };
+        if !color_spaces.is_empty() {
+            resources.set("ColorSpace", color_spaces);
+        }
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => xobject_bbox(bounds),
+            "Matrix" => vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), 0.into()],
+            "Resources" => resources,
+        };
+        if self.transparency_group {
+            dict.set(
+                "Group",
+                dictionary! {
+                    "Type" => "Group",
+                    "S" => "Transparency",
+                    "I" => false,
+                    "K" => false,
+                },
+            );
+        }
+        dict
+    }
+
+    /// 文本 XObject 开头的图形状态操作：保存状态、透明度、颜色与线型
+    ///
+    /// # 说明
+    /// 调用方在路径（或字形 `Do`）之后负责追加绘制操作符与 `Q`。
+    fn text_state_ops(&self, color: &WatermarkColor) -> Vec<Operation> {
+        let mut ops = vec![
+            Operation::new("q", vec![]),
+            Operation::new("gs", vec!["GS1".into()]),
+        ];
+        ops.extend(self.style.state_ops(color));
+        ops
+    }
+
+    /// 文本 XObject 使用的填充颜色：颜色循环的各个颜色，或单一填充颜色
+    fn palette(&self) -> &[WatermarkColor] {
+        match &self.color_cycle {
+            Some(cycle) => cycle.colors(),
+            None => std::slice::from_ref(&self.color),
+        }
+    }
+
+    /// 页面（或图案）资源中各文本 XObject 变体的名称，与 [`Watermarker::palette`] 一一对应
+    fn text_xobject_names(&self) -> Vec<String> {
+        (1..=self.palette().len())
+            .map(|i| format!("Watermark{}", i))
+            .collect()
+    }
+
+    /// 网格单元格的 XObject 名称选择（按行或列循环）
+    fn cell_names<'a>(&self, names: &'a [String]) -> CellNames<'a> {
+        CellNames {
+            names,
+            by_rows: self.color_cycle.as_ref().is_none_or(ColorCycle::by_rows),
+        }
+    }
+
+    /// 计算塑形结果绘制后的范围：文字范围（见 [`Watermarker::stroked_bounds`]）与底框的并集
+    ///
+    /// # 说明
+    /// 用于文本 XObject 的 BBox 与网格裁剪，保证描边与底框不会被裁掉。
+    fn painted_bounds(&self, shaped: &ShapedText) -> TextBounds {
+        let text = self.stroked_bounds(shaped);
+        match &self.badge {
+            Some(badge) => badge.painted_bounds(&text),
+            None => text,
+        }
+    }
+
+    /// 底框的内容流操作（未设置底框时为空）
+    fn badge_ops(&self, shaped: &ShapedText) -> Vec<Operation> {
+        match &self.badge {
+            Some(badge) => badge.ops(&self.stroked_bounds(shaped)),
+            None => Vec::new(),
+        }
+    }
+
+    /// 计算文字绘制后的范围：轮廓包围盒按描边外扩
+    fn stroked_bounds(&self, shaped: &ShapedText) -> TextBounds {
+        let bounds = self.shaped_bounds(shaped);
+        let pad = self.style.stroke_extent();
+        TextBounds {
+            x_min: bounds.x_min - pad,
+            y_min: bounds.y_min - pad,
+            x_max: bounds.x_max + pad,
+            y_max: bounds.y_max + pad,
+            ..bounds
+        }
+    }
+
+    /// 计算塑形结果的实际尺寸（见 [`Watermarker::measure`]）
+    fn shaped_bounds(&self, shaped: &ShapedText) -> TextBounds {
+        let advance = shaped.advance;
+        let mut bounds: Option<(f32, f32, f32, f32)> = None;
+        for placed in &shaped.glyphs {
+            let path = self.glyph_cache.get_or_insert(
+                &self.fonts[placed.font_slot].font,
+                placed.font_slot,
+                placed.glyph_id,
+                self.font_size,
+            );
+            let Some((x0, y0, x1, y1)) = path_bounds(&path) else {
+                continue;
+            };
+            let (x0, y0, x1, y1) = (x0 + placed.x, y0 + placed.y, x1 + placed.x, y1 + placed.y);
+            bounds = Some(match bounds {
+                None => (x0, y0, x1, y1),
+                Some((a, b, c, d)) => (a.min(x0), b.min(y0), c.max(x1), d.max(y1)),
+            });
+        }
+
+        let empty = if shaped.vertical {
+            (0.0, -advance, 0.0, 0.0)
+        } else {
+            (0.0, 0.0, advance, 0.0)
+        };
+        let (x_min, y_min, x_max, y_max) = bounds.unwrap_or(empty);
+        TextBounds {
+            advance,
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            vertical: shaped.vertical,
+        }
+    }
+
+    /// 将操作序列编码为流对象
+    ///
+    /// # 说明
+    /// - 按 [`Watermarker::with_precision`] 舍入坐标
+    /// - 按 [`Watermarker::with_compression`] 进行 Flate 压缩
+    fn encode_stream(
+        &self,
+        dict: lopdf::Dictionary,
+        mut ops: Vec<Operation>,
+    ) -> Result<Stream, Box<dyn std::error::Error>> {
+        if let Some(precision) = self.precision {
+            round_operands(&mut ops, precision);
+        }
+        let content = Content { operations: ops }
+            .encode()
+            .map_err(|e| format!("encode watermark content failed: {:?}", e))?;
+        let mut stream = Stream::new(dict, content);
+        if self.compress {
+            stream.compress()?;
+        }
+        Ok(stream)
+    }
+
+    /// 获取文本缓存锁（缓存内容不会处于不一致状态，忽略锁中毒）
+    fn lock_text_cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<PreparedText>>> {
+        self.text_cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 为所有页面生成水印网格操作
+    ///
+    /// # 说明
+    /// - [`GridMode::Center`] 时每页只在中心放置一个水印
+    /// - 页数少于 [`PARALLEL_PAGE_THRESHOLD`] 或 `workers <= 1` 时串行生成
+    /// - 否则按页面分块，在多个线程中并行生成
+    ///
+    /// # 返回
+    /// 与 `pages` 一一对应的网格操作（或错误信息）
+    fn build_page_grids(
+        &self,
+        pages: &[PageJob],
+        cells: &CellNames,
+        workers: usize,
+    ) -> Vec<Result<Vec<Operation>, String>> {
+        let build = |page: &PageJob| {
+            if self.grid_mode == GridMode::Center {
+                return Ok(build_centered_ops(
+                    cells.get(0, 0),
+                    self.angle,
+                    page.width,
+                    page.height,
+                    &page.text,
+                    page.rotation,
+                ));
+            }
+            // 生成水印网格操作（传入页面旋转角度）
+            build_watermark_grid_ops_xobject_optimized(
+                cells,
+                self.font_size,
+                self.angle,
+                page.width,
+                page.height,
+                &page.text,
+                page.rotation,
+            )
+            .map_err(|e| e.to_string())
+        };
+
+        if workers <= 1 || pages.len() < PARALLEL_PAGE_THRESHOLD {
+            return pages.iter().map(build).collect();
+        }
+
+        let chunk_size = pages.len().div_ceil(workers);
+        thread::scope(|scope| {
+            let handles: Vec<_> = pages
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let handle = scope.spawn(move || chunk.iter().map(build).collect::<Vec<_>>());
+                    (chunk.len(), handle)
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|(len, h)| {
+                    h.join()
+                        .unwrap_or_else(|_| vec![Err("grid worker panicked".to_string()); len])
+                })
+                .collect()
+        })
+    }
+}
+
+/// 在 `workers` 个工作线程中并行执行任务，结果与 `jobs` 一一对应（保持原顺序）
+fn run_parallel<T, F>(
+    workers: usize,
+    jobs: &[BatchJob],
+    run: F,
+) -> Vec<Result<T, Box<dyn std::error::Error>>>
+where
+    T: Send,
+    F: Fn(&BatchJob) -> Result<T, Box<dyn std::error::Error>> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let worker_count = workers.min(jobs.len()).max(1);
+
+    // Box<dyn Error> 不是 Send，线程内先转为 String 再还原
+    let finished: Vec<(usize, Result<T, String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..worker_count)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(idx) else { break };
+                        // 单个畸形文件在解析库中触发 panic 时只记该任务失败，
+                        // 同一线程已完成与后续的任务不受影响
+                        let res = match panic::catch_unwind(AssertUnwindSafe(|| run(job))) {
+                            Ok(res) => res.map_err(|e| e.to_string()),
+                            Err(payload) => Err(format!(
+                                "处理时发生内部错误（panic）: {}",
+                                panic_message(payload.as_ref())
+                            )),
+                        };
+                        done.push((idx, res));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_default())
+            .collect()
+    });
+
+    // 按原顺序归位；任务内的 panic 已在上面捕获，缺失只可能来自线程本身异常退出
+    let mut results: Vec<Option<Result<T, String>>> = (0..jobs.len()).map(|_| None).collect();
+    for (idx, res) in finished {
+        results[idx] = Some(res);
+    }
+    results
+        .into_iter()
+        .map(|res| {
+            res.unwrap_or_else(|| Err("worker thread panicked".to_string()))
+                .map_err(|e| e.into())
+        })
+        .collect()
+}
+
+/// 从 panic 负载中取出消息文本
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "未知错误"
+    }
+}
+
+/// 默认工作线程数（CPU 核心数，无法获取时为 1）
+fn default_worker_count() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// 根据文本包围盒计算 Form XObject 的 BBox
+///
+/// # 说明
+/// `bounds` 应已包含描边范围（见 [`TextStyle`]）；再外扩 [`BBOX_PADDING`]
+/// 并向外取整，避免边缘被抗锯齿或舍入裁掉。
+fn xobject_bbox(bounds: &TextBounds) -> Vec<Object> {
+    let pad = BBOX_PADDING;
+    vec![
+        (bounds.x_min - pad).floor().into(),
+        (bounds.y_min - pad).floor().into(),
+        (bounds.x_max + pad).ceil().into(),
+        (bounds.y_max + pad).ceil().into(),
+    ]
+}
+
+/// 将操作数中的实数舍入到指定小数位数
+///
+/// # 说明
+/// `cm` 的旋转系数至少保留 [`MATRIX_MIN_PRECISION`] 位小数，平移量按 `precision` 舍入。
+fn round_operands(ops: &mut [Operation], precision: u8) {
+    let round = |v: f32, digits: u8| {
+        let factor = 10f32.powi(digits as i32);
+        let r = (v * factor).round() / factor;
+        // 避免输出 "-0"
+        if r == 0.0 { 0.0 } else { r }
+    };
+    for op in ops {
+        let is_matrix = op.operator == "cm";
+        for (i, operand) in op.operands.iter_mut().enumerate() {
+            if let Object::Real(v) = operand {
+                let digits = if is_matrix && i < 4 {
+                    precision.max(MATRIX_MIN_PRECISION)
+                } else {
+                    precision
+                };
+                *v = round(*v, digits);
+            }
+        }
+    }
+}
+
+/// 将内容流对象的引用追加到页面 `Contents` 末尾
+///
+/// # 说明
+/// - 原有内容流保持不变；`Contents` 为单个引用时转换为数组
+/// - 同一内容流可被多个页面引用（共享网格）
+fn append_page_content(
+    doc: &mut Document,
+    page_id: ObjectId,
+    stream_id: ObjectId,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut contents: Vec<Object> = match doc.get_dictionary(page_id)?.get(b"Contents") {
+        // Contents 也可能是指向数组对象的间接引用
+        Ok(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(arr)) => arr.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        Ok(Object::Array(arr)) => arr.clone(),
+        _ => vec![],
+    };
+    contents.push(Object::Reference(stream_id));
+    doc.get_dictionary_mut(page_id)?.set("Contents", contents);
+    Ok(())
+}
+
+/// 解析 UTC 偏移量
+///
+/// # 说明
+/// 支持 `Z` / `UTC`、`+08:00`、`+0800`、`+8`、`-05:30` 等写法，范围为 ±23:59。
+pub(crate) fn parse_utc_offset(offset: &str) -> Result<FixedOffset, Box<dyn std::error::Error>> {
+    let trimmed = offset.trim();
+    if trimmed.eq_ignore_ascii_case("z") || trimmed.eq_ignore_ascii_case("utc") {
+        return Ok(FixedOffset::east_opt(0).expect("zero offset is valid"));
+    }
+    let invalid = || format!("无效的 UTC 偏移量 '{}'，应为 +08:00、-0530、Z 等形式", offset);
+    let (sign, rest) = match trimmed.as_bytes().first() {
+        Some(b'+') => (1, &trimmed[1..]),
+        Some(b'-') => (-1, &trimmed[1..]),
+        _ => return Err(invalid().into()),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let (Ok(hours), Ok(minutes)) = (hours.parse::<i32>(), minutes.parse::<i32>()) else {
+        return Err(invalid().into());
+    };
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+        return Err(invalid().into());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(|| invalid().into())
+}
+
+/// 按 strftime 格式格式化时间；格式串无效时返回 `None`
+fn format_datetime<Tz: TimeZone>(time: &DateTime<Tz>, format: &str) -> Option<String>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return None;
+    }
+    Some(time.format_with_items(items.into_iter()).to_string())
+}
+
+/// 文本中是否仍含有页码占位符（`{page}` / `{pages}`）
+fn has_page_placeholders(text: &str) -> bool {
+    text.contains("{page}") || text.contains("{pages}")
+}
+
+/// 渲染水印模板
+///
+/// # 说明
+/// - `{key}` 被替换为 `vars` 中对应的值
+/// - 未提供的占位符原样保留，便于排查模板错误
+fn render_template(template: &str, vars: &TemplateVars) -> String {
+    replace_placeholders(template, |key| vars.get(key).cloned())
+}
+
+/// 替换模板中的 `{...}` 占位符
+///
+/// # 参数
+/// - `resolve`: 根据花括号内的内容返回替换值，返回 `None` 时占位符原样保留
+fn replace_placeholders(template: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let key = &after[..close];
+                match resolve(key) {
+                    Some(value) => out.push_str(&value),
+                    None => {
+                        out.push('{');
+                        out.push_str(key);
+                        out.push('}');
+                    }
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// ============================================================================
+// 字形轮廓缓存 - Glyph cache
+// ============================================================================
+
+/// 已缩放到目标字号、以字形原点为基准的路径段
+#[derive(Debug, Clone, Copy)]
+enum PathSeg {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// 字形缓存键：(字体槽位, 字形ID, 字号的位表示)
+type GlyphKey = (usize, GlyphId, u32);
+
+/// 字形轮廓缓存
+///
+/// # 说明
+/// - 以 `(字体, glyph_id, 字号)` 为键缓存转换后的路径段
+/// - 不同收件人姓名通常共享大部分字符，跨调用复用可显著减少轮廓计算
+/// - 线程安全，可被并行批量处理共享
+struct GlyphCache {
+    map: Mutex<HashMap<GlyphKey, Arc<[PathSeg]>>>,
+}
+
+impl GlyphCache {
+    fn new() -> Self {
+        Self {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取字形路径，未命中时提取轮廓并写入缓存
+    ///
+    /// # 参数
+    /// - `font`: 字体引用
+    /// - `font_slot`: 字体在水印器中的槽位（区分不同字体的相同 glyph_id）
+    /// - `glyph_id`: 字形ID
+    /// - `size`: 字体大小（点数）
+    fn get_or_insert<F: Font>(
+        &self,
+        font: &F,
+        font_slot: usize,
+        glyph_id: GlyphId,
+        size: f32,
+    ) -> Arc<[PathSeg]> {
+        let key = (font_slot, glyph_id, size.to_bits());
+        if let Some(hit) = self.lock().get(&key) {
+            return Arc::clone(hit);
+        }
+
+        // 轮廓提取在锁外进行，避免阻塞其他线程
+        let path: Arc<[PathSeg]> = outline_glyph_path(font, glyph_id, size).into();
+        let mut map = self.lock();
+        if map.len() >= MAX_GLYPH_CACHE_ENTRIES {
+            map.clear();
+        }
+        map.insert(key, Arc::clone(&path));
+        path
+    }
+
+    fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// 获取缓存锁（缓存内容不会处于不一致状态，忽略锁中毒）
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<GlyphKey, Arc<[PathSeg]>>> {
+        self.map.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// ============================================================================
+// 内部算法逻辑 (私有函数)
+// ============================================================================
+
+/// 按文件路径或系统字体名称加载字体
+///
+/// # 说明
+/// `spec` 的形式：
+/// - `path`：字体文件（字体集合取第 0 个字面）
+/// - `path#N`：字体集合（`.ttc`/`.otc`）中索引为 `N` 的字面
+/// - `path#名称`：字体集合中按名称匹配的字面（如 `NotoSansCJK.ttc#Noto Sans CJK SC`）
+/// - 其他：在系统字体索引中按名称查找
+fn load_font_spec(spec: &str) -> Result<LoadedFont, Box<dyn std::error::Error>> {
+    if Path::new(spec).is_file() {
+        return load_font_face(Path::new(spec), 0);
+    }
+    if let Some((path, face)) = spec.rsplit_once('#')
+        && Path::new(path).is_file()
+    {
+        let index = match face.trim().parse::<u32>() {
+            Ok(index) => index,
+            Err(_) => {
+                let faces = collection_faces(path)
+                    .map_err(|e| format!("read font {} failed: {}", path, e))?;
+                match font_index::find_entry(&faces, face) {
+                    Some(entry) => entry.index,
+                    None => {
+                        let available: Vec<String> = faces
+                            .iter()
+                            .map(|e| format!("{}: {} {}", e.index, e.family, e.style))
+                            .collect();
+                        return Err(format!(
+                            "no face named {:?} in {} (available: {})",
+                            face,
+                            path,
+                            available.join(", ")
+                        )
+                        .into());
+                    }
+                }
+            }
+        };
+        return load_font_face(Path::new(path), index);
+    }
+    let index = FontIndex::system();
+    let entry = index
+        .find(spec)
+        .ok_or_else(|| format!("font not found: {} (neither a file nor an installed font name)", spec))?;
+    load_font_face(&entry.path, entry.index)
+}
+
+/// 加载字体文件中指定索引的字面
+fn load_font_face(path: &Path, index: u32) -> Result<LoadedFont, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path)
+        .map_err(|e| format!("read font {} failed: {}", path.display(), e))?;
+    font_from_data(data, index).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// 从字体数据中解析指定索引的字面
+fn font_from_data(data: Vec<u8>, index: u32) -> Result<LoadedFont, String> {
+    let face_count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+    if index >= face_count {
+        return Err(format!(
+            "face index {} out of range (font has {} face(s))",
+            index, face_count
+        ));
+    }
+    FontVec::try_from_vec_and_index(data, index)
+        .map(|font| LoadedFont::new(font, index))
+        .map_err(|e| format!("parse font face {} failed: {}", index, e))
+}
+
+/// 将文本转换为PDF路径操作序列
+///
+/// # 功能
+/// - 按塑形结果中每个字形的位置
+/// - 从字形缓存获取（或提取）字形轮廓
+/// - 将轮廓平移到字形位置并转换为PDF图形操作指令
+///
+/// # 参数
+/// - `fonts`: 字体回退链
+/// - `glyphs`: 字形轮廓缓存
+/// - `shaped`: 文本的塑形结果
+/// - `x_start`: 水平起始位置
+/// - `y_start`: 垂直起始位置
+/// - `size`: 字体大小（点数）
+///
+/// # 返回
+/// PDF路径操作向量（包括移动、线段、贝塞尔曲线等），不含图形状态与绘制操作符
+fn text_to_pdf_paths(
+    fonts: &[LoadedFont],
+    glyphs: &GlyphCache,
+    shaped: &ShapedText,
+    x_start: f32,
+    y_start: f32,
+    size: f32,
+) -> Vec<Operation> {
+    let mut ops = Vec::new();
+    for placed in &shaped.glyphs {
+        let font = &fonts[placed.font_slot].font;
+        let path = glyphs.get_or_insert(font, placed.font_slot, placed.glyph_id, size);
+        push_path_ops(&mut ops, &path, x_start + placed.x, y_start + placed.y);
+    }
+    ops
+}
+
+/// 将已缓存的字形路径平移后追加为PDF路径操作
+fn push_path_ops(ops: &mut Vec<Operation>, path: &[PathSeg], dx: f32, dy: f32) {
+    for seg in path {
+        match *seg {
+            PathSeg::MoveTo(x, y) => {
+                ops.push(Operation::new("m", vec![(dx + x).into(), (dy + y).into()]));
+            }
+            PathSeg::LineTo(x, y) => {
+                ops.push(Operation::new("l", vec![(dx + x).into(), (dy + y).into()]));
+            }
+            PathSeg::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                ops.push(Operation::new(
+                    "c",
+                    vec![
+                        (dx + x1).into(),
+                        (dy + y1).into(),
+                        (dx + x2).into(),
+                        (dy + y2).into(),
+                        (dx + x3).into(),
+                        (dy + y3).into(),
+                    ],
+                ));
+            }
+            PathSeg::Close => ops.push(Operation::new("h", vec![])),
+        }
+    }
+}
+
+/// 计算路径段的包围盒（包含贝塞尔控制点，结果偏保守）
+///
+/// # 返回
+/// - `Some((x_min, y_min, x_max, y_max))`
+/// - `None`: 路径为空
+fn path_bounds(path: &[PathSeg]) -> Option<(f32, f32, f32, f32)> {
+    let mut bounds: Option<(f32, f32, f32, f32)> = None;
+    let mut include = |x: f32, y: f32| {
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    };
+    for seg in path {
+        match *seg {
+            PathSeg::MoveTo(x, y) | PathSeg::LineTo(x, y) => include(x, y),
+            PathSeg::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                include(x1, y1);
+                include(x2, y2);
+                include(x3, y3);
+            }
+            PathSeg::Close => {}
+        }
+    }
+    bounds
+}
+
+/// 提取单个字形的轮廓，转换为以原点为基准、已按字号缩放的路径段
+///
+/// # 说明
+/// - 二次贝塞尔转换为三次贝塞尔（PDF只支持三次）
+/// - 相邻曲线端点不连续时视为新的子轮廓
+fn outline_glyph_path<F: Font>(font: &F, glyph_id: GlyphId, size: f32) -> Vec<PathSeg> {
+    let scaled_font = font.as_scaled(PxScale::from(size));
+    let h_factor = scaled_font.h_scale_factor();
+    let v_factor = scaled_font.v_scale_factor();
+    let pt = |p: Point| (p.x * h_factor, p.y * v_factor);
+
+    let mut path = Vec::new();
+    let Some(outline) = font.outline(glyph_id) else {
+        return path;
+    };
+
+    // 使用 Option<Point> 替代 NaN 作为轮廓分界的标记
+    let mut last_point: Option<Point> = None;
+    for curve in outline.curves {
+        let p0 = match curve {
+            OutlineCurve::Line(p0, _) => p0,
+            OutlineCurve::Quad(p0, _, _) => p0,
+            OutlineCurve::Cubic(p0, _, _, _) => p0,
+        };
+
+        // 判断是否为新轮廓（新的子轮廓起点）
+        let is_new_contour = match last_point {
+            None => true,
+            Some(lp) => ((p0.x - lp.x).abs() > 0.001) || ((p0.y - lp.y).abs() > 0.001),
+        };
+
+        if is_new_contour {
+            if last_point.is_some() {
+                path.push(PathSeg::Close); // 闭合上一个轮廓
+            }
+            let (x, y) = pt(p0);
+            path.push(PathSeg::MoveTo(x, y)); // 移动到新起点
+        }
+
+        match curve {
+            OutlineCurve::Line(_, p1) => {
+                let (x, y) = pt(p1);
+                path.push(PathSeg::LineTo(x, y));
+                last_point = Some(p1);
+            }
+            OutlineCurve::Quad(_, p1, p2) => {
+                // 将二次贝塞尔转换为三次贝塞尔（PDF只支持三次）
+                let q1 = Point {
+                    x: p0.x + (2.0 / 3.0) * (p1.x - p0.x),
+                    y: p0.y + (2.0 / 3.0) * (p1.y - p0.y),
+                };
+                let q2 = Point {
+                    x: p2.x + (2.0 / 3.0) * (p1.x - p2.x),
+                    y: p2.y + (2.0 / 3.0) * (p1.y - p2.y),
+                };
+                let ((x1, y1), (x2, y2), (x3, y3)) = (pt(q1), pt(q2), pt(p2));
+                path.push(PathSeg::CurveTo(x1, y1, x2, y2, x3, y3));
+                last_point = Some(p2);
+            }
+            OutlineCurve::Cubic(_, p1, p2, p3) => {
+                let ((x1, y1), (x2, y2), (x3, y3)) = (pt(p1), pt(p2), pt(p3));
+                path.push(PathSeg::CurveTo(x1, y1, x2, y2, x3, y3));
+                last_point = Some(p3);
+            }
+        }
+    }
+    if last_point.is_some() {
+        path.push(PathSeg::Close); // 闭合最后一个轮廓
+    }
 
-    ops
-}
-
-/// 计算文本宽度
-///
-/// # 参数
-/// - `font`: 字体引用
-/// - `text`: 文本内容
-/// - `size`: 字体大小
-///
-/// # 返回
-/// 文本总宽度（点数）
-fn measure_text_width(font: &FontRef, text: &str, size: f32) -> f32 {
-    let scaled = font.as_scaled(PxScale::from(size));
-    let mut w = 0.0;
-    for c in text.chars() {
-        w += scaled.h_advance(font.glyph_id(c));
-    }
-    w
+    path
 }
 
 /// 从PDF页面对象中提取媒体框尺寸
@@ -500,79 +2970,140 @@ fn get_page_rotation(doc: &Document, page_id: ObjectId) -> f32 {
     0.0
 }
 
-/// 将XObject资源添加到PDF页面
+/// 将资源条目添加到PDF页面
 ///
 /// # 说明
-/// 创建或更新页面的Resources > XObject字典，
-/// 使其能引用水印XObject对象
-fn add_xobject_to_page(
+/// - 创建或更新页面的 Resources > `category` 字典（如 XObject、Pattern），
+///   使其能引用水印相关对象
+/// - 页面 Resources 为间接引用或继承自父节点时，复制为页面自己的内联字典后再修改，
+///   避免影响共享同一资源字典的其他页面，也不会丢失继承的字体等资源
+fn add_page_resource(
     doc: &mut Document,
     page_id: ObjectId,
-    x_name: &str,
-    x_id: ObjectId,
+    category: &str,
+    name: &str,
+    id: ObjectId,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let obj = doc.get_object_mut(page_id)?;
-    match obj {
-        Object::Dictionary(page_dict) => {
-            if !page_dict.has(b"Resources") {
-                page_dict.set(b"Resources", dictionary! {});
-            }
-            let resources = page_dict.get_mut(b"Resources")?.as_dict_mut()?;
-            if !resources.has(b"XObject") {
-                resources.set(b"XObject", dictionary! {});
-            }
-            let xobjects = resources.get_mut(b"XObject")?.as_dict_mut()?;
-            xobjects.set(x_name.as_bytes().to_vec(), Object::Reference(x_id));
-            Ok(())
-        }
-        Object::Stream(s) => {
-            let page_dict = &mut s.dict;
-            if !page_dict.has(b"Resources") {
-                page_dict.set(b"Resources", dictionary! {});
-            }
-            let resources = page_dict.get_mut(b"Resources")?.as_dict_mut()?;
-            if !resources.has(b"XObject") {
-                resources.set(b"XObject", dictionary! {});
-            }
-            let xobjects = resources.get_mut(b"XObject")?.as_dict_mut()?;
-            xobjects.set(x_name.as_bytes().to_vec(), Object::Reference(x_id));
-            Ok(())
+    let mut resources = effective_resources(doc, page_id)?;
+    let mut entries = match resources.get(category.as_bytes()) {
+        Ok(Object::Dictionary(d)) => d.clone(),
+        Ok(Object::Reference(r)) => doc.get_dictionary(*r).cloned().unwrap_or_default(),
+        _ => lopdf::Dictionary::new(),
+    };
+    entries.set(name.as_bytes().to_vec(), Object::Reference(id));
+    resources.set(category.as_bytes().to_vec(), entries);
+
+    let page_dict = match doc.get_object_mut(page_id)? {
+        Object::Dictionary(d) => d,
+        Object::Stream(s) => &mut s.dict,
+        _ => return Err("page object is not a Dictionary or Stream".into()),
+    };
+    page_dict.set("Resources", resources);
+    Ok(())
+}
+
+/// 获取页面当前生效的 Resources 字典副本
+///
+/// # 说明
+/// - 依次查找页面及其父节点（与 Rotate 一样，Resources 可继承）
+/// - 解析间接引用
+/// - 都不存在时返回空字典
+fn effective_resources(
+    doc: &Document,
+    page_id: ObjectId,
+) -> Result<lopdf::Dictionary, Box<dyn std::error::Error>> {
+    const MAX_PARENT_DEPTH: usize = 10;
+    let mut current_id = Some(page_id);
+    let mut depth = 0usize;
+
+    while let Some(id) = current_id {
+        if depth > MAX_PARENT_DEPTH {
+            break;
+        }
+        let dict = match doc.get_object(id)? {
+            Object::Dictionary(d) => d,
+            Object::Stream(s) => &s.dict,
+            _ => return Err("page object is not a Dictionary or Stream".into()),
+        };
+        match dict.get(b"Resources") {
+            Ok(Object::Dictionary(d)) => return Ok(d.clone()),
+            Ok(Object::Reference(r)) => return Ok(doc.get_dictionary(*r)?.clone()),
+            _ => {}
         }
-        _ => Err("page object is not a Dictionary or Stream".into()),
+        current_id = dict.get(b"Parent").and_then(Object::as_reference).ok();
+        depth += 1;
+    }
+    Ok(lopdf::Dictionary::new())
+}
+
+/// 计算水印网格间距：(u 方向, v 方向)，u/v 为文本自身坐标系的 x/y 轴
+///
+/// # 说明
+/// 沿书写方向的间距为文本 advance + [`GRID_HORIZONTAL_GAP`]，
+/// 垂直于书写方向的间距为 `size * GRID_VERTICAL_MULTIPLIER`；竖排时二者交换轴向。
+fn grid_steps(size: f32, text: &TextBounds) -> (f32, f32) {
+    let along = text.advance + GRID_HORIZONTAL_GAP;
+    let across = size * GRID_VERTICAL_MULTIPLIER;
+    if text.vertical {
+        (across, along)
+    } else {
+        (along, across)
+    }
+}
+
+/// 网格单元格引用的文本 XObject 资源名
+///
+/// # 说明
+/// 只有一个名称时所有单元格相同；颜色循环时按单元格所在的行（v 索引）
+/// 或列（u 索引）循环选用，索引 0（页面中心）对应第一个名称。
+struct CellNames<'a> {
+    names: &'a [String],
+    by_rows: bool,
+}
+
+impl CellNames<'_> {
+    /// 网格索引 (u, v) 处单元格的资源名
+    fn get(&self, ui: isize, vi: isize) -> &str {
+        let index = if self.by_rows { vi } else { ui };
+        &self.names[index.rem_euclid(self.names.len() as isize) as usize]
     }
 }
 
 /// 生成水印网格PDF操作指令（优化版本）
 ///
 /// # 功能
-/// - 基于旋转角度计算水印网格位置
+/// - 基于旋转角度计算水印网格位置（网格原点位于页面中心）
 /// - 支持 PDF 页面旋转（90°、180°、270°）
 /// - 生成PDF操作指令来绘制网格中的水印
-/// - 裁剪超出页面可见区域的水印以优化性能
+/// - 精确裁剪：只保留旋转后的文本包围盒与页面相交的单元格
+///
+/// # 裁剪算法
+/// 文本包围盒与页面都是矩形，使用分离轴定理在四条轴上做投影检测：
+/// - 文本的两条轴（u/v）：直接换算为网格索引范围，范围外的单元格不会被遍历
+/// - 页面的两条轴（x/y）：对范围内每个单元格检测旋转后包围盒的 x/y 区间
 ///
 /// # 参数
-/// - `x_name`: XObject资源名称
+/// - `cells`: 各单元格的文本XObject资源名称（颜色循环时按行或列变化）
 /// - `size`: 字体大小（用于计算垂直间距）
 /// - `angle`: 水印旋转角度（度数）
 /// - `width`: 页面宽度
 /// - `height`: 页面高度
-/// - `text_w`: 文本宽度（预计算）
+/// - `text`: 文本尺寸（预计算，advance 用于沿书写方向的间距，包围盒用于裁剪）
 /// - `page_rotation`: 页面旋转角度（度数，来自 PDF Rotate 属性）
 ///
 /// # 返回
 /// - `Ok(Vec<Operation>)`: PDF操作指令向量
 /// - `Err`: 参数错误或水印数量超限
 fn build_watermark_grid_ops_xobject_optimized(
-    x_name: &str,
+    cells: &CellNames,
     size: f32,
     angle: f32,
     width: f32,
     height: f32,
-    text_w: f32,
+    text: &TextBounds,
     page_rotation: f32,
 ) -> Result<Vec<Operation>, Box<dyn std::error::Error>> {
-    let step_inner = text_w + GRID_HORIZONTAL_GAP;
-    let step_outer = size * GRID_VERTICAL_MULTIPLIER;
+    let (step_inner, step_outer) = grid_steps(size, text);
 
     // 添加最小间距校验，防止过度计算
     if !(step_inner > MIN_GRID_STEP_SIZE && step_outer > MIN_GRID_STEP_SIZE) {
@@ -590,21 +3121,33 @@ fn build_watermark_grid_ops_xobject_optimized(
 
     let mut ops = Vec::new();
 
-    // 计算覆盖范围
-    let diag = (width.powi(2) + height.powi(2)).sqrt() * COVERAGE_MULTIPLIER;
     let cx = width / 2.0 + CENTER_X_OFFSET;
     let cy = height / 2.0 - CENTER_Y_OFFSET;
 
-    // 计算索引上限，避免浮点累积误差与无限循环
-    let v_start = -diag - 200.0;
-    let v_end = diag;
-    let total_v_span = v_end - v_start;
-    let v_count = ((total_v_span / step_outer).ceil() as isize).max(0) as usize;
+    // 页面四角投影到网格 u/v 轴（相对网格原点）的区间
+    let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)];
+    let (mut pu_min, mut pu_max) = (f32::INFINITY, f32::NEG_INFINITY);
+    let (mut pv_min, mut pv_max) = (f32::INFINITY, f32::NEG_INFINITY);
+    for (px, py) in corners {
+        let (dx, dy) = (px - cx, py - cy);
+        let pu = dx * c + dy * s;
+        let pv = -dx * s + dy * c;
+        pu_min = pu_min.min(pu);
+        pu_max = pu_max.max(pu);
+        pv_min = pv_min.min(pv);
+        pv_max = pv_max.max(pv);
+    }
+
+    // u/v 轴上的分离轴检测：单元格原点为 u 时文本占据 [u + x_min, u + x_max]，
+    // 与页面投影区间相交的 u 取值换算为索引范围（v 方向同理）
+    let ui_start = ((pu_min - text.x_max) / step_inner).ceil() as isize;
+    let ui_end = ((pu_max - text.x_min) / step_inner).floor() as isize;
+    let vi_start = ((pv_min - text.y_max) / step_outer).ceil() as isize;
+    let vi_end = ((pv_max - text.y_min) / step_outer).floor() as isize;
 
-    let u_start = -diag;
-    let u_end = diag;
-    let total_u_span = u_end - u_start;
-    let u_count = ((total_u_span / step_inner).ceil() as isize).max(0) as usize;
+    // 计算索引上限，避免浮点累积误差与无限循环
+    let u_count = (ui_end - ui_start + 1).max(0) as usize;
+    let v_count = (vi_end - vi_start + 1).max(0) as usize;
 
     // 防止生成过多水印对象导致性能问题
     let estimated = v_count.saturating_mul(u_count);
@@ -612,42 +3155,902 @@ fn build_watermark_grid_ops_xobject_optimized(
         return Err(format!("Too many watermarks to render: {}", estimated).into());
     }
 
+    // 旋转后文本包围盒相对单元格原点在页面 x/y 轴上的区间
+    let box_corners = [
+        (text.x_min, text.y_min),
+        (text.x_max, text.y_min),
+        (text.x_min, text.y_max),
+        (text.x_max, text.y_max),
+    ];
+    let (mut bx_min, mut bx_max) = (f32::INFINITY, f32::NEG_INFINITY);
+    let (mut by_min, mut by_max) = (f32::INFINITY, f32::NEG_INFINITY);
+    for (lx, ly) in box_corners {
+        let bx = lx * c - ly * s;
+        let by = lx * s + ly * c;
+        bx_min = bx_min.min(bx);
+        bx_max = bx_max.max(bx);
+        by_min = by_min.min(by);
+        by_max = by_max.max(by);
+    }
+
     // 使用整数循环消除浮点累积误差
-    for vi in 0..=v_count {
-        let v = v_start + (vi as f32) * step_outer;
-        for ui in 0..=u_count {
-            let u = u_start + (ui as f32) * step_inner;
+    for vi in vi_start..=vi_end {
+        let v = (vi as f32) * step_outer;
+        for ui in ui_start..=ui_end {
+            let u = (ui as f32) * step_inner;
             // 应用2D旋转变换
             let x = cx + u * c - v * s;
             let y = cy + u * s + v * c;
 
-            // 裁剪超出页面可见区域的水印
-            if x > -VISIBILITY_MARGIN
-                && x < width + VISIBILITY_MARGIN
-                && y > -VISIBILITY_MARGIN
-                && y < height + VISIBILITY_MARGIN
+            // x/y 轴上的分离轴检测：包围盒完全在页面之外则跳过
+            if x + bx_max < 0.0 || x + bx_min > width || y + by_max < 0.0 || y + by_min > height
             {
-                ops.push(Operation::new("q", vec![])); // 保存图形状态
-                // cm 操作参数顺序：a b c d e f
-                // | a c e |   | cos  -sin  x |
-                // | b d f | = | sin   cos  y |
-                // | 0 0 1 |   | 0     0    1 |
-                ops.push(Operation::new(
-                    "cm",
-                    vec![
-                        c.into(),
-                        s.into(),
-                        (-s).into(),
-                        c.into(),
-                        x.into(),
-                        y.into(),
-                    ],
-                ));
-                ops.push(Operation::new("Do", vec![x_name.into()])); // 绘制XObject
-                ops.push(Operation::new("Q", vec![])); // 恢复图形状态
+                continue;
             }
+
+            ops.push(Operation::new("q", vec![])); // 保存图形状态
+            // cm 操作参数顺序：a b c d e f
+            // | a c e |   | cos  -sin  x |
+            // | b d f | = | sin   cos  y |
+            // | 0 0 1 |   | 0     0    1 |
+            ops.push(Operation::new(
+                "cm",
+                vec![
+                    c.into(),
+                    s.into(),
+                    (-s).into(),
+                    c.into(),
+                    x.into(),
+                    y.into(),
+                ],
+            ));
+            ops.push(Operation::new("Do", vec![cells.get(ui, vi).into()])); // 绘制XObject
+            ops.push(Operation::new("Q", vec![])); // 恢复图形状态
         }
     }
 
     Ok(ops)
-}
\ No newline at end of file
+}
+
+/// 生成页面中心单个水印的PDF操作指令
+///
+/// # 说明
+/// 文本包围盒的中心经旋转后与页面中心重合（叠加页面旋转角度），
+/// 因此不同长度的文本都视觉居中。
+///
+/// # 参数
+/// - `name`: 文本XObject资源名称
+/// - 其余参数与 [`build_watermark_grid_ops_xobject_optimized`] 相同
+fn build_centered_ops(
+    name: &str,
+    angle: f32,
+    width: f32,
+    height: f32,
+    text: &TextBounds,
+    page_rotation: f32,
+) -> Vec<Operation> {
+    let rad = (angle + page_rotation).to_radians();
+    let (c, s) = (rad.cos(), rad.sin());
+
+    // 文本坐标系中的包围盒中心，旋转后平移到页面中心
+    let mx = (text.x_min + text.x_max) / 2.0;
+    let my = (text.y_min + text.y_max) / 2.0;
+    let x = width / 2.0 + CENTER_X_OFFSET - (mx * c - my * s);
+    let y = height / 2.0 - CENTER_Y_OFFSET - (mx * s + my * c);
+
+    vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "cm",
+            vec![c.into(), s.into(), (-s).into(), c.into(), x.into(), y.into()],
+        ),
+        Operation::new("Do", vec![name.into()]),
+        Operation::new("Q", vec![]),
+    ]
+}
+
+/// 生成水印平铺图案（Tiling Pattern）
+///
+/// # 说明
+/// - 单元格间距与显式网格相同（`XStep` = 文本宽度 + 水平间距，`YStep` = 垂直间距）
+/// - 图案矩阵包含旋转（叠加页面旋转），并使某个单元格的文本原点落在页面中心
+/// - 单元格内容为一次文本 XObject 调用，文本在单元格内水平留出半个间距、垂直居中
+/// - 颜色循环时单元格沿循环方向扩大为 N 行（列），依次调用 N 个颜色的文本 XObject
+/// - 图案 BBox 取文本实测包围盒，超出间距时相邻单元格允许重叠，不会裁掉字形
+///
+/// # 参数
+/// - `cells`: 文本XObject资源名称（颜色循环时每个颜色一个）
+/// - `x_ids`: 与 `cells` 中名称一一对应的文本XObject对象ID
+/// - 其余参数与 [`build_watermark_grid_ops_xobject_optimized`] 相同
+///
+/// # 返回
+/// - `Ok((图案字典, 单元格内容操作))`
+/// - `Err`: 网格间距过小
+#[allow(clippy::too_many_arguments)]
+fn build_watermark_pattern(
+    cells: &CellNames,
+    x_ids: &[ObjectId],
+    size: f32,
+    angle: f32,
+    width: f32,
+    height: f32,
+    text: &TextBounds,
+    page_rotation: f32,
+) -> Result<(lopdf::Dictionary, Vec<Operation>), Box<dyn std::error::Error>> {
+    let (step_inner, step_outer) = grid_steps(size, text);
+
+    if !(step_inner > MIN_GRID_STEP_SIZE && step_outer > MIN_GRID_STEP_SIZE) {
+        return Err(format!(
+            "Grid step too small: inner={}, outer={}",
+            step_inner, step_outer
+        )
+        .into());
+    }
+
+    // 叠加页面旋转角度，确保水印相对于内容方向正确
+    let rad = (angle + page_rotation).to_radians();
+    let (c, s) = (rad.cos(), rad.sin());
+
+    // 单元格内的文本原点（竖排时位于单元格顶部的水平中点）
+    let (ox, oy) = if text.vertical {
+        (step_inner / 2.0, step_outer - GRID_HORIZONTAL_GAP / 2.0)
+    } else {
+        (GRID_HORIZONTAL_GAP / 2.0, step_outer / 2.0)
+    };
+
+    // 平移使该原点经旋转后落在页面中心
+    let cx = width / 2.0 + CENTER_X_OFFSET;
+    let cy = height / 2.0 - CENTER_Y_OFFSET;
+    let tx = cx - (ox * c - oy * s);
+    let ty = cy - (ox * s + oy * c);
+
+    // 颜色循环：单元格沿循环方向包含 N 个文本，相邻文本相距一个网格间距
+    let repeats = cells.names.len();
+    let (du, dv) = if cells.by_rows {
+        (0.0, step_outer)
+    } else {
+        (step_inner, 0.0)
+    };
+    let extra = (repeats - 1) as f32;
+
+    // 单元格坐标系中的文本包围盒（N 个文本的并集）
+    let cell_bounds = TextBounds {
+        x_min: text.x_min + ox,
+        y_min: text.y_min + oy,
+        x_max: text.x_max + ox + du * extra,
+        y_max: text.y_max + oy + dv * extra,
+        ..*text
+    };
+
+    let mut xobjects = lopdf::Dictionary::new();
+    let mut ops = Vec::with_capacity(repeats * 4);
+    for (k, (name, id)) in cells.names.iter().zip(x_ids).enumerate() {
+        let k = k as f32;
+        xobjects.set(name.as_bytes().to_vec(), Object::Reference(*id));
+        ops.push(Operation::new("q", vec![]));
+        ops.push(Operation::new(
+            "cm",
+            vec![
+                1.into(),
+                0.into(),
+                0.into(),
+                1.into(),
+                (ox + du * k).into(),
+                (oy + dv * k).into(),
+            ],
+        ));
+        ops.push(Operation::new("Do", vec![name.as_str().into()]));
+        ops.push(Operation::new("Q", vec![]));
+    }
+
+    let dict = dictionary! {
+        "Type" => "Pattern",
+        "PatternType" => 1,  // 平铺图案
+        "PaintType" => 1,    // 彩色图案（颜色由单元格内容决定）
+        "TilingType" => 1,   // 固定间距
+        "BBox" => xobject_bbox(&cell_bounds),
+        "XStep" => step_inner + du * extra,
+        "YStep" => step_outer + dv * extra,
+        "Matrix" => vec![c.into(), s.into(), (-s).into(), c.into(), tx.into(), ty.into()],
+        "Resources" => dictionary! {
+            "XObject" => xobjects,
+        },
+    };
+
+    Ok((dict, ops))
+}
+
+/// 生成用平铺图案填充整个页面的操作指令
+fn build_pattern_fill_ops(pattern_name: &str, width: f32, height: f32) -> Vec<Operation> {
+    vec![
+        Operation::new("q", vec![]),
+        Operation::new("cs", vec!["Pattern".into()]),
+        Operation::new("scn", vec![pattern_name.into()]),
+        Operation::new(
+            "re",
+            vec![0.into(), 0.into(), width.into(), height.into()],
+        ),
+        Operation::new("f", vec![]),
+        Operation::new("Q", vec![]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_font::{self, TestFont};
+
+    fn job(input: &str) -> BatchJob {
+        BatchJob {
+            input: input.to_string(),
+            output: format!("{}.out", input),
+            vars: TemplateVars::new(),
+        }
+    }
+
+    fn report(pages: usize) -> WatermarkReport {
+        WatermarkReport {
+            pages,
+            input_bytes: 0,
+            output_bytes: 0,
+            missing_chars: Vec::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn run_parallel_isolates_panicking_job() {
+        let jobs: Vec<BatchJob> = ["a", "b", "bad", "c", "d"].into_iter().map(job).collect();
+        // 单线程：出错任务前后的任务都在同一线程中执行
+        let results = run_parallel(1, &jobs, |job| match job.input.as_str() {
+            "bad" => panic!("malformed input"),
+            "c" => Err("plain error".into()),
+            _ => Ok(report(job.input.len())),
+        });
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap().pages, 1);
+        assert_eq!(results[1].as_ref().unwrap().pages, 1);
+        assert_eq!(
+            results[2].as_ref().unwrap_err().to_string(),
+            "处理时发生内部错误（panic）: malformed input"
+        );
+        assert_eq!(results[3].as_ref().unwrap_err().to_string(), "plain error");
+        assert_eq!(results[4].as_ref().unwrap().pages, 1);
+    }
+
+    #[test]
+    fn run_parallel_keeps_job_order() {
+        let jobs: Vec<BatchJob> = (1..=40).map(|i| job(&"x".repeat(i))).collect();
+        let results = run_parallel(4, &jobs, |job| Ok(report(job.input.len())));
+        let pages: Vec<usize> = results.into_iter().map(|r| r.unwrap().pages).collect();
+        assert_eq!(pages, (1..=40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn panic_message_formats_payloads() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new(String::from("owned"));
+        assert_eq!(panic_message(payload.as_ref()), "owned");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(42_u8);
+        assert_eq!(panic_message(payload.as_ref()), "未知错误");
+    }
+
+    // ------------------------------------------------------------------
+    // 网格裁剪（分离轴检测）
+    // ------------------------------------------------------------------
+
+    const PAGE_W: f32 = 595.0;
+    const PAGE_H: f32 = 842.0;
+
+    fn line_text() -> TextBounds {
+        TextBounds {
+            advance: 200.0,
+            x_min: 0.0,
+            y_min: -5.0,
+            x_max: 200.0,
+            y_max: 20.0,
+            vertical: false,
+        }
+    }
+
+    /// 从网格指令中取出每个单元格的 (x, y, 资源名)
+    fn grid_cells(ops: &[Operation]) -> Vec<(f32, f32, String)> {
+        let mut cells = Vec::new();
+        let mut origin = None;
+        for op in ops {
+            match op.operator.as_str() {
+                "cm" => {
+                    origin = Some((
+                        op.operands[4].as_float().unwrap(),
+                        op.operands[5].as_float().unwrap(),
+                    ));
+                }
+                "Do" => {
+                    let (x, y) = origin.take().expect("Do without cm");
+                    let name = String::from_utf8(op.operands[0].as_name().unwrap().to_vec()).unwrap();
+                    cells.push((x, y, name));
+                }
+                _ => {}
+            }
+        }
+        cells
+    }
+
+    /// 原点在 (x, y) 的旋转文本包围盒与页面之间的分离距离（正数为分离，负数为相交）
+    fn separation(x: f32, y: f32, c: f32, s: f32, text: &TextBounds) -> f32 {
+        let corners = [
+            (text.x_min, text.y_min),
+            (text.x_max, text.y_min),
+            (text.x_min, text.y_max),
+            (text.x_max, text.y_max),
+        ]
+        .map(|(lx, ly)| (x + lx * c - ly * s, y + lx * s + ly * c));
+        let page = [(0.0, 0.0), (PAGE_W, 0.0), (0.0, PAGE_H), (PAGE_W, PAGE_H)];
+        let project = |points: &[(f32, f32)], (ax, ay): (f32, f32)| {
+            points.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), (px, py)| {
+                let d = px * ax + py * ay;
+                (lo.min(d), hi.max(d))
+            })
+        };
+        [(1.0, 0.0), (0.0, 1.0), (c, s), (-s, c)]
+            .into_iter()
+            .map(|axis| {
+                let (a_lo, a_hi) = project(&page, axis);
+                let (b_lo, b_hi) = project(&corners, axis);
+                (b_lo - a_hi).max(a_lo - b_hi)
+            })
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    #[test]
+    fn grid_culling_matches_brute_force() {
+        let text = line_text();
+        let names = ["A".to_string()];
+        let cells = CellNames { names: &names, by_rows: false };
+        let (step_inner, step_outer) = grid_steps(DEFAULT_FONT_SIZE, &text);
+
+        for rotation in [0.0_f32, 90.0] {
+            for angle in [0.0_f32, 30.0, 60.0, 90.0, 135.0, -45.0] {
+                let ops = build_watermark_grid_ops_xobject_optimized(
+                    &cells, DEFAULT_FONT_SIZE, angle, PAGE_W, PAGE_H, &text, rotation,
+                )
+                .unwrap();
+                let emitted = grid_cells(&ops);
+                let rad = (angle + rotation).to_radians();
+                let (c, s) = (rad.cos(), rad.sin());
+                let (cx, cy) = (PAGE_W / 2.0, PAGE_H / 2.0);
+
+                // 逐个检查足够大范围内的所有单元格
+                let mut expected = 0;
+                for vi in -40..=40 {
+                    for ui in -40..=40 {
+                        let (u, v) = (ui as f32 * step_inner, vi as f32 * step_outer);
+                        let (x, y) = (cx + u * c - v * s, cy + u * s + v * c);
+                        let gap = separation(x, y, c, s, &text);
+                        let found = emitted
+                            .iter()
+                            .any(|&(ex, ey, _)| (ex - x).abs() < 1e-2 && (ey - y).abs() < 1e-2);
+                        if gap < -1e-2 {
+                            assert!(found, "angle {} rotation {}: cell ({}, {}) dropped", angle, rotation, ui, vi);
+                            expected += 1;
+                        } else if gap > 1e-2 {
+                            assert!(!found, "angle {} rotation {}: cell ({}, {}) kept", angle, rotation, ui, vi);
+                        }
+                    }
+                }
+                assert!(expected > 0);
+                for &(x, y, _) in &emitted {
+                    assert!(separation(x, y, c, s, &text) <= 1e-2, "emitted cell at ({}, {}) is off page", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_axis_aligned_cell_count() {
+        // 0°：u 方向步长 230，v 方向步长 156，包围盒 x ∈ [0, 200]、y ∈ [-5, 20]
+        // 原点 x = 297.5 + 230i，需 x + 200 ≥ 0 且 x ≤ 595 → i ∈ [-2, 1]，共 4 列
+        // 原点 y = 421 + 156j，需 y + 20 ≥ 0 且 y - 5 ≤ 842 → j ∈ [-2, 2]，共 5 行
+        let text = line_text();
+        let names = ["A".to_string()];
+        let cells = CellNames { names: &names, by_rows: false };
+        let ops = build_watermark_grid_ops_xobject_optimized(
+            &cells, DEFAULT_FONT_SIZE, 0.0, PAGE_W, PAGE_H, &text, 0.0,
+        )
+        .unwrap();
+        let emitted = grid_cells(&ops);
+        assert_eq!(emitted.len(), 20);
+        assert_eq!(ops.len(), 20 * 4);
+        assert!(emitted.iter().any(|&(x, y, _)| x == 297.5 && y == 421.0));
+    }
+
+    #[test]
+    fn grid_cycles_names_by_row() {
+        let text = line_text();
+        let names = ["A".to_string(), "B".to_string()];
+        let cells = CellNames { names: &names, by_rows: true };
+        let ops = build_watermark_grid_ops_xobject_optimized(
+            &cells, DEFAULT_FONT_SIZE, 0.0, PAGE_W, PAGE_H, &text, 0.0,
+        )
+        .unwrap();
+        for (_, y, name) in grid_cells(&ops) {
+            let row = ((y - PAGE_H / 2.0) / (DEFAULT_FONT_SIZE * GRID_VERTICAL_MULTIPLIER)).round() as i32;
+            assert_eq!(name, if row.rem_euclid(2) == 0 { "A" } else { "B" }, "row {}", row);
+        }
+    }
+
+    #[test]
+    fn grid_rejects_tiny_steps() {
+        let names = ["A".to_string()];
+        let cells = CellNames { names: &names, by_rows: false };
+        let err = build_watermark_grid_ops_xobject_optimized(
+            &cells, 0.01, 60.0, PAGE_W, PAGE_H, &line_text(), 0.0,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Grid step too small: inner=230, outer=0.06");
+    }
+
+    // ------------------------------------------------------------------
+    // FFI 字体参数
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn font_spec_list_splits_on_newlines_only() {
+        assert_eq!(font_spec_list("C:\\Fonts\\msyh.ttc#1"), ["C:\\Fonts\\msyh.ttc#1"]);
+        assert_eq!(font_spec_list("/fonts/a:b.otf"), ["/fonts/a:b.otf"]);
+        assert_eq!(
+            font_spec_list("main.otf\r\nNoto Sans CJK SC;Bold\n\nemoji.ttf\n"),
+            ["main.otf", "Noto Sans CJK SC;Bold", "emoji.ttf"]
+        );
+        assert!(font_spec_list("").is_empty());
+    }
+
+    // ------------------------------------------------------------------
+    // 模板与时间占位符
+    // ------------------------------------------------------------------
+
+    fn offset_seconds(offset: &str) -> i32 {
+        parse_utc_offset(offset).unwrap().local_minus_utc()
+    }
+
+    #[test]
+    fn parses_utc_offsets() {
+        assert_eq!(offset_seconds("Z"), 0);
+        assert_eq!(offset_seconds(" utc "), 0);
+        assert_eq!(offset_seconds("+08:00"), 8 * 3600);
+        assert_eq!(offset_seconds("+0800"), 8 * 3600);
+        assert_eq!(offset_seconds("+8"), 8 * 3600);
+        assert_eq!(offset_seconds("-05:30"), -(5 * 3600 + 30 * 60));
+        assert_eq!(offset_seconds("-0530"), -(5 * 3600 + 30 * 60));
+        assert_eq!(offset_seconds("+23:59"), 23 * 3600 + 59 * 60);
+        assert_eq!(offset_seconds("-00:00"), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_utc_offsets() {
+        for offset in ["08:00", "+24:00", "+08:60", "+", "+123", "+-5", "+8h", "", "GMT+8"] {
+            assert_eq!(
+                parse_utc_offset(offset).unwrap_err().to_string(),
+                format!("无效的 UTC 偏移量 '{}'，应为 +08:00、-0530、Z 等形式", offset),
+                "{:?}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_known_placeholders() {
+        let vars = TemplateVars::from([
+            ("name".to_string(), "张三".to_string()),
+            ("date".to_string(), "{now:%Y-%m-%d}".to_string()),
+        ]);
+        assert_eq!(render_template(DEFAULT_TEMPLATE, &vars), "致张三-{now:%Y-%m-%d}:高度保密");
+        // 未提供的变量原样保留，替换值不会被再次展开
+        assert_eq!(render_template("{name}/{dept}/{name}", &vars), "张三/{dept}/张三");
+        assert_eq!(render_template("", &vars), "");
+    }
+
+    #[test]
+    fn replace_placeholders_edge_cases() {
+        let resolve = |key: &str| match key {
+            "a" => Some("1".to_string()),
+            "now:%H:%M" => Some("09:30".to_string()),
+            "" => Some("empty".to_string()),
+            _ => None,
+        };
+        assert_eq!(replace_placeholders("{a}{a}", resolve), "11");
+        // 花括号内的全部内容（含冒号后的格式）作为键
+        assert_eq!(replace_placeholders("at {now:%H:%M}", resolve), "at 09:30");
+        // 未闭合的花括号与其后的文本原样保留
+        assert_eq!(replace_placeholders("x {a} {a", resolve), "x 1 {a");
+        // 第一个 } 结束占位符
+        assert_eq!(replace_placeholders("{{a}}", resolve), "{{a}}");
+        assert_eq!(replace_placeholders("{}", resolve), "empty");
+        assert_eq!(replace_placeholders("}{a}}", resolve), "}1}");
+        assert_eq!(replace_placeholders("致{a}：机密", resolve), "致1：机密");
+    }
+
+    #[test]
+    fn formats_datetimes() {
+        let offset = parse_utc_offset("+08:00").unwrap();
+        let time = Utc.with_ymd_and_hms(2026, 2, 5, 16, 30, 0).unwrap().with_timezone(&offset);
+        assert_eq!(format_datetime(&time, DEFAULT_DATETIME_FORMAT).as_deref(), Some("2026-02-06 00:30"));
+        assert_eq!(format_datetime(&time, "%Y年%m月%d日").as_deref(), Some("2026年02月06日"));
+        assert_eq!(format_datetime(&time, "%Q"), None);
+    }
+
+    #[test]
+    fn detects_page_placeholders() {
+        assert!(has_page_placeholders("第 {page} 页"));
+        assert!(has_page_placeholders("共 {pages} 页"));
+        assert!(!has_page_placeholders("{pagex} {name}"));
+    }
+
+    // ------------------------------------------------------------------
+    // 坐标精度与大小报告
+    // ------------------------------------------------------------------
+
+    /// 测试用临时目录（每个测试独立）
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("water_mark_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 生成 A4 空白页组成的PDF
+    fn blank_pdf(path: &Path, pages: usize) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                let content_id = doc.add_object(Stream::new(dictionary! {}, Vec::new()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => pages as i64 }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    fn test_marker(chars: &str) -> Watermarker {
+        Watermarker::from_bytes(TestFont::new(chars).build()).unwrap()
+    }
+
+    fn reals(op: &Operation) -> Vec<f32> {
+        op.operands
+            .iter()
+            .map(|o| match o {
+                Object::Real(v) => *v,
+                other => panic!("{:?} is not a real", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rounds_operands_to_precision() {
+        let mut ops = vec![
+            Operation::new("m", vec![Object::Real(1.23456), Object::Real(-0.0004)]),
+            Operation::new("l", vec![Object::Real(-7.25), Object::Real(99.999)]),
+            Operation::new("re", vec![Object::Integer(3), Object::Real(0.5)]),
+        ];
+        round_operands(&mut ops, 2);
+        assert_eq!(reals(&ops[0]), [1.23, 0.0]);
+        assert_eq!(reals(&ops[1]), [-7.25, 100.0]);
+        assert_eq!(ops[2].operands, [Object::Integer(3), Object::Real(0.5)]);
+
+        round_operands(&mut ops, 0);
+        assert_eq!(reals(&ops[0]), [1.0, 0.0]);
+        assert_eq!(reals(&ops[1]), [-7.0, 100.0]);
+    }
+
+    #[test]
+    fn rounding_leaves_no_negative_zero() {
+        let mut ops = vec![Operation::new(
+            "cm",
+            vec![-0.00001f32, -0.4, 0.0, -0.0, -0.001, -0.04].into_iter().map(Object::Real).collect(),
+        )];
+        round_operands(&mut ops, 1);
+        let values = reals(&ops[0]);
+        assert_eq!(values, [0.0, -0.4, 0.0, 0.0, 0.0, 0.0]);
+        assert!(values.iter().filter(|v| **v == 0.0).all(|v| v.is_sign_positive()), "{:?}", values);
+
+        let encoded = String::from_utf8(Content { operations: ops }.encode().unwrap()).unwrap();
+        assert!(!encoded.contains("-0 ") && !encoded.contains("-0\n"), "{}", encoded);
+    }
+
+    #[test]
+    fn matrix_keeps_minimum_precision() {
+        // 30° 旋转 + 平移
+        let (s, c) = 30f32.to_radians().sin_cos();
+        let cm = || Operation::new("cm", [c, s, -s, c, 123.456_79, -45.678_9].into_iter().map(Object::Real).collect());
+
+        let mut ops = vec![cm()];
+        round_operands(&mut ops, 1);
+        assert_eq!(reals(&ops[0]), [0.866, 0.5, -0.5, 0.866, 123.5, -45.7]);
+        assert_eq!(MATRIX_MIN_PRECISION, 4);
+
+        // 精度高于最小值时按精度舍入
+        let mut ops = vec![cm()];
+        round_operands(&mut ops, 6);
+        assert_eq!(reals(&ops[0]), [0.866025, 0.5, -0.5, 0.866025, 123.456_79, -45.678_9]);
+
+        // 其他操作符的前四个操作数没有最小精度
+        let mut ops = vec![Operation::new("c", [c, s, -s, c].into_iter().map(Object::Real).collect())];
+        round_operands(&mut ops, 1);
+        assert_eq!(reals(&ops[0]), [0.9, 0.5, -0.5, 0.9]);
+    }
+
+    #[test]
+    fn encode_stream_applies_precision() {
+        let (s, c) = 60f32.to_radians().sin_cos();
+        let ops = || {
+            vec![
+                Operation::new("cm", [c, s, -s, c, 10.123_45, 0.000_04].into_iter().map(Object::Real).collect()),
+                Operation::new("m", vec![Object::Real(1.987_65), Object::Real(-0.02)]),
+            ]
+        };
+        // 解码后整数值的操作数为 Integer
+        let decode = |stream: Stream| -> Vec<Vec<f32>> {
+            Content::decode(&stream.content)
+                .unwrap()
+                .operations
+                .iter()
+                .map(|op| op.operands.iter().map(|o| o.as_float().unwrap()).collect())
+                .collect()
+        };
+
+        let marker = test_marker("A").with_compression(false);
+        let full = decode(marker.encode_stream(dictionary! {}, ops()).unwrap());
+        assert_eq!(full[1], [1.987_65, -0.02]);
+
+        let marker = marker.with_precision(Some(1));
+        let rounded = decode(marker.encode_stream(dictionary! {}, ops()).unwrap());
+        assert_eq!(rounded[0], [0.5, 0.866, -0.866, 0.5, 10.1, 0.0]);
+        assert_eq!(rounded[1], [2.0, 0.0]);
+    }
+
+    #[test]
+    fn report_sizes_match_files() {
+        let dir = temp_dir("report");
+        let input = dir.join("in.pdf");
+        blank_pdf(&input, 3);
+        let output = dir.join("out.pdf");
+        let marker = test_marker("AB").with_workers(1);
+
+        let report = marker.apply_text(&input.to_string_lossy(), &output.to_string_lossy(), "AB").unwrap();
+        let input_len = std::fs::metadata(&input).unwrap().len();
+        let output_len = std::fs::metadata(&output).unwrap().len();
+        assert_eq!((report.pages, report.input_bytes, report.output_bytes), (3, input_len, output_len));
+        assert_eq!(report.size_delta(), output_len as i64 - input_len as i64);
+        assert!(report.size_delta() > 0);
+        assert!(report.missing_chars.is_empty());
+
+        // 缺字报告
+        let report = marker.apply_text(&input.to_string_lossy(), &output.to_string_lossy(), "ABC✓").unwrap();
+        assert_eq!(report.missing_chars, ['C', '✓']);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn size_delta_can_be_negative() {
+        let shrunk = WatermarkReport { input_bytes: 5000, output_bytes: 4200, ..report(1) };
+        assert_eq!(shrunk.size_delta(), -800);
+        let grown = WatermarkReport { input_bytes: 5000, output_bytes: 5300, ..report(1) };
+        assert_eq!(grown.size_delta(), 300);
+        assert_eq!(report(0).size_delta(), 0);
+    }
+
+    // ------------------------------------------------------------------
+    // 字体规格（路径#序号 / 路径#名称）
+    // ------------------------------------------------------------------
+
+    /// 写入两个字面的字体集合：0 为 Regular（含 `A`），1 为 Bold（含 `B`）
+    fn write_collection(dir: &Path) -> String {
+        let data = test_font::collection(&[
+            TestFont::new("A").named("Test Serif", "Regular"),
+            TestFont::new("B").named("Test Serif", "Bold"),
+        ]);
+        let path = dir.join("serif.ttc");
+        std::fs::write(&path, data).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn loaded_index(spec: &str) -> u32 {
+        let loaded = load_font_spec(spec).unwrap();
+        // 字面索引与实际加载的字面一致
+        let expected = if loaded.index == 0 { 'A' } else { 'B' };
+        assert_ne!(loaded.font.glyph_id(expected).0, 0, "{}", spec);
+        loaded.index
+    }
+
+    fn spec_error(spec: &str) -> String {
+        load_font_spec(spec).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn font_spec_selects_face_by_index() {
+        let dir = temp_dir("font_spec_index");
+        let ttc = write_collection(&dir);
+        assert_eq!(loaded_index(&ttc), 0);
+        assert_eq!(loaded_index(&format!("{}#0", ttc)), 0);
+        assert_eq!(loaded_index(&format!("{}#1", ttc)), 1);
+        assert_eq!(loaded_index(&format!("{}# 1 ", ttc)), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn font_spec_selects_face_by_name() {
+        let dir = temp_dir("font_spec_name");
+        let ttc = write_collection(&dir);
+        assert_eq!(loaded_index(&format!("{}#Test Serif Bold", ttc)), 1);
+        assert_eq!(loaded_index(&format!("{}#TestSerif-Bold", ttc)), 1);
+        assert_eq!(loaded_index(&format!("{}#test serif regular", ttc)), 0);
+        // 只给族名时选择最接近常规的字面
+        assert_eq!(loaded_index(&format!("{}#Test Serif", ttc)), 0);
+
+        // 文件名本身含有 # 时按文件加载
+        let hashed = dir.join("serif#1.ttc");
+        std::fs::copy(&ttc, &hashed).unwrap();
+        assert_eq!(loaded_index(&hashed.to_string_lossy()), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn font_spec_reports_out_of_range_faces() {
+        let dir = temp_dir("font_spec_range");
+        let ttc = write_collection(&dir);
+        assert_eq!(
+            spec_error(&format!("{}#2", ttc)),
+            format!("{}: face index 2 out of range (font has 2 face(s))", ttc)
+        );
+        let ttf = dir.join("single.ttf");
+        std::fs::write(&ttf, TestFont::new("A").build()).unwrap();
+        let ttf = ttf.to_string_lossy();
+        assert_eq!(
+            spec_error(&format!("{}#1", ttf)),
+            format!("{}: face index 1 out of range (font has 1 face(s))", ttf)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn font_spec_lists_available_faces() {
+        let dir = temp_dir("font_spec_faces");
+        let ttc = write_collection(&dir);
+        assert_eq!(
+            spec_error(&format!("{}#Test Serif Italic", ttc)),
+            format!(
+                "no face named \"Test Serif Italic\" in {} (available: 0: Test Serif Regular, 1: Test Serif Bold)",
+                ttc
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn font_from_data_errors() {
+        let data = TestFont::new("A").build();
+        assert_eq!(
+            font_from_data(data.clone(), 3).err().unwrap(),
+            "face index 3 out of range (font has 1 face(s))"
+        );
+        assert_eq!(font_from_data(data, 0).unwrap().index, 0);
+        let err = font_from_data(b"not a font".to_vec(), 0).err().unwrap();
+        assert!(err.starts_with("parse font face 0 failed: "), "{}", err);
+    }
+
+    // ------------------------------------------------------------------
+    // 页码占位符与多文本字形复用
+    // ------------------------------------------------------------------
+
+    fn vars(pairs: &[(&str, &str)]) -> TemplateVars {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn renders_page_placeholders_per_page() {
+        let marker = test_marker("A").with_template("致{name} 第{page}/{pages}页");
+        let named = vars(&[("name", "张三")]);
+        let text_for_page = marker.page_text_renderer(&named, "missing.pdf");
+        assert_eq!(text_for_page(1, 3), "致张三 第1/3页");
+        assert_eq!(text_for_page(3, 3), "致张三 第3/3页");
+
+        // 同名变量优先于页码
+        let overridden = vars(&[("name", "张三"), ("page", "附件")]);
+        let text_for_page = marker.page_text_renderer(&overridden, "missing.pdf");
+        assert_eq!(text_for_page(2, 3), "致张三 第附件/3页");
+
+        let plain = test_marker("A").with_template("致{name}");
+        let text_for_page = plain.page_text_renderer(&named, "missing.pdf");
+        assert_eq!(text_for_page(1, 2), "致张三");
+        assert_eq!(text_for_page(2, 2), "致张三");
+    }
+
+    /// 输出文档中的 Form XObject：(对象ID, 引用的 XObject 资源 名称 -> 对象ID)
+    fn form_xobjects(doc: &Document) -> Vec<(ObjectId, Vec<(String, ObjectId)>)> {
+        doc.objects
+            .iter()
+            .filter_map(|(id, object)| {
+                let Object::Stream(stream) = object else {
+                    return None;
+                };
+                if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Form".as_slice()) {
+                    return None;
+                }
+                let refs = stream
+                    .dict
+                    .get(b"Resources")
+                    .and_then(Object::as_dict)
+                    .and_then(|resources| resources.get(b"XObject"))
+                    .and_then(Object::as_dict)
+                    .map(|xobjects| {
+                        xobjects
+                            .iter()
+                            .map(|(name, r)| (String::from_utf8_lossy(name).into_owned(), r.as_reference().unwrap()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Some((*id, refs))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn distinct_texts_embed_each_shared_glyph_once() {
+        let dir = temp_dir("glyph_reuse");
+        let input = dir.join("in.pdf");
+        let output = dir.join("out.pdf");
+        blank_pdf(&input, 3);
+        let marker = test_marker("AB 123").with_template("AB {page}").with_workers(1);
+        let report = marker.apply(&input.to_string_lossy(), &output.to_string_lossy(), &TemplateVars::new()).unwrap();
+        assert_eq!(report.pages, 3);
+
+        let doc = Document::load(&output).unwrap();
+        let forms = form_xobjects(&doc);
+        // 三个文本 "AB 1"、"AB 2"、"AB 3"，各自引用四个字形对象（测试字体中空格也有轮廓）
+        let texts: Vec<_> = forms.iter().filter(|(_, refs)| !refs.is_empty()).collect();
+        assert_eq!(texts.len(), 3);
+        for (_, refs) in &texts {
+            assert_eq!(refs.len(), 4);
+            assert!(refs.iter().all(|(name, _)| name.starts_with("G0_")), "{:?}", refs);
+        }
+        // A、B 和空格只嵌入一次（共 6 个而非 12 个），三个文本引用同一对象
+        let glyphs: HashSet<ObjectId> = texts.iter().flat_map(|(_, refs)| refs.iter().map(|(_, id)| *id)).collect();
+        assert_eq!(glyphs.len(), 6);
+        assert_eq!(forms.len(), texts.len() + glyphs.len());
+        for name in ["G0_1", "G0_2", "G0_3"] {
+            let ids: HashSet<ObjectId> = texts
+                .iter()
+                .map(|(_, refs)| refs.iter().find(|(n, _)| n == name).unwrap().1)
+                .collect();
+            assert_eq!(ids.len(), 1, "{}", name);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn single_text_is_outlined_without_glyph_objects() {
+        let dir = temp_dir("glyph_single");
+        let input = dir.join("in.pdf");
+        let output = dir.join("out.pdf");
+        blank_pdf(&input, 3);
+        let marker = test_marker("AB").with_template("AB").with_workers(1);
+        marker.apply(&input.to_string_lossy(), &output.to_string_lossy(), &TemplateVars::new()).unwrap();
+
+        let forms = form_xobjects(&Document::load(&output).unwrap());
+        assert_eq!(forms.len(), 1);
+        assert!(forms[0].1.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
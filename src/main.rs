@@ -1,33 +1,1230 @@
+use std::collections::HashSet;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::time::Instant;
-use water_mark::run_watermark_process; // 调用 lib 中的公开函数
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use serde_json::{json, Value};
+use water_mark::{
+    read_manifest, BatchJob, GridMode, PageSelection, Profile, ProfileConfig,
+    TemplateVars, WatermarkColor, WatermarkReport, Watermarker,
+}; // 调用 lib 中的公开接口
+
+/// 未指定 --config 时读取的配置文件（当前目录）
+const DEFAULT_CONFIG: &str = "water_mark.toml";
+
+/// 命令行用法说明
+const USAGE: &str = "\
+用法:
+  water_mark_cli [选项] [输入文件] [输出文件]
+  water_mark_cli [选项] --output-dir <目录> <输入文件>...
+  water_mark_cli batch [选项] <目录或通配符>... --out-dir <目录>
+  water_mark_cli manifest [选项] <清单.csv|清单.jsonl>
+
+输入、输出文件默认分别为 in.pdf、out.pdf。
+
+batch 子命令处理目录中的所有 PDF 或匹配通配符（*、?、**）的文件，
+在输出目录中保留相对路径；已带水印、已带后缀或输出已存在的文件会被跳过。
+
+manifest 子命令按清单逐行生成副本：input、output 两列必填，其余列作为模板变量
+（覆盖 --var 给出的默认值）；相对路径以清单所在目录为基准，同一输入只解析一次。
+
+选项:
+  --profile <名称>        使用配置文件中的水印方案 [profile.<名称>]，命令行选项优先于方案
+  --config <路径>         配置文件（TOML 或 JSON，默认当前目录的 water_mark.toml）
+  --font <路径或字体名>   字体文件或已安装的字体名称；多次指定时后面的作为缺字回退字体
+  --text <文本>           水印文本（可含 {now}、{page} 等时间与页码占位符，不使用 --var）
+  --template <模板>       水印模板，{key} 由 --var 提供（默认 \"致{name}-{date}:高度保密\"）
+  --var <key=value>       模板变量，可多次指定（默认 name=张三、date={now:%Y-%m-%d}）
+  --size <点数>           字体大小（默认 26）
+  --angle <度数>          旋转角度，逆时针（默认 60）
+  --opacity <0-1>         透明度（默认 0.1）
+  --color <颜色>          颜色：#RRGGBB、gray:0.5、rgb:r,g,b、cmyk:c,m,y,k、spot:名称:c,m,y,k
+  --pages <范围>          只处理指定页面，如 1-3,5,10-、odd、even、last（默认全部）
+  --layout <方式>         tile（平铺，默认）、pattern（平铺图案）、center（页面中心单个水印）
+  --output-dir <目录>     多文件模式：输出到目录（文件名不变）
+  --overwrite             覆盖已存在的输出文件（默认拒绝；batch 中默认跳过）
+  -r, --recursive         batch：递归处理子目录
+  --suffix <后缀>         batch：输出文件名追加的后缀，如 _wm（a.pdf -> a_wm.pdf）
+  -j, --jobs <N>          并行线程数（默认为 CPU 核心数）
+  -q, --quiet             只输出错误信息
+  --json                  以 JSON 格式输出处理结果
+  -h, --help              显示本帮助
+
+退出码: 0 全部成功，1 有文件处理失败，2 参数错误";
+
+/// 结果输出方式
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Quiet,
+    Json,
+}
+
+/// 解析后的命令行参数
+struct Options {
+    fonts: Vec<String>,
+    text: Option<String>,
+    template: Option<String>,
+    vars: TemplateVars,
+    size: Option<f32>,
+    angle: Option<f32>,
+    opacity: Option<f32>,
+    color: Option<WatermarkColor>,
+    pages: Option<PageSelection>,
+    layout: Option<GridMode>,
+    /// --profile 选中的水印方案
+    profile: Option<Profile>,
+    output_dir: Option<String>,
+    overwrite: bool,
+    jobs: Option<usize>,
+    format: OutputFormat,
+    recursive: bool,
+    suffix: String,
+    positional: Vec<String>,
+}
+
+/// 参数解析结果
+enum Command {
+    Run(Box<Options>),
+    /// batch 子命令
+    Batch(Box<Options>),
+    /// manifest 子命令
+    Manifest(Box<Options>),
+    Help,
+}
+
+/// 单个文件的处理结果
+struct FileResult {
+    input: String,
+    output: String,
+    outcome: Outcome,
+}
+
+/// 文件处理状态
+enum Outcome {
+    Done(WatermarkReport),
+    Failed(String),
+    /// 未处理，附原因
+    Skipped(String),
+}
+
+impl FileResult {
+    fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Failed(_))
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Command::Batch(options)) => return run_batch(&options),
+        Ok(Command::Manifest(options)) => return run_manifest(&options),
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            eprintln!("使用 --help 查看用法");
+            return ExitCode::from(2);
+        }
+    };
+    run(&options)
+}
+
+/// 解析命令行参数
+///
+/// # 返回
+/// - `Ok(Command)`: 待执行的命令
+/// - `Err`: 参数错误（未知选项、缺少参数值或参数值无效）
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (subcommand, args) = match args.split_first() {
+        Some((first, rest)) if first == "batch" || first == "manifest" => {
+            (Some(first.as_str()), rest)
+        }
+        _ => (None, args),
+    };
+    let batch = subcommand == Some("batch");
+    let mut options = Options {
+        fonts: Vec::new(),
+        text: None,
+        template: None,
+        vars: TemplateVars::new(),
+        size: None,
+        angle: None,
+        opacity: None,
+        color: None,
+        pages: None,
+        layout: None,
+        profile: None,
+        output_dir: None,
+        overwrite: false,
+        jobs: None,
+        format: OutputFormat::Text,
+        recursive: false,
+        suffix: String::new(),
+        positional: Vec::new(),
+    };
+
+    let (mut profile_name, mut config_path) = (None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // 同时支持 "--size 30" 与 "--size=30"
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{} 缺少参数", flag))
+        };
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--profile" => profile_name = Some(value()?),
+            "--config" => config_path = Some(value()?),
+            "--font" => options.fonts.push(value()?),
+            "--text" => options.text = Some(value()?),
+            "--template" => options.template = Some(value()?),
+            "--var" => {
+                let pair = value()?;
+                let (key, val) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--var 应为 key=value 形式: {}", pair))?;
+                if key.is_empty() {
+                    return Err(format!("--var 的变量名为空: {}", pair));
+                }
+                options.vars.insert(key.to_string(), val.to_string());
+            }
+            "--size" => {
+                let size = parse_number(flag, &value()?)?;
+                if size <= 0.0 {
+                    return Err(format!("--size 必须大于 0: {}", size));
+                }
+                options.size = Some(size);
+            }
+            "--angle" => options.angle = Some(parse_number(flag, &value()?)?),
+            "--opacity" => {
+                let opacity = parse_number(flag, &value()?)?;
+                if !(0.0..=1.0).contains(&opacity) {
+                    return Err(format!("--opacity 应在 0 到 1 之间: {}", opacity));
+                }
+                options.opacity = Some(opacity);
+            }
+            "--color" => {
+                let color = value()?;
+                options.color = Some(color.parse().map_err(|e| format!("--color: {}", e))?);
+            }
+            "--pages" => {
+                let pages = value()?;
+                options.pages = Some(pages.parse().map_err(|e| format!("--pages: {}", e))?);
+            }
+            "--layout" => {
+                options.layout = Some(value()?.parse().map_err(|e| format!("--layout: {}", e))?);
+            }
+            "--output-dir" | "--out-dir" => options.output_dir = Some(value()?),
+            "--overwrite" => options.overwrite = true,
+            "-j" | "--jobs" => {
+                let n = value()?;
+                options.jobs = Some(n.parse().map_err(|_| format!("无效的线程数: {}", n))?);
+            }
+            "-r" | "--recursive" if batch => options.recursive = true,
+            "--suffix" if batch => options.suffix = value()?,
+            "-r" | "--recursive" | "--suffix" => {
+                return Err(format!("{} 只能用于 batch 子命令", flag));
+            }
+            "-q" | "--quiet" => options.format = OutputFormat::Quiet,
+            "--json" => options.format = OutputFormat::Json,
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("未知选项: {}", flag));
+            }
+            _ => options.positional.push(arg.clone()),
+        }
+    }
+
+    match (&profile_name, config_path) {
+        (Some(name), config_path) => {
+            let config_path = config_path.as_deref().unwrap_or(DEFAULT_CONFIG);
+            let config = ProfileConfig::load(config_path).map_err(|e| e.to_string())?;
+            let profile = config.profile(name).map_err(|e| format!("--profile: {}", e))?;
+            options.profile = Some(profile.clone());
+        }
+        (None, Some(_)) => return Err("--config 需要与 --profile 一起使用".to_string()),
+        (None, None) => {}
+    }
+    if options.text.is_some() && options.template.is_some() {
+        return Err("--text 与 --template 不能同时使用".to_string());
+    }
+    if options.text.is_some() && !options.vars.is_empty() {
+        return Err("--var 只能与模板一起使用，--text 的文本不做变量替换".to_string());
+    }
+    if subcommand == Some("manifest") {
+        if options.positional.len() != 1 {
+            return Err("manifest 子命令需要且只需要一个清单文件".to_string());
+        }
+        if options.output_dir.is_some() {
+            return Err("manifest 的输出路径由清单给出，不能使用 --output-dir".to_string());
+        }
+        if options.text.is_some() {
+            return Err("manifest 需要使用模板（--template），--text 的文本不做变量替换".to_string());
+        }
+        return Ok(Command::Manifest(Box::new(options)));
+    }
+    if batch {
+        if options.output_dir.is_none() {
+            return Err("batch 子命令需要 --out-dir 指定输出目录".to_string());
+        }
+        if options.positional.is_empty() {
+            return Err("batch 子命令至少需要一个目录或通配符".to_string());
+        }
+        if options.suffix.contains(['/', '\\']) {
+            return Err(format!("--suffix 不能包含路径分隔符: {}", options.suffix));
+        }
+        return Ok(Command::Batch(Box::new(options)));
+    }
+    match &options.output_dir {
+        Some(_) if options.positional.is_empty() => {
+            return Err("--output-dir 模式至少需要一个输入文件".to_string());
+        }
+        None if options.positional.len() > 2 => {
+            return Err("多个输入文件请使用 --output-dir 指定输出目录".to_string());
+        }
+        _ => {}
+    }
+    Ok(Command::Run(Box::new(options)))
+}
+
+/// 解析数值参数
+fn parse_number(flag: &str, value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("{} 需要数值: {}", flag, value))
+}
+
+/// 按参数创建水印器
+fn build_marker(options: &Options) -> Result<Watermarker, Box<dyn std::error::Error>> {
+    // 字体优先取 --font，其次取水印方案，都未指定时使用随项目附带的字体
+    let profile_fonts = options.profile.as_ref().map(|p| &p.fonts).filter(|f| !f.is_empty());
+    let fonts = if !options.fonts.is_empty() {
+        options.fonts.clone()
+    } else if let Some(fonts) = profile_fonts {
+        fonts.clone()
+    } else {
+        let bundled = Path::new("STSongStd-Light-Acro").join("STSongStd-Light-Acro.otf");
+        vec![bundled.to_string_lossy().into_owned()]
+    };
+
+    let mut marker = Watermarker::from_specs(&fonts)?;
+    if let Some(profile) = &options.profile {
+        marker = profile.apply_to(marker)?;
+    }
+    if let Some(layout) = options.layout {
+        marker = marker.with_grid_mode(layout);
+    }
+    if let Some(template) = options.text.as_ref().or(options.template.as_ref()) {
+        marker = marker.with_template(template);
+    }
+    if options.text.is_some() {
+        // --text 的文本不做变量替换，方案中的默认变量也不使用
+        marker = marker.with_vars(TemplateVars::new());
+    }
+    if let Some(size) = options.size {
+        marker = marker.with_font_size(size);
+    }
+    if let Some(angle) = options.angle {
+        marker = marker.with_angle(angle);
+    }
+    if let Some(opacity) = options.opacity {
+        marker = marker.with_opacity(opacity);
+    }
+    if let Some(color) = &options.color {
+        marker = marker.with_color(color.clone());
+    }
+    if let Some(pages) = &options.pages {
+        marker = marker.with_pages(pages.clone());
+    }
+    if let Some(n) = options.jobs {
+        marker = marker.with_workers(n);
+    }
+    Ok(marker)
+}
+
+/// 模板变量：默认值在前，依次被水印方案的 `vars` 与 `--var` 覆盖；`--text` 不使用变量
+fn template_vars(options: &Options) -> TemplateVars {
+    if options.text.is_some() {
+        return TemplateVars::new();
+    }
+    // 日期在处理时按本机时区渲染（时间占位符见 Watermarker::apply）
+    let mut vars = TemplateVars::from([
+        ("name".to_string(), "张三".to_string()),
+        ("date".to_string(), "{now:%Y-%m-%d}".to_string()),
+    ]);
+    if let Some(profile) = &options.profile {
+        vars.extend(profile.vars.clone());
+    }
+    vars.extend(options.vars.clone());
+    vars
+}
+
+/// 执行水印处理并输出结果
+fn run(options: &Options) -> ExitCode {
+    let start_time = Instant::now();
+
+    let marker = match build_marker(options) {
+        Ok(marker) => marker,
+        Err(e) => {
+            eprintln!("错误: 无法加载字体: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let vars = template_vars(options);
+
+    // 1. 确定输入输出
+    let jobs: Vec<BatchJob> = match &options.output_dir {
+        // 多文件模式：所有输入并行处理，输出到同一目录（文件名不变）
+        Some(dir) => {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!("错误: 无法创建输出目录 {}: {}", dir, e);
+                return ExitCode::FAILURE;
+            }
+            options
+                .positional
+                .iter()
+                .map(|input| {
+                    let file_name = Path::new(input).file_name().unwrap_or_default();
+                    BatchJob {
+                        input: input.clone(),
+                        output: Path::new(dir).join(file_name).to_string_lossy().into_owned(),
+                        vars: vars.clone(),
+                    }
+                })
+                .collect()
+        }
+        None => vec![BatchJob {
+            input: options.positional.first().cloned().unwrap_or_else(|| "in.pdf".to_string()),
+            output: options.positional.get(1).cloned().unwrap_or_else(|| "out.pdf".to_string()),
+            vars,
+        }],
+    };
+
+    // 2. 不同目录下的同名输入会输出到同一文件，只处理第一个（避免并行写入同一文件）；
+    //    未指定 --overwrite 时不覆盖已有文件
+    let duplicates = duplicate_outputs(jobs.iter().map(|job| job.output.as_str()));
+    let mut pending = Vec::with_capacity(jobs.len());
+    let mut results = Vec::new();
+    for (job, duplicate) in jobs.into_iter().zip(duplicates) {
+        let refused = if duplicate {
+            format!("与前面的输入输出到同一文件 {}", job.output)
+        } else if !options.overwrite && Path::new(&job.output).exists() {
+            format!("输出文件 {} 已存在（使用 --overwrite 覆盖）", job.output)
+        } else {
+            pending.push(job);
+            continue;
+        };
+        results.push(FileResult {
+            outcome: Outcome::Failed(refused),
+            input: job.input,
+            output: job.output,
+        });
+    }
+
+    // 3. 调用库中的核心逻辑
+    if options.format == OutputFormat::Text {
+        if pending.len() > 1 {
+            println!("正在并行处理 {} 个 PDF（{} 线程）", pending.len(), marker.workers());
+        } else if let Some(job) = pending.first() {
+            println!("正在处理 PDF: {}", job.input);
+        }
+    }
+    results.extend(process(&marker, pending));
+
+    // 4. 输出结果
+    let failed = results.iter().filter(|r| r.failed()).count();
+    match options.format {
+        OutputFormat::Json => println!("{}", results_json(&results, start_time)),
+        OutputFormat::Quiet => print_errors(&results),
+        OutputFormat::Text => {
+            for file in &results {
+                if let Outcome::Done(report) = &file.outcome {
+                    println!(
+                        "完成: {} -> {}（{} 页，{} -> {} 字节）",
+                        file.input, file.output, report.pages, report.input_bytes, report.output_bytes
+                    );
+                }
+            }
+            print_errors(&results);
+            if results.len() > 1 {
+                println!("成功 {} 个，失败 {} 个", results.len() - failed, failed);
+            }
+            println!("总耗时: {:.2?}", start_time.elapsed());
+        }
+    }
+
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// 标记与前面的路径重复的输出路径（第一次出现的照常处理），结果与 `outputs` 一一对应
+///
+/// # 说明
+/// 路径按组成部分比较（忽略 `.` 与多余的分隔符）。重复的任务若交给并行处理，
+/// 两个线程会同时写入同一文件，前一份输出被覆盖甚至损坏。
+fn duplicate_outputs<'a>(outputs: impl IntoIterator<Item = &'a str>) -> Vec<bool> {
+    let mut seen = HashSet::new();
+    outputs
+        .into_iter()
+        .map(|output| !seen.insert(Path::new(output).components().collect::<PathBuf>()))
+        .collect()
+}
+
+/// batch 子命令找到的输入文件
+struct SourceFile {
+    path: PathBuf,
+    /// 相对目录或通配符起始目录的路径，在输出目录中按此还原目录结构
+    relative: PathBuf,
+}
+
+/// 执行 batch 子命令：为目录或通配符匹配到的 PDF 添加水印，输出时保留相对路径
+///
+/// # 说明
+/// 以下文件不处理并记为跳过：文件名已带 `--suffix` 后缀、输出文件已存在（未指定 `--overwrite`）、
+/// 文档中已有本工具添加的水印。
+fn run_batch(options: &Options) -> ExitCode {
+    let start_time = Instant::now();
+    let out_dir = PathBuf::from(options.output_dir.clone().unwrap_or_default());
+
+    // 1. 收集输入文件（输出目录位于输入目录内时不会被重复收集）
+    let mut sources: Vec<SourceFile> = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for pattern in &options.positional {
+        let found = match collect_sources(pattern, options.recursive, &out_dir) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                return ExitCode::from(2);
+            }
+        };
+        if found.is_empty() {
+            eprintln!("WARN: {} 中没有找到 PDF 文件", pattern);
+        }
+        sources.extend(found.into_iter().filter(|source| seen.insert(source.path.clone())));
+    }
+
+    let marker = match build_marker(options) {
+        Ok(marker) => marker,
+        Err(e) => {
+            eprintln!("错误: 无法加载字体: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let vars = template_vars(options);
+
+    let jobs: Vec<BatchJob> = sources
+        .iter()
+        .map(|source| BatchJob {
+            input: source.path.to_string_lossy().into_owned(),
+            output: batch_output_path(&out_dir, &source.relative, &options.suffix)
+                .to_string_lossy()
+                .into_owned(),
+            vars: vars.clone(),
+        })
+        .collect();
+
+    // 2. 跳过无需处理的文件：已带后缀的文件、输出已存在的文件；
+    //    多个输入对应同一输出（如 `batch dir1 dir2` 中都有 report.pdf）时只处理第一个。
+    //    文档中是否已有水印在处理时检查（与加水印共用同一次解析）
+    let mut outcomes: Vec<Option<Outcome>> = sources
+        .iter()
+        .map(|source| {
+            let stem = source.path.file_stem().unwrap_or_default().to_string_lossy();
+            (!options.suffix.is_empty() && stem.ends_with(&options.suffix))
+                .then(|| Outcome::Skipped(format!("文件名已带后缀 {}", options.suffix)))
+        })
+        .collect();
+    let candidates: Vec<usize> = (0..jobs.len()).filter(|&i| outcomes[i].is_none()).collect();
+    let duplicates = duplicate_outputs(candidates.iter().map(|&i| jobs[i].output.as_str()));
+    for (&i, duplicate) in candidates.iter().zip(duplicates) {
+        outcomes[i] = if duplicate {
+            Some(Outcome::Failed(format!("与前面的输入输出到同一文件 {}", jobs[i].output)))
+        } else if !options.overwrite && Path::new(&jobs[i].output).exists() {
+            Some(Outcome::Skipped("输出文件已存在".to_string()))
+        } else {
+            None
+        };
+    }
+
+    // 3. 创建输出子目录并处理剩余文件
+    let mut pending: Vec<usize> = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        if outcomes[i].is_some() {
+            continue;
+        }
+        let parent = Path::new(&job.output).parent().unwrap_or(Path::new(""));
+        match std::fs::create_dir_all(parent) {
+            Ok(()) => pending.push(i),
+            Err(e) => {
+                outcomes[i] = Some(Outcome::Failed(format!(
+                    "无法创建输出目录 {}: {}",
+                    parent.display(),
+                    e
+                )));
+            }
+        }
+    }
+    if options.format == OutputFormat::Text && !pending.is_empty() {
+        println!(
+            "找到 {} 个 PDF，正在处理 {} 个（{} 线程）",
+            jobs.len(),
+            pending.len(),
+            marker.workers()
+        );
+    }
+    let reports = marker.batch_parallel_unmarked(pending.iter().map(|&i| jobs[i].clone()));
+    for (&i, result) in pending.iter().zip(reports) {
+        outcomes[i] = Some(match result {
+            Ok(Some(report)) => Outcome::Done(report),
+            Ok(None) => Outcome::Skipped("已有水印".to_string()),
+            Err(e) => Outcome::Failed(e.to_string()),
+        });
+    }
+
+    let results: Vec<FileResult> = jobs
+        .into_iter()
+        .zip(outcomes)
+        .map(|(job, outcome)| FileResult {
+            input: job.input,
+            output: job.output,
+            outcome: outcome.unwrap_or_else(|| Outcome::Failed("未处理".to_string())),
+        })
+        .collect();
+
+    // 4. 输出汇总
+    match options.format {
+        OutputFormat::Json => println!("{}", results_json(&results, start_time)),
+        OutputFormat::Quiet => print_errors(&results),
+        OutputFormat::Text => {
+            print_summary_table(&results, start_time, false);
+            print_errors(&results);
+        }
+    }
+
+    if results.iter().any(FileResult::failed) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// 执行 manifest 子命令：按清单每行生成一份水印副本
+///
+/// # 说明
+/// - 行内变量覆盖 `--var` 与默认变量
+/// - 相同输入只解析一次（见 [`Watermarker::distribute`]）
+/// - 输出已存在（未指定 `--overwrite`）时跳过，多行输出到同一路径时后面的行记为失败
+fn run_manifest(options: &Options) -> ExitCode {
     let start_time = Instant::now();
+    let manifest = &options.positional[0];
+    let mut jobs = match read_manifest(manifest) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let marker = match build_marker(options) {
+        Ok(marker) => marker,
+        Err(e) => {
+            eprintln!("错误: 无法加载字体: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let defaults = template_vars(options);
+    for job in &mut jobs {
+        let mut vars = defaults.clone();
+        vars.extend(std::mem::take(&mut job.vars));
+        job.vars = vars;
+    }
+
+    // 检查输出路径并创建输出目录
+    let mut outcomes: Vec<Option<Outcome>> = Vec::with_capacity(jobs.len());
+    let duplicates = duplicate_outputs(jobs.iter().map(|job| job.output.as_str()));
+    for (job, duplicate) in jobs.iter().zip(duplicates) {
+        let parent = Path::new(&job.output).parent().unwrap_or(Path::new(""));
+        let outcome = if duplicate {
+            Some(Outcome::Failed("与清单中前面的行输出到同一文件".to_string()))
+        } else if !options.overwrite && Path::new(&job.output).exists() {
+            Some(Outcome::Skipped("输出文件已存在".to_string()))
+        } else if let Err(e) = std::fs::create_dir_all(parent) {
+            Some(Outcome::Failed(format!("无法创建输出目录 {}: {}", parent.display(), e)))
+        } else {
+            None
+        };
+        outcomes.push(outcome);
+    }
+
+    let pending: Vec<usize> = (0..jobs.len()).filter(|&i| outcomes[i].is_none()).collect();
+    if options.format == OutputFormat::Text {
+        println!(
+            "清单共 {} 行，正在生成 {} 份（{} 线程）",
+            jobs.len(),
+            pending.len(),
+            marker.workers()
+        );
+    }
+    let reports = marker.distribute(pending.iter().map(|&i| jobs[i].clone()));
+    for (&i, result) in pending.iter().zip(reports) {
+        outcomes[i] = Some(match result {
+            Ok(report) => Outcome::Done(report),
+            Err(e) => Outcome::Failed(e.to_string()),
+        });
+    }
+
+    let results: Vec<FileResult> = jobs
+        .into_iter()
+        .zip(outcomes)
+        .map(|(job, outcome)| FileResult {
+            input: job.input,
+            output: job.output,
+            outcome: outcome.unwrap_or_else(|| Outcome::Failed("未处理".to_string())),
+        })
+        .collect();
 
-    // 1. 环境准备
-    let args: Vec<String> = env::args().collect();
-    let input_path = args.get(1).map(|s| s.as_str()).unwrap_or("in.pdf");
-    let output_path = args.get(2).map(|s| s.as_str()).unwrap_or("out.pdf");
-    let font_path = ".\\STSongStd-Light-Acro\\STSongStd-Light-Acro.otf";
-    
-    let name = "张三";
-    let date = "2026-02-05";
-    let text = format!("致{}-{}:高度保密", name, date);
-
-    println!("正在处理 PDF: {}", input_path);
-    
-    // 2. 调用库中的核心逻辑
-    match run_watermark_process(input_path, output_path, font_path, &text) {
-        Ok(_) => {
-            let duration = start_time.elapsed();
-            println!("Rust 矢量水印生成成功！保存为 {}", output_path);
-            println!("总耗时: {:.2?}", duration);
-        },
+    match options.format {
+        OutputFormat::Json => println!("{}", results_json(&results, start_time)),
+        OutputFormat::Quiet => print_errors(&results),
+        OutputFormat::Text => {
+            print_summary_table(&results, start_time, true);
+            print_errors(&results);
+        }
+    }
+
+    if results.iter().any(FileResult::failed) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// 收集目录、通配符或单个文件对应的 PDF 文件（按路径排序）
+///
+/// # 说明
+/// - 目录：其中扩展名为 `.pdf`（不区分大小写）的文件，`recursive` 时包含子目录
+/// - 通配符：`*`、`?` 匹配单层文件名，`**` 匹配任意层目录（结尾的 `**` 等同 `**/*.pdf`）
+/// - 以 `.` 开头的隐藏文件与目录不会被收集（通配符中显式写出的除外）
+/// - `out_dir` 及其内容不会被收集
+fn collect_sources(
+    pattern: &str,
+    recursive: bool,
+    out_dir: &Path,
+) -> Result<Vec<SourceFile>, String> {
+    let path = Path::new(pattern);
+    let excluded = out_dir.canonicalize().ok();
+    let mut found = Vec::new();
+    if path.is_dir() {
+        walk_dir(path, path, recursive, excluded.as_deref(), &mut found);
+    } else if pattern.contains(['*', '?']) {
+        let parts: Vec<&str> = pattern.split(std::path::is_separator).collect();
+        let literal = parts.iter().take_while(|part| !part.contains(['*', '?'])).count();
+        let mut base: PathBuf = parts[..literal].iter().collect();
+        if pattern.starts_with(std::path::is_separator) {
+            base = Path::new(std::path::MAIN_SEPARATOR_STR).join(base);
+        }
+        walk_glob(&base, &base, &parts[literal..], excluded.as_deref(), &mut found);
+    } else if path.is_file() {
+        found.push(SourceFile {
+            path: path.to_path_buf(),
+            relative: PathBuf::from(path.file_name().unwrap_or_default()),
+        });
+    } else {
+        return Err(format!("找不到目录或文件: {}", pattern));
+    }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+/// 读取目录项（按名称排序）；读取失败时输出警告并返回空列表
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let read_from = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    match std::fs::read_dir(read_from) {
+        Ok(entries) => {
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| dir.join(entry.file_name()))
+                .collect();
+            paths.sort();
+            paths
+        }
         Err(e) => {
-            eprintln!("错误: 无法处理 PDF 文件: {}", e);
+            eprintln!("WARN: 无法读取目录 {}: {}", read_from.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// 是否为输出目录（或其内容），避免把上次的输出当作输入
+fn is_excluded(path: &Path, excluded: Option<&Path>) -> bool {
+    excluded.is_some_and(|dir| path.canonicalize().is_ok_and(|p| p.starts_with(dir)))
+}
+
+/// 是否为隐藏文件或目录（名称以 `.` 开头）
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// 收集目录中的 PDF 文件（跳过隐藏文件与目录）
+fn walk_dir(
+    dir: &Path,
+    root: &Path,
+    recursive: bool,
+    excluded: Option<&Path>,
+    found: &mut Vec<SourceFile>,
+) {
+    for path in sorted_entries(dir) {
+        if is_hidden(&path) || is_excluded(&path, excluded) {
+            continue;
+        }
+        if path.is_dir() {
+            if recursive {
+                walk_dir(&path, root, recursive, excluded, found);
+            }
+        } else if is_pdf(&path) {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            found.push(SourceFile { path, relative });
+        }
+    }
+}
+
+/// 按通配符的各级路径（`parts`）在 `dir` 下逐级匹配
+fn walk_glob(
+    dir: &Path,
+    root: &Path,
+    parts: &[&str],
+    excluded: Option<&Path>,
+    found: &mut Vec<SourceFile>,
+) {
+    let Some((first, rest)) = parts.split_first() else {
+        return;
+    };
+    if *first == "**" {
+        // 匹配零层目录，或进入每个子目录后继续匹配 "**"
+        let rest = if rest.is_empty() { &["*.pdf"][..] } else { rest };
+        walk_glob(dir, root, rest, excluded, found);
+        for path in sorted_entries(dir) {
+            if path.is_dir() && !is_hidden(&path) && !is_excluded(&path, excluded) {
+                walk_glob(&path, root, parts, excluded, found);
+            }
+        }
+        return;
+    }
+    for path in sorted_entries(dir) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !wildcard_match(first, &name) || is_excluded(&path, excluded) {
+            continue;
+        }
+        if rest.is_empty() {
+            if path.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                found.push(SourceFile { path, relative });
+            }
+        } else if path.is_dir() {
+            walk_glob(&path, root, rest, excluded, found);
+        }
+    }
+}
+
+/// 单层文件名通配符匹配：`*` 匹配任意个字符，`?` 匹配一个字符
+///
+/// # 说明
+/// 与 shell 一致，以 `.` 开头的名称只能被同样以 `.` 开头的模式匹配。
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    // 最近一个 '*' 的位置及其当前匹配到的名称位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    pi = sp + 1;
+                    ni = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// 扩展名是否为 `.pdf`（不区分大小写）
+fn is_pdf(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// batch 的输出路径：输出目录 + 相对路径，文件名追加后缀（`a/b.pdf` -> `out/a/b_wm.pdf`）
+fn batch_output_path(out_dir: &Path, relative: &Path, suffix: &str) -> PathBuf {
+    let mut name = relative.file_stem().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    if let Some(ext) = relative.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    out_dir.join(relative).with_file_name(name)
+}
+
+/// 输出汇总表（状态、页数、大小、耗时）与合计
+///
+/// # 参数
+/// - `show_output`: 文件列显示输出路径（否则显示输入路径）
+fn print_summary_table(results: &[FileResult], start_time: Instant, show_output: bool) {
+    println!("状态    页数    输入大小    输出大小      耗时  文件");
+    let (mut ok, mut failed, mut skipped) = (0, 0, 0);
+    let (mut pages, mut input_bytes, mut output_bytes) = (0, 0, 0);
+    for file in results {
+        let path = if show_output { &file.output } else { &file.input };
+        let row = match &file.outcome {
+            Outcome::Done(report) => {
+                ok += 1;
+                pages += report.pages;
+                input_bytes += report.input_bytes;
+                output_bytes += report.output_bytes;
+                format!(
+                    "成功  {:>6}  {:>10}  {:>10}  {:>8}  {}",
+                    report.pages,
+                    format_bytes(report.input_bytes),
+                    format_bytes(report.output_bytes),
+                    format!("{:.2?}", report.elapsed),
+                    path
+                )
+            }
+            Outcome::Failed(_) => {
+                failed += 1;
+                format!("失败  {:>6}  {:>10}  {:>10}  {:>8}  {}", "-", "-", "-", "-", path)
+            }
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                format!(
+                    "跳过  {:>6}  {:>10}  {:>10}  {:>8}  {}（{}）",
+                    "-", "-", "-", "-", path, reason
+                )
+            }
+        };
+        println!("{}", row);
+    }
+    println!(
+        "合计: 成功 {} 个，失败 {} 个，跳过 {} 个；共 {} 页，{} -> {}；总耗时 {:.2?}",
+        ok,
+        failed,
+        skipped,
+        pages,
+        format_bytes(input_bytes),
+        format_bytes(output_bytes),
+        start_time.elapsed()
+    );
+}
+
+/// 以 B / KB / MB / GB 显示文件大小
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// 处理任务（多个任务时并行），结果与任务一一对应
+fn process(marker: &Watermarker, jobs: Vec<BatchJob>) -> Vec<FileResult> {
+    let reports = if jobs.len() > 1 {
+        marker.batch_parallel(jobs.clone())
+    } else {
+        marker.batch(jobs.clone())
+    };
+    jobs.into_iter()
+        .zip(reports)
+        .map(|(job, result)| FileResult {
+            input: job.input,
+            output: job.output,
+            outcome: match result {
+                Ok(report) => Outcome::Done(report),
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// 将失败的文件输出到标准错误
+fn print_errors(results: &[FileResult]) {
+    for file in results {
+        if let Outcome::Failed(e) = &file.outcome {
+            eprintln!("错误: 无法处理 PDF 文件 {}: {}", file.input, e);
+        }
+    }
+}
+
+/// 将处理结果序列化为 JSON
+fn results_json(results: &[FileResult], start_time: Instant) -> String {
+    results_value(results, start_time.elapsed().as_millis()).to_string()
+}
+
+/// 构造 `--json` 输出的 JSON 值
+///
+/// # 参数
+/// - `elapsed_ms`: 总耗时（毫秒）
+fn results_value(results: &[FileResult], elapsed_ms: u128) -> Value {
+    let files: Vec<Value> = results
+        .iter()
+        .map(|file| {
+            let mut entry = json!({ "input": file.input, "output": file.output });
+            let status = match &file.outcome {
+                Outcome::Done(report) => json!({
+                    "status": "ok",
+                    "pages": report.pages,
+                    "input_bytes": report.input_bytes,
+                    "output_bytes": report.output_bytes,
+                    "elapsed_ms": report.elapsed.as_millis(),
+                    "missing_chars": report.missing_chars.iter().collect::<String>(),
+                }),
+                Outcome::Failed(e) => json!({ "status": "failed", "error": e }),
+                Outcome::Skipped(reason) => json!({ "status": "skipped", "reason": reason }),
+            };
+            if let (Value::Object(entry), Value::Object(status)) = (&mut entry, status) {
+                entry.extend(status);
+            }
+            entry
+        })
+        .collect();
+    let count = |f: fn(&Outcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    json!({
+        "files": files,
+        "succeeded": count(|o| matches!(o, Outcome::Done(_))),
+        "failed": count(|o| matches!(o, Outcome::Failed(_))),
+        "skipped": count(|o| matches!(o, Outcome::Skipped(_))),
+        "elapsed_ms": elapsed_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(input: &str, output: &str) -> BatchJob {
+        BatchJob {
+            input: input.to_string(),
+            output: output.to_string(),
+            vars: TemplateVars::new(),
+        }
+    }
+
+    #[test]
+    fn duplicate_outputs_flags_later_collisions() {
+        let jobs = [
+            job("a/x.pdf", "out/x.pdf"),
+            job("b/x.pdf", "out/x.pdf"),
+            job("a/y.pdf", "out/y.pdf"),
+            job("c/x.pdf", "out//./x.pdf"),
+            job("d/x.pdf", "other/x.pdf"),
+        ];
+        let outputs = jobs.iter().map(|job| job.output.as_str());
+        assert_eq!(duplicate_outputs(outputs), [false, true, false, true, false]);
+    }
+
+    // ------------------------------------------------------------------
+    // batch：通配符与目录收集
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn wildcard_matches_names() {
+        assert!(wildcard_match("*.pdf", "a.pdf"));
+        assert!(wildcard_match("*", "a.pdf"));
+        assert!(wildcard_match("合同*.pdf", "合同2026.pdf"));
+        assert!(wildcard_match("a?c.pdf", "abc.pdf"));
+        assert!(wildcard_match("?.pdf", "张.pdf"));
+        assert!(wildcard_match("*a*b*", "xxaYYbzz"));
+        assert!(!wildcard_match("*.pdf", ".pdf.pdf"));
+        assert!(wildcard_match(".*", ".hidden"));
+        assert!(!wildcard_match("*.pdf", "a.PDF"));
+        assert!(!wildcard_match("a?c.pdf", "ac.pdf"));
+        assert!(!wildcard_match("*.pdf", "a.pdf.bak"));
+        assert!(!wildcard_match("*.pdf", ".hidden.pdf"));
+        assert!(!wildcard_match("", "a"));
+        assert!(wildcard_match("", ""));
+        // 回溯：第一个 * 先少匹配，失败后扩展
+        assert!(wildcard_match("*ab", "aab"));
+        assert!(wildcard_match("**x", "abcx"));
+    }
+
+    /// 在临时目录下创建空文件组成的目录树
+    fn temp_tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("water_mark_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
         }
+        root
+    }
+
+    const TREE: &[&str] = &[
+        "a.pdf",
+        "b.PDF",
+        "notes.txt",
+        ".hidden.pdf",
+        "sub/c.pdf",
+        "sub/合同1.pdf",
+        "sub/deep/d.pdf",
+        ".git/e.pdf",
+        "out/f.pdf",
+    ];
+
+    fn relatives(pattern: &str, recursive: bool, out_dir: &Path) -> Vec<String> {
+        collect_sources(pattern, recursive, out_dir)
+            .unwrap()
+            .into_iter()
+            .map(|source| source.relative.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn collects_directories() {
+        let root = temp_tree("dir", TREE);
+        let out = root.join("out");
+        let dir = root.to_string_lossy().into_owned();
+        assert_eq!(relatives(&dir, false, &out), ["a.pdf", "b.PDF"]);
+        assert_eq!(
+            relatives(&dir, true, &out),
+            ["a.pdf", "b.PDF", "sub/c.pdf", "sub/deep/d.pdf", "sub/合同1.pdf"]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn collects_globs() {
+        let root = temp_tree("glob", TREE);
+        let out = root.join("out");
+        let glob = |pattern: &str| format!("{}/{}", root.to_string_lossy(), pattern);
+        assert_eq!(relatives(&glob("*.pdf"), false, &out), ["a.pdf"]);
+        assert_eq!(relatives(&glob("*/*.pdf"), false, &out), ["sub/c.pdf", "sub/合同1.pdf"]);
+        assert_eq!(
+            relatives(&glob("**"), false, &out),
+            ["a.pdf", "sub/c.pdf", "sub/deep/d.pdf", "sub/合同1.pdf"]
+        );
+        assert_eq!(relatives(&glob("**/合同*.pdf"), false, &out), ["sub/合同1.pdf"]);
+        // 字面目录前缀不计入相对路径
+        assert_eq!(relatives(&glob("sub/**/?.pdf"), false, &out), ["c.pdf", "deep/d.pdf"]);
+        // 显式写出的隐藏名称可以匹配
+        assert_eq!(relatives(&glob(".*.pdf"), false, &out), [".hidden.pdf"]);
+        assert_eq!(relatives(&glob("*.txt"), false, &out), ["notes.txt"]);
+        std::fs::remove_dir_all(&root).unwrap();
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn collect_reports_missing_paths() {
+        let missing = std::env::temp_dir().join("water_mark_missing_dir/x.pdf");
+        let err = collect_sources(&missing.to_string_lossy(), false, Path::new("out")).err();
+        assert_eq!(err, Some(format!("找不到目录或文件: {}", missing.to_string_lossy())));
+    }
+
+    #[test]
+    fn batch_output_paths() {
+        let out = Path::new("out");
+        assert_eq!(batch_output_path(out, Path::new("a/b.pdf"), "_wm"), Path::new("out/a/b_wm.pdf"));
+        assert_eq!(batch_output_path(out, Path::new("b.PDF"), ""), Path::new("out/b.PDF"));
+        assert_eq!(batch_output_path(out, Path::new("v1.2/报告"), "_wm"), Path::new("out/v1.2/报告_wm"));
+    }
+
+    // ------------------------------------------------------------------
+    // --json 输出
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn json_output_lists_each_outcome() {
+        let results = [
+            FileResult {
+                input: "a.pdf".to_string(),
+                output: "out/a.pdf".to_string(),
+                outcome: Outcome::Done(WatermarkReport {
+                    pages: 3,
+                    input_bytes: 1000,
+                    output_bytes: 1500,
+                    missing_chars: vec!['✓', '"'],
+                    elapsed: std::time::Duration::from_millis(12),
+                }),
+            },
+            FileResult {
+                input: "b\\\"c\".pdf".to_string(),
+                output: "out/b.pdf".to_string(),
+                outcome: Outcome::Failed("第一行\n\t第二行".to_string()),
+            },
+            FileResult {
+                input: "c.pdf".to_string(),
+                output: "out/c.pdf".to_string(),
+                outcome: Outcome::Skipped("已有水印".to_string()),
+            },
+        ];
+        let expected = json!({
+            "files": [
+                {
+                    "input": "a.pdf", "output": "out/a.pdf", "status": "ok", "pages": 3,
+                    "input_bytes": 1000, "output_bytes": 1500, "elapsed_ms": 12,
+                    "missing_chars": "✓\"",
+                },
+                {
+                    "input": "b\\\"c\".pdf", "output": "out/b.pdf", "status": "failed",
+                    "error": "第一行\n\t第二行",
+                },
+                { "input": "c.pdf", "output": "out/c.pdf", "status": "skipped", "reason": "已有水印" },
+            ],
+            "succeeded": 1,
+            "failed": 1,
+            "skipped": 1,
+            "elapsed_ms": 40,
+        });
+        let value = results_value(&results, 40);
+        assert_eq!(value, expected);
+        // 输出必须是可以重新解析的合法 JSON
+        let text = value.to_string();
+        assert!(text.contains(r#""input":"b\\\"c\".pdf""#), "{}", text);
+        assert!(text.contains(r#""error":"第一行\n\t第二行""#), "{}", text);
+        assert_eq!(serde_json::from_str::<Value>(&text).unwrap(), expected);
+    }
+
+    #[test]
+    fn formats_byte_sizes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KB");
+        assert_eq!(format_bytes(9261), "9.0 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 + 512 * 1024), "5.5 MB");
+        assert_eq!(format_bytes(3 << 30), "3.0 GB");
+        assert_eq!(format_bytes(2048 << 30), "2048.0 GB");
+    }
+}
@@ -0,0 +1,405 @@
+//! 分发清单 - 从 CSV 或 JSON Lines 文件读取 (输入, 输出, 模板变量) 任务
+//!
+//! 每行一个任务：`input`、`output` 两列为必填，其余列都作为模板变量（如 `name`、`email`）。
+
+use crate::{BatchJob, TemplateVars};
+use serde_json::Value;
+use std::path::Path;
+
+/// 清单文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// 逗号分隔，首行为列名；字段可用双引号包围（内含逗号、换行，`""` 表示一个引号）
+    Csv,
+    /// 每行一个 JSON 对象（也接受整个文件为一个对象数组）
+    JsonLines,
+}
+
+impl ManifestFormat {
+    /// 按扩展名判断格式（`.csv`、`.jsonl` / `.ndjson` / `.json`），无法判断时按内容首字符识别
+    pub fn detect(path: &Path, content: &str) -> Self {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "csv" => ManifestFormat::Csv,
+            "jsonl" | "ndjson" | "json" => ManifestFormat::JsonLines,
+            _ if content.trim_start_matches('\u{feff}').trim_start().starts_with(['{', '[']) => {
+                ManifestFormat::JsonLines
+            }
+            _ => ManifestFormat::Csv,
+        }
+    }
+}
+
+/// 读取清单文件
+///
+/// # 说明
+/// 清单中的相对路径以清单文件所在目录为基准。
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{read_manifest, Watermarker};
+///
+/// // recipients.csv:
+/// // input,output,name
+/// // dataroom.pdf,out/zhangsan.pdf,张三
+/// let jobs = read_manifest("recipients.csv")?;
+/// let marker = Watermarker::from_file("font.otf")?;
+/// let results = marker.distribute(jobs);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_manifest(path: &str) -> Result<Vec<BatchJob>, Box<dyn std::error::Error>> {
+    let path = Path::new(path);
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取清单 {} 失败: {}", path.display(), e))?;
+    let mut jobs = parse_manifest(&content, ManifestFormat::detect(path, &content))?;
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let resolve = |file: &str| -> String {
+        if Path::new(file).is_absolute() {
+            file.to_string()
+        } else {
+            base.join(file).to_string_lossy().into_owned()
+        }
+    };
+    for job in &mut jobs {
+        job.input = resolve(&job.input);
+        job.output = resolve(&job.output);
+    }
+    Ok(jobs)
+}
+
+/// 解析清单内容（路径按原样返回）
+///
+/// # 返回
+/// - `Ok(Vec<BatchJob>)`: 与清单各行一一对应的任务
+/// - `Err`: 格式错误，错误信息包含行号
+pub fn parse_manifest(
+    content: &str,
+    format: ManifestFormat,
+) -> Result<Vec<BatchJob>, Box<dyn std::error::Error>> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    match format {
+        ManifestFormat::Csv => parse_csv_manifest(content),
+        ManifestFormat::JsonLines => parse_json_manifest(content),
+    }
+}
+
+/// 由一行的 (列名, 值) 构造任务
+fn row_to_job<'a, I>(line: usize, fields: I) -> Result<BatchJob, Box<dyn std::error::Error>>
+where
+    I: IntoIterator<Item = (&'a str, String)>,
+{
+    let (mut input, mut output) = (None, None);
+    let mut vars = TemplateVars::new();
+    for (key, value) in fields {
+        if key.eq_ignore_ascii_case("input") {
+            input = Some(value);
+        } else if key.eq_ignore_ascii_case("output") {
+            output = Some(value);
+        } else {
+            vars.insert(key.to_string(), value);
+        }
+    }
+    let required = |value: Option<String>, key: &str| match value {
+        Some(value) if !value.trim().is_empty() => Ok(value.trim().to_string()),
+        _ => Err(format!("清单第 {} 行: 缺少 {}", line, key)),
+    };
+    Ok(BatchJob {
+        input: required(input, "input")?,
+        output: required(output, "output")?,
+        vars,
+    })
+}
+
+/// 解析 CSV 清单：首行为列名，必须包含 `input` 与 `output` 列
+fn parse_csv_manifest(content: &str) -> Result<Vec<BatchJob>, Box<dyn std::error::Error>> {
+    let mut rows = parse_csv(content)?.into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Err("清单为空".into());
+    };
+    let header: Vec<String> = header.iter().map(|name| name.trim().to_string()).collect();
+    for (i, name) in header.iter().enumerate() {
+        if name.is_empty() {
+            return Err(format!("清单第 1 行: 第 {} 列的列名为空", i + 1).into());
+        }
+        if header[..i].iter().any(|prev| prev.eq_ignore_ascii_case(name)) {
+            return Err(format!("清单第 1 行: 列名 {} 重复", name).into());
+        }
+    }
+    for key in ["input", "output"] {
+        if !header.iter().any(|name| name.eq_ignore_ascii_case(key)) {
+            return Err(format!("清单第 1 行: 缺少 {} 列", key).into());
+        }
+    }
+
+    rows.map(|(line, fields)| {
+        if fields.len() != header.len() {
+            return Err(format!(
+                "清单第 {} 行: 有 {} 列，列名有 {} 列",
+                line,
+                fields.len(),
+                header.len()
+            )
+            .into());
+        }
+        row_to_job(line, header.iter().map(String::as_str).zip(fields))
+    })
+    .collect()
+}
+
+/// 将 CSV 文本拆分为 (起始行号, 字段) 记录，跳过空行
+fn parse_csv(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // 当前字段是否以引号开头（用于区分空行与只有一个空字段的行）
+    let mut quoted = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                in_quotes = true;
+                quoted = true;
+            }
+            ',' => {
+                fields.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !fields.is_empty() || !field.is_empty() || quoted {
+                    fields.push(std::mem::take(&mut field));
+                    records.push((record_line, std::mem::take(&mut fields)));
+                }
+                quoted = false;
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!("清单第 {} 行: 引号未闭合", record_line));
+    }
+    if !fields.is_empty() || !field.is_empty() || quoted {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+    Ok(records)
+}
+
+/// 解析 JSON Lines 清单（或整个文件为对象数组）
+fn parse_json_manifest(content: &str) -> Result<Vec<BatchJob>, Box<dyn std::error::Error>> {
+    if content.trim_start().starts_with('[') {
+        let rows: Vec<Value> =
+            serde_json::from_str(content).map_err(|e| format!("清单 JSON 解析失败: {}", e))?;
+        return rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| json_row_to_job(i + 1, row))
+            .collect();
+    }
+
+    let mut jobs = Vec::new();
+    for (i, text) in content.lines().enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let row: Value = serde_json::from_str(text)
+            .map_err(|e| format!("清单第 {} 行: JSON 解析失败: {}", i + 1, e))?;
+        jobs.push(json_row_to_job(i + 1, &row)?);
+    }
+    Ok(jobs)
+}
+
+/// 由一个 JSON 对象构造任务：字符串、数字、布尔值作为变量值，`null` 视为空字符串
+fn json_row_to_job(line: usize, row: &Value) -> Result<BatchJob, Box<dyn std::error::Error>> {
+    let Value::Object(map) = row else {
+        return Err(format!("清单第 {} 行: 应为 JSON 对象", line).into());
+    };
+    let mut fields = Vec::with_capacity(map.len());
+    for (key, value) in map {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::Null => String::new(),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(
+                    format!("清单第 {} 行: {} 的值必须是字符串或数字", line, key).into(),
+                );
+            }
+        };
+        fields.push((key.as_str(), value));
+    }
+    row_to_job(line, fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 期望的 (行号, 字段) 记录
+    fn rows(expected: &[(usize, &[&str])]) -> Vec<(usize, Vec<String>)> {
+        expected
+            .iter()
+            .map(|(line, fields)| (*line, fields.iter().map(|f| f.to_string()).collect()))
+            .collect()
+    }
+
+    fn records(content: &str) -> Vec<(usize, Vec<String>)> {
+        parse_csv(content).unwrap()
+    }
+
+    fn csv_error(content: &str) -> String {
+        parse_manifest(content, ManifestFormat::Csv).unwrap_err().to_string()
+    }
+
+    fn json_error(content: &str) -> String {
+        parse_manifest(content, ManifestFormat::JsonLines).unwrap_err().to_string()
+    }
+
+    // ------------------------------------------------------------------
+    // parse_csv
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn csv_splits_records_and_fields() {
+        assert_eq!(records("a,b\n1,2\n"), rows(&[(1, &["a", "b"]), (2, &["1", "2"])]));
+        // 末行没有换行符
+        assert_eq!(records("a,b\n1,2"), rows(&[(1, &["a", "b"]), (2, &["1", "2"])]));
+        // 空字段保留
+        assert_eq!(records(",x,\n"), rows(&[(1, &["", "x", ""])]));
+        assert_eq!(records(""), rows(&[]));
+    }
+
+    #[test]
+    fn csv_handles_quotes() {
+        assert_eq!(
+            records("\"a,b\",\"say \"\"hi\"\"\"\n"),
+            rows(&[(1, &["a,b", "say \"hi\""])])
+        );
+        // 只有一个空的引号字段的行不是空行
+        assert_eq!(records("\"\"\n"), rows(&[(1, &[""])]));
+        assert_eq!(records("\"\"\"\"\n"), rows(&[(1, &["\""])]));
+        // 不以引号开头的字段中的引号按原样保留
+        assert_eq!(records("a\"b\n"), rows(&[(1, &["a\"b"])]));
+    }
+
+    #[test]
+    fn csv_quoted_newlines_keep_line_numbers() {
+        assert_eq!(
+            records("h1,h2\n\"第一行\n第二行\",x\ny,z\n"),
+            rows(&[(1, &["h1", "h2"]), (2, &["第一行\n第二行", "x"]), (4, &["y", "z"])])
+        );
+    }
+
+    #[test]
+    fn csv_handles_crlf_and_blank_lines() {
+        assert_eq!(
+            records("a,b\r\n\r\n1,2\r\n\"x\r\ny\",3"),
+            rows(&[(1, &["a", "b"]), (3, &["1", "2"]), (4, &["x\r\ny", "3"])])
+        );
+    }
+
+    #[test]
+    fn csv_reports_unclosed_quote_at_record_start() {
+        assert_eq!(parse_csv("a,b\n1,\"2\n3\n"), Err("清单第 2 行: 引号未闭合".to_string()));
+        assert_eq!(parse_csv("\"a"), Err("清单第 1 行: 引号未闭合".to_string()));
+    }
+
+    // ------------------------------------------------------------------
+    // CSV 清单
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn csv_manifest_builds_jobs() {
+        let content = "\u{feff}Input, output ,name\r\n a.pdf ,out/张三.pdf,\"张三, 法务\"\r\nb.pdf,out/b.pdf,\n";
+        let jobs = parse_manifest(content, ManifestFormat::Csv).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!((jobs[0].input.as_str(), jobs[0].output.as_str()), ("a.pdf", "out/张三.pdf"));
+        assert_eq!(jobs[0].vars.get("name").map(String::as_str), Some("张三, 法务"));
+        assert_eq!(jobs[0].vars.len(), 1);
+        assert_eq!(jobs[1].vars.get("name").map(String::as_str), Some(""));
+    }
+
+    #[test]
+    fn csv_manifest_errors_name_the_line() {
+        assert_eq!(csv_error(""), "清单为空");
+        assert_eq!(csv_error("input,,output\n"), "清单第 1 行: 第 2 列的列名为空");
+        assert_eq!(csv_error("input,output,Input\n"), "清单第 1 行: 列名 Input 重复");
+        assert_eq!(csv_error("input,name\n"), "清单第 1 行: 缺少 output 列");
+        // 引号内的换行计入行号
+        assert_eq!(
+            csv_error("input,output,note\na.pdf,o.pdf,\"多\n行\"\nb.pdf,o2.pdf\n"),
+            "清单第 4 行: 有 2 列，列名有 3 列"
+        );
+        assert_eq!(csv_error("input,output\n  ,o.pdf\n"), "清单第 2 行: 缺少 input");
+        assert_eq!(csv_error("input,output\na.pdf,\"x"), "清单第 2 行: 引号未闭合");
+    }
+
+    // ------------------------------------------------------------------
+    // JSON Lines 清单
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn json_manifest_builds_jobs() {
+        let content = "{\"input\":\"a.pdf\",\"output\":\"o.pdf\",\"id\":7,\"vip\":true,\"note\":null}\n\n{\"input\":\"b.pdf\",\"output\":\"p.pdf\"}";
+        let jobs = parse_manifest(content, ManifestFormat::JsonLines).unwrap();
+        assert_eq!(jobs.len(), 2);
+        let var = |key: &str| jobs[0].vars.get(key).map(String::as_str);
+        assert_eq!((var("id"), var("vip"), var("note")), (Some("7"), Some("true"), Some("")));
+        assert_eq!(jobs[1].input, "b.pdf");
+
+        let array = parse_manifest("[{\"input\":\"a.pdf\",\"output\":\"o.pdf\"}]", ManifestFormat::JsonLines)
+            .unwrap();
+        assert_eq!(array[0].output, "o.pdf");
+    }
+
+    #[test]
+    fn json_manifest_errors_name_the_line() {
+        let err = json_error("{\"input\":\"a.pdf\",\"output\":\"o.pdf\"}\n\n{\"input\":");
+        assert!(err.starts_with("清单第 3 行: JSON 解析失败: "), "{}", err);
+        assert_eq!(json_error("[1]\n"), "清单第 1 行: 应为 JSON 对象");
+        assert_eq!(
+            json_error("{\"input\":\"a.pdf\",\"output\":\"o.pdf\",\"tags\":[1]}"),
+            "清单第 1 行: tags 的值必须是字符串或数字"
+        );
+        assert_eq!(json_error("{\"input\":\"a.pdf\"}"), "清单第 1 行: 缺少 output");
+        assert_eq!(
+            json_error("[{\"input\":\"a.pdf\",\"output\":\"o.pdf\"},{\"output\":\"p.pdf\"}]"),
+            "清单第 2 行: 缺少 input"
+        );
+    }
+
+    #[test]
+    fn detects_format() {
+        let detect = |path: &str, content: &str| ManifestFormat::detect(Path::new(path), content);
+        assert_eq!(detect("a.CSV", "{"), ManifestFormat::Csv);
+        assert_eq!(detect("a.ndjson", "input,output"), ManifestFormat::JsonLines);
+        assert_eq!(detect("list", "\u{feff}  {\"input\":1}"), ManifestFormat::JsonLines);
+        assert_eq!(detect("list", "input,output"), ManifestFormat::Csv);
+    }
+}
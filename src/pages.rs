@@ -0,0 +1,175 @@
+//! 页面选择 - 只为指定页码添加水印
+//!
+//! 页码从 1 开始，格式与打印对话框一致：`1-3,5,10-`。
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// 需要添加水印的页面集合
+///
+/// # 说明
+/// 由逗号分隔的若干项组成，每项为：
+/// - `N`：单页
+/// - `N-M`：第 N 到第 M 页（含）
+/// - `N-`：第 N 页到最后一页
+/// - `-M`：第 1 页到第 M 页
+/// - `odd` / `even`：奇数页 / 偶数页
+/// - `last`：最后一页
+///
+/// # 示例
+/// ```
+/// use water_mark::PageSelection;
+///
+/// let pages: PageSelection = "1-3,5,10-".parse()?;
+/// assert!(pages.contains(2, 20));
+/// assert!(!pages.contains(4, 20));
+/// assert!(pages.contains(15, 20));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSelection {
+    items: Vec<PageItem>,
+}
+
+/// 页面选择中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PageItem {
+    /// 页码区间，`None` 上界表示到最后一页
+    Range(u32, Option<u32>),
+    Odd,
+    Even,
+    Last,
+}
+
+impl PageSelection {
+    /// 由页码区间构造（如 `[1..=3, 5..=5]`）
+    pub fn from_ranges<I: IntoIterator<Item = RangeInclusive<u32>>>(ranges: I) -> Self {
+        PageSelection {
+            items: ranges
+                .into_iter()
+                .map(|r| PageItem::Range(*r.start(), Some(*r.end())))
+                .collect(),
+        }
+    }
+
+    /// 第 `page` 页（从 1 开始）是否被选中
+    ///
+    /// # 参数
+    /// - `page`: 页码
+    /// - `total`: 文档总页数（用于 `N-`、`last`）
+    pub fn contains(&self, page: u32, total: usize) -> bool {
+        let total = u32::try_from(total).unwrap_or(u32::MAX);
+        // 文档之外的页码（包括空文档中 `last` 对应的第 0 页）一律不选中
+        if page == 0 || page > total {
+            return false;
+        }
+        self.items.iter().any(|item| match *item {
+            PageItem::Range(start, end) => page >= start && page <= end.unwrap_or(total),
+            PageItem::Odd => !page.is_multiple_of(2),
+            PageItem::Even => page.is_multiple_of(2),
+            PageItem::Last => page == total,
+        })
+    }
+}
+
+impl FromStr for PageSelection {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let page = |v: &str| -> Result<u32, Box<dyn std::error::Error>> {
+            match v.trim().parse::<u32>() {
+                Ok(n) if n >= 1 => Ok(n),
+                _ => Err(format!("无效的页码 '{}'（页码从 1 开始）", v.trim()).into()),
+            }
+        };
+
+        let mut items = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            let item = match part.to_ascii_lowercase().as_str() {
+                "" => return Err(format!("页面范围 '{}' 中有空项", s).into()),
+                "odd" => PageItem::Odd,
+                "even" => PageItem::Even,
+                "last" => PageItem::Last,
+                _ => match part.split_once('-') {
+                    None => {
+                        let n = page(part)?;
+                        PageItem::Range(n, Some(n))
+                    }
+                    Some((start, end)) if start.trim().is_empty() && end.trim().is_empty() => {
+                        return Err(format!("页面范围 '{}' 缺少起始页或结束页", part).into());
+                    }
+                    Some((start, end)) => {
+                        let start = if start.trim().is_empty() { 1 } else { page(start)? };
+                        let end = if end.trim().is_empty() { None } else { Some(page(end)?) };
+                        if end.is_some_and(|end| end < start) {
+                            return Err(format!("页面范围 '{}' 的起始页大于结束页", part).into());
+                        }
+                        PageItem::Range(start, end)
+                    }
+                },
+            };
+            items.push(item);
+        }
+        Ok(PageSelection { items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(spec: &str, total: usize) -> Vec<u32> {
+        let pages: PageSelection = spec.parse().unwrap();
+        (1..=total as u32).filter(|&page| pages.contains(page, total)).collect()
+    }
+
+    fn parse_err(spec: &str) -> String {
+        spec.parse::<PageSelection>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_single_pages_and_ranges() {
+        assert_eq!(selected("1-3,5,10-", 12), [1, 2, 3, 5, 10, 11, 12]);
+        assert_eq!(selected("-2", 5), [1, 2]);
+        assert_eq!(selected(" 2 - 3 , 3 ", 5), [2, 3]);
+        assert_eq!(selected("4-4", 5), [4]);
+    }
+
+    #[test]
+    fn parses_keywords() {
+        assert_eq!(selected("odd", 5), [1, 3, 5]);
+        assert_eq!(selected("EVEN", 5), [2, 4]);
+        assert_eq!(selected("1,last", 5), [1, 5]);
+        assert_eq!(selected("last", 1), [1]);
+    }
+
+    #[test]
+    fn handles_pages_beyond_document() {
+        assert_eq!(selected("10-", 5), Vec::<u32>::new());
+        assert_eq!(selected("3-100", 5), [3, 4, 5]);
+        // 空文档：last 不对应任何页
+        let pages: PageSelection = "last".parse().unwrap();
+        assert!(!pages.contains(0, 0));
+        assert!(!pages.contains(1, 0));
+        let pages: PageSelection = "1-".parse().unwrap();
+        assert!(!pages.contains(6, 5));
+    }
+
+    #[test]
+    fn from_ranges_matches_parsed() {
+        assert_eq!(PageSelection::from_ranges([1..=3, 5..=5]), "1-3,5".parse().unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        assert_eq!(parse_err("0"), "无效的页码 '0'（页码从 1 开始）");
+        assert_eq!(parse_err("0-3"), "无效的页码 '0'（页码从 1 开始）");
+        assert_eq!(parse_err("3-1"), "页面范围 '3-1' 的起始页大于结束页");
+        assert_eq!(parse_err("-"), "页面范围 '-' 缺少起始页或结束页");
+        assert_eq!(parse_err("1-2-3"), "无效的页码 '2-3'（页码从 1 开始）");
+        assert_eq!(parse_err("first"), "无效的页码 'first'（页码从 1 开始）");
+        assert_eq!(parse_err("99999999999"), "无效的页码 '99999999999'（页码从 1 开始）");
+        assert_eq!(parse_err("1,,2"), "页面范围 '1,,2' 中有空项");
+        assert_eq!(parse_err(""), "页面范围 '' 中有空项");
+    }
+}
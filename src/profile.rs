@@ -0,0 +1,560 @@
+//! 水印配置文件 - 从 TOML 或 JSON 文件读取命名的水印方案（profile）
+//!
+//! 每个方案对应一组水印设置，按名称选用：
+//!
+//! ```toml
+//! [profile.confidential]
+//! template = "致{name}-{date}:高度保密"
+//! font = "STSongStd-Light-Acro.otf"
+//! opacity = 0.15
+//!
+//! [profile.confidential.vars]
+//! name = "张三"
+//! ```
+//!
+//! JSON 文件结构相同：`{"profile": {"confidential": {"opacity": 0.15}}}`。
+
+use crate::{BlendMode, GridMode, PageSelection, TemplateVars, WatermarkColor, Watermarker};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 配置文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// 按扩展名判断格式（`.toml`、`.json`），无法判断时按内容首字符识别
+    pub fn detect(path: &Path, content: &str) -> Self {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "toml" => ConfigFormat::Toml,
+            "json" => ConfigFormat::Json,
+            _ if content.trim_start_matches('\u{feff}').trim_start().starts_with('{') => {
+                ConfigFormat::Json
+            }
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// 一个命名的水印方案
+///
+/// # 说明
+/// 未设置的项（`None` / 空）保持水印器原有设置。配置文件中的键名：
+///
+/// | 键 | 类型 | 说明 |
+/// |----|------|------|
+/// | `font` | 字符串或字符串数组 | 字体文件或字体名称，数组中后面的作为缺字回退字体 |
+/// | `template` | 字符串 | 水印模板 |
+/// | `vars` | 表 | 模板默认变量，值为字符串或数字 |
+/// | `size` | 数值 | 字体大小（点数，大于 0） |
+/// | `angle` | 数值 | 旋转角度（度数，逆时针） |
+/// | `opacity` | 数值 | 透明度（0 到 1） |
+/// | `color` | 字符串 | 颜色，格式见 [`WatermarkColor`] |
+/// | `blend_mode` | 字符串 | 混合模式，见 [`BlendMode`] |
+/// | `pages` | 字符串 | 页面范围，格式见 [`PageSelection`] |
+/// | `layout` | 字符串 | `tile`、`pattern` 或 `center`，见 [`GridMode`] |
+/// | `utc_offset` | 字符串 | 时间占位符的 UTC 偏移量，如 `+08:00` |
+/// | `precision` | 整数 | 路径坐标保留的小数位数（0 到 10） |
+/// | `compress` | 布尔值 | 是否压缩新增的内容流 |
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// 字体回退链（主字体在前）
+    pub fonts: Vec<String>,
+    pub template: Option<String>,
+    /// 模板默认变量，见 [`Watermarker::with_vars`]
+    pub vars: TemplateVars,
+    pub font_size: Option<f32>,
+    pub angle: Option<f32>,
+    pub opacity: Option<f32>,
+    pub color: Option<WatermarkColor>,
+    pub blend_mode: Option<BlendMode>,
+    pub pages: Option<PageSelection>,
+    pub layout: Option<GridMode>,
+    pub utc_offset: Option<String>,
+    pub precision: Option<u8>,
+    pub compress: Option<bool>,
+}
+
+impl Profile {
+    /// 使用方案中的字体创建水印器，并应用其余设置
+    ///
+    /// # 返回
+    /// - `Err`: 方案未指定字体，或字体加载失败
+    pub fn watermarker(&self) -> Result<Watermarker, Box<dyn std::error::Error>> {
+        if self.fonts.is_empty() {
+            return Err("水印方案未指定字体（font）".into());
+        }
+        self.apply_to(Watermarker::from_specs(&self.fonts)?)
+    }
+
+    /// 将方案中的设置（字体除外）应用到已有的水印器
+    pub fn apply_to(&self, mut marker: Watermarker) -> Result<Watermarker, Box<dyn std::error::Error>> {
+        if let Some(template) = &self.template {
+            marker = marker.with_template(template);
+        }
+        if !self.vars.is_empty() {
+            marker = marker.with_vars(self.vars.clone());
+        }
+        if let Some(size) = self.font_size {
+            marker = marker.with_font_size(size);
+        }
+        if let Some(angle) = self.angle {
+            marker = marker.with_angle(angle);
+        }
+        if let Some(opacity) = self.opacity {
+            marker = marker.with_opacity(opacity);
+        }
+        if let Some(color) = &self.color {
+            marker = marker.with_color(color.clone());
+        }
+        if let Some(mode) = self.blend_mode {
+            marker = marker.with_blend_mode(mode);
+        }
+        if let Some(pages) = &self.pages {
+            marker = marker.with_pages(pages.clone());
+        }
+        if let Some(layout) = self.layout {
+            marker = marker.with_grid_mode(layout);
+        }
+        if let Some(offset) = &self.utc_offset {
+            marker = marker.with_utc_offset(offset)?;
+        }
+        if self.precision.is_some() {
+            marker = marker.with_precision(self.precision);
+        }
+        if let Some(compress) = self.compress {
+            marker = marker.with_compression(compress);
+        }
+        Ok(marker)
+    }
+}
+
+/// 配置文件中的全部水印方案
+///
+/// # 示例
+/// ```no_run
+/// use water_mark::{ProfileConfig, TemplateVars};
+///
+/// let config = ProfileConfig::load("water_mark.toml")?;
+/// let marker = config.profile("confidential")?.watermarker()?;
+/// let vars = TemplateVars::from([("name".to_string(), "李四".to_string())]);
+/// marker.apply("in.pdf", "out.pdf", &vars)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProfileConfig {
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl ProfileConfig {
+    /// 读取配置文件
+    ///
+    /// # 说明
+    /// 字体中的相对路径以配置文件所在目录为基准（该目录下不存在时按原样作为字体名称或路径）。
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = Path::new(path);
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置 {} 失败: {}", path.display(), e))?;
+        let mut config = Self::parse(&content, ConfigFormat::detect(path, &content))
+            .map_err(|e| format!("配置 {}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for profile in config.profiles.values_mut() {
+            for font in &mut profile.fonts {
+                *font = resolve_font(base, font);
+            }
+        }
+        Ok(config)
+    }
+
+    /// 解析配置内容（字体路径按原样返回）
+    ///
+    /// # 返回
+    /// - `Ok(ProfileConfig)`: 全部方案
+    /// - `Err`: 格式错误或取值无效，错误信息指出出错的键（如 `profile.confidential.opacity`）
+    pub fn parse(content: &str, format: ConfigFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let root: Value = match format {
+            ConfigFormat::Toml => {
+                toml::from_str(content).map_err(|e| format!("TOML 解析失败: {}", e))?
+            }
+            ConfigFormat::Json => {
+                serde_json::from_str(content).map_err(|e| format!("JSON 解析失败: {}", e))?
+            }
+        };
+        let Value::Object(root) = root else {
+            return Err("配置顶层应为表（对象）".into());
+        };
+
+        let mut profiles = BTreeMap::new();
+        for (key, value) in &root {
+            if key != "profile" {
+                return Err(format!("{}: 未知的配置项（水印方案应写在 [profile.<名称>] 下）", key).into());
+            }
+            let Value::Object(table) = value else {
+                return Err("profile: 应为表（对象）".into());
+            };
+            for (name, value) in table {
+                let Value::Object(entries) = value else {
+                    return Err(format!("profile.{}: 应为表（对象）", name).into());
+                };
+                profiles.insert(name.clone(), parse_profile(name, entries)?);
+            }
+        }
+        Ok(ProfileConfig { profiles })
+    }
+
+    /// 按名称取水印方案
+    ///
+    /// # 返回
+    /// - `Err`: 没有该名称的方案，错误信息列出可用的方案名
+    pub fn profile(&self, name: &str) -> Result<&Profile, Box<dyn std::error::Error>> {
+        self.profiles.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.names().collect();
+            if names.is_empty() {
+                format!("配置中没有水印方案 '{}'（配置中没有任何方案）", name).into()
+            } else {
+                format!("配置中没有水印方案 '{}'（可用: {}）", name, names.join(", ")).into()
+            }
+        })
+    }
+
+    /// 全部方案名（按字母顺序）
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+}
+
+/// 校验并解析一个方案，错误信息带有完整的键路径
+fn parse_profile(name: &str, entries: &Map<String, Value>) -> Result<Profile, String> {
+    let mut profile = Profile::default();
+    for (key, value) in entries {
+        let path = format!("profile.{}.{}", name, key);
+        let invalid = |expected: &str| format!("{}: 应为{}，实际为 {}", path, expected, value);
+        match key.as_str() {
+            "font" => {
+                profile.fonts = match value {
+                    Value::String(font) => vec![font.clone()],
+                    Value::Array(fonts) => fonts
+                        .iter()
+                        .map(|font| font.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid("字符串或字符串数组"))?,
+                    _ => return Err(invalid("字符串或字符串数组")),
+                };
+                if profile.fonts.is_empty() || profile.fonts.iter().any(|f| f.trim().is_empty()) {
+                    return Err(format!("{}: 字体不能为空", path));
+                }
+            }
+            "template" => {
+                profile.template = Some(value.as_str().ok_or_else(|| invalid("字符串"))?.to_string());
+            }
+            "vars" => {
+                let Value::Object(vars) = value else {
+                    return Err(invalid("表（变量名 = 值）"));
+                };
+                for (var, value) in vars {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Number(n) => n.to_string(),
+                        Value::Bool(b) => b.to_string(),
+                        _ => {
+                            return Err(format!("{}.{}: 应为字符串或数字，实际为 {}", path, var, value));
+                        }
+                    };
+                    profile.vars.insert(var.clone(), value);
+                }
+            }
+            "size" => match value.as_f64() {
+                Some(size) if size > 0.0 && size.is_finite() => profile.font_size = Some(size as f32),
+                _ => return Err(invalid("大于 0 的数值")),
+            },
+            "angle" => match value.as_f64() {
+                Some(angle) if angle.is_finite() => profile.angle = Some(angle as f32),
+                _ => return Err(invalid("数值")),
+            },
+            "opacity" => match value.as_f64() {
+                Some(opacity) if (0.0..=1.0).contains(&opacity) => {
+                    profile.opacity = Some(opacity as f32);
+                }
+                _ => return Err(invalid("介于 0 和 1 之间的数值")),
+            },
+            "color" => {
+                let color = value.as_str().ok_or_else(|| invalid("颜色字符串"))?;
+                profile.color = Some(color.parse().map_err(|e| format!("{}: {}", path, e))?);
+            }
+            "blend_mode" => {
+                let mode = value.as_str().ok_or_else(|| invalid("混合模式名称"))?;
+                profile.blend_mode = Some(mode.parse().map_err(|e| format!("{}: {}", path, e))?);
+            }
+            "pages" => {
+                let pages = match value {
+                    Value::String(pages) => pages.clone(),
+                    Value::Number(page) => page.to_string(),
+                    _ => return Err(invalid("页面范围字符串（如 \"1-3,5\"）")),
+                };
+                profile.pages = Some(pages.parse().map_err(|e| format!("{}: {}", path, e))?);
+            }
+            "layout" => {
+                let layout = value.as_str().ok_or_else(|| invalid("字符串（tile、pattern 或 center）"))?;
+                profile.layout = Some(layout.parse().map_err(|e| format!("{}: {}", path, e))?);
+            }
+            "utc_offset" => {
+                let offset = value.as_str().ok_or_else(|| invalid("字符串（如 \"+08:00\"）"))?;
+                crate::parse_utc_offset(offset).map_err(|e| format!("{}: {}", path, e))?;
+                profile.utc_offset = Some(offset.to_string());
+            }
+            "precision" => match value.as_u64() {
+                Some(precision) if precision <= 10 => profile.precision = Some(precision as u8),
+                _ => return Err(invalid("介于 0 和 10 之间的整数")),
+            },
+            "compress" => {
+                profile.compress = Some(value.as_bool().ok_or_else(|| invalid("布尔值"))?);
+            }
+            _ => return Err(format!("{}: 未知的配置项", path)),
+        }
+    }
+    Ok(profile)
+}
+
+/// 以配置文件所在目录为基准解析字体路径（支持 `路径#序号` 形式）
+fn resolve_font(base: &Path, spec: &str) -> String {
+    if Path::new(spec).is_absolute() {
+        return spec.to_string();
+    }
+    let file = spec.rsplit_once('#').map_or(spec, |(path, _)| path);
+    if base.join(spec).is_file() || base.join(file).is_file() {
+        base.join(spec).to_string_lossy().into_owned()
+    } else {
+        spec.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml_error(content: &str) -> String {
+        ProfileConfig::parse(content, ConfigFormat::Toml).unwrap_err().to_string()
+    }
+
+    fn json_error(content: &str) -> String {
+        ProfileConfig::parse(content, ConfigFormat::Json).unwrap_err().to_string()
+    }
+
+    /// 单个键的 TOML 方案 `x` 的解析错误
+    fn key_error(entry: &str) -> String {
+        toml_error(&format!("[profile.x]\n{}\n", entry))
+    }
+
+    // ------------------------------------------------------------------
+    // 解析
+    // ------------------------------------------------------------------
+
+    const TOML: &str = r##"
+[profile.confidential]
+template = "致{name}-{date}:高度保密"
+font = ["STSongStd-Light-Acro.otf", "NotoSansCJK.ttc#2"]
+size = 18
+angle = 30.5
+opacity = 0.15
+color = "#FF0000"
+blend_mode = "multiply"
+pages = "1-3,5"
+layout = "pattern"
+utc_offset = "+08:00"
+precision = 2
+compress = false
+
+[profile.confidential.vars]
+name = "张三"
+id = 7
+vip = true
+
+[profile.draft]
+font = "Noto Sans CJK SC"
+"##;
+
+    const JSON: &str = r##"{"profile": {
+        "confidential": {
+            "template": "致{name}-{date}:高度保密",
+            "font": ["STSongStd-Light-Acro.otf", "NotoSansCJK.ttc#2"],
+            "size": 18, "angle": 30.5, "opacity": 0.15,
+            "color": "#FF0000", "blend_mode": "multiply",
+            "pages": "1-3,5", "layout": "pattern", "utc_offset": "+08:00",
+            "precision": 2, "compress": false,
+            "vars": {"name": "张三", "id": 7, "vip": true}
+        },
+        "draft": {"font": "Noto Sans CJK SC"}
+    }}"##;
+
+    #[test]
+    fn parses_same_profile_from_toml_and_json() {
+        let toml = ProfileConfig::parse(TOML, ConfigFormat::Toml).unwrap();
+        let json = ProfileConfig::parse(JSON, ConfigFormat::Json).unwrap();
+        for config in [&toml, &json] {
+            assert_eq!(config.names().collect::<Vec<_>>(), ["confidential", "draft"]);
+            let p = config.profile("confidential").unwrap();
+            assert_eq!(p.fonts, ["STSongStd-Light-Acro.otf", "NotoSansCJK.ttc#2"]);
+            assert_eq!(p.template.as_deref(), Some("致{name}-{date}:高度保密"));
+            assert_eq!(p.font_size, Some(18.0));
+            assert_eq!(p.angle, Some(30.5));
+            assert_eq!(p.opacity, Some(0.15));
+            assert_eq!(p.color, Some("#FF0000".parse().unwrap()));
+            assert_eq!(p.blend_mode, Some(BlendMode::Multiply));
+            assert_eq!(p.pages, Some("1-3,5".parse().unwrap()));
+            assert_eq!(p.layout, Some(GridMode::Pattern));
+            assert_eq!(p.utc_offset.as_deref(), Some("+08:00"));
+            assert_eq!(p.precision, Some(2));
+            assert_eq!(p.compress, Some(false));
+            let var = |key: &str| p.vars.get(key).map(String::as_str);
+            assert_eq!((var("name"), var("id"), var("vip")), (Some("张三"), Some("7"), Some("true")));
+            assert_eq!(p.vars.len(), 3);
+
+            let draft = config.profile("draft").unwrap();
+            assert_eq!(draft.fonts, ["Noto Sans CJK SC"]);
+            assert_eq!((draft.template.as_ref(), draft.opacity), (None, None));
+        }
+    }
+
+    #[test]
+    fn parses_page_number_and_bom() {
+        let config = ProfileConfig::parse("\u{feff}[profile.x]\npages = 3\n", ConfigFormat::Toml).unwrap();
+        assert_eq!(config.profile("x").unwrap().pages, Some("3".parse().unwrap()));
+        assert_eq!(ProfileConfig::parse("", ConfigFormat::Toml).unwrap().names().count(), 0);
+    }
+
+    // ------------------------------------------------------------------
+    // 校验错误指出出错的键
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn value_errors_name_the_key() {
+        assert_eq!(key_error("opacity = 1.5"), "profile.x.opacity: 应为介于 0 和 1 之间的数值，实际为 1.5");
+        assert_eq!(key_error("opacity = \"high\""), "profile.x.opacity: 应为介于 0 和 1 之间的数值，实际为 \"high\"");
+        assert_eq!(key_error("size = 0"), "profile.x.size: 应为大于 0 的数值，实际为 0");
+        assert_eq!(key_error("angle = true"), "profile.x.angle: 应为数值，实际为 true");
+        assert_eq!(key_error("template = 1"), "profile.x.template: 应为字符串，实际为 1");
+        assert_eq!(key_error("compress = 1"), "profile.x.compress: 应为布尔值，实际为 1");
+        assert_eq!(key_error("vars = 1"), "profile.x.vars: 应为表（变量名 = 值），实际为 1");
+        assert_eq!(key_error("vars = { k = [1] }"), "profile.x.vars.k: 应为字符串或数字，实际为 [1]");
+        assert_eq!(
+            json_error(r#"{"profile": {"x": {"vars": {"k": null}}}}"#),
+            "profile.x.vars.k: 应为字符串或数字，实际为 null"
+        );
+    }
+
+    #[test]
+    fn font_errors() {
+        assert_eq!(key_error("font = \"\""), "profile.x.font: 字体不能为空");
+        assert_eq!(key_error("font = []"), "profile.x.font: 字体不能为空");
+        assert_eq!(key_error("font = [\"a.ttf\", \" \"]"), "profile.x.font: 字体不能为空");
+        assert_eq!(key_error("font = [1]"), "profile.x.font: 应为字符串或字符串数组，实际为 [1]");
+        assert_eq!(key_error("font = 1"), "profile.x.font: 应为字符串或字符串数组，实际为 1");
+    }
+
+    #[test]
+    fn precision_errors() {
+        assert_eq!(key_error("precision = 11"), "profile.x.precision: 应为介于 0 和 10 之间的整数，实际为 11");
+        assert_eq!(key_error("precision = -1"), "profile.x.precision: 应为介于 0 和 10 之间的整数，实际为 -1");
+        assert_eq!(key_error("precision = 2.5"), "profile.x.precision: 应为介于 0 和 10 之间的整数，实际为 2.5");
+        let config = ProfileConfig::parse("[profile.x]\nprecision = 0\n", ConfigFormat::Toml).unwrap();
+        assert_eq!(config.profile("x").unwrap().precision, Some(0));
+    }
+
+    #[test]
+    fn parsed_value_errors_keep_the_parser_message() {
+        let color = "nope".parse::<WatermarkColor>().unwrap_err();
+        assert_eq!(key_error("color = \"nope\""), format!("profile.x.color: {}", color));
+        let mode = "nope".parse::<BlendMode>().unwrap_err();
+        assert_eq!(key_error("blend_mode = \"nope\""), format!("profile.x.blend_mode: {}", mode));
+        let pages = "3-1".parse::<PageSelection>().unwrap_err();
+        assert_eq!(key_error("pages = \"3-1\""), format!("profile.x.pages: {}", pages));
+        assert_eq!(
+            key_error("layout = \"grid\""),
+            "profile.x.layout: 未知的布局 'grid'，可选 tile、pattern、center"
+        );
+        let offset = crate::parse_utc_offset("+25:00").unwrap_err();
+        assert_eq!(key_error("utc_offset = \"+25:00\""), format!("profile.x.utc_offset: {}", offset));
+    }
+
+    #[test]
+    fn structure_errors() {
+        assert_eq!(key_error("sizee = 12"), "profile.x.sizee: 未知的配置项");
+        assert_eq!(
+            toml_error("title = \"a\"\n"),
+            "title: 未知的配置项（水印方案应写在 [profile.<名称>] 下）"
+        );
+        assert_eq!(toml_error("profile = 1\n"), "profile: 应为表（对象）");
+        assert_eq!(toml_error("[profile]\nx = 1\n"), "profile.x: 应为表（对象）");
+        assert_eq!(json_error("[1]"), "配置顶层应为表（对象）");
+        assert!(toml_error("[profile.x\n").starts_with("TOML 解析失败: "));
+        assert!(json_error("{").starts_with("JSON 解析失败: "));
+    }
+
+    // ------------------------------------------------------------------
+    // 方案查找、格式识别与字体路径
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn missing_profile_lists_available_names() {
+        let config = ProfileConfig::parse(TOML, ConfigFormat::Toml).unwrap();
+        assert_eq!(
+            config.profile("secret").unwrap_err().to_string(),
+            "配置中没有水印方案 'secret'（可用: confidential, draft）"
+        );
+        assert_eq!(
+            ProfileConfig::default().profile("secret").unwrap_err().to_string(),
+            "配置中没有水印方案 'secret'（配置中没有任何方案）"
+        );
+    }
+
+    #[test]
+    fn detects_format() {
+        let detect = |path: &str, content: &str| ConfigFormat::detect(Path::new(path), content);
+        assert_eq!(detect("a.TOML", "{"), ConfigFormat::Toml);
+        assert_eq!(detect("a.json", "[profile.x]"), ConfigFormat::Json);
+        assert_eq!(detect("config", "\u{feff} {\"profile\": {}}"), ConfigFormat::Json);
+        assert_eq!(detect("config", "[profile.x]"), ConfigFormat::Toml);
+    }
+
+    #[test]
+    fn resolves_fonts_relative_to_config() {
+        let base = std::env::temp_dir().join(format!("water_mark_profile_{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("font.ttc"), b"").unwrap();
+        let in_base = |spec: &str| base.join(spec).to_string_lossy().into_owned();
+
+        assert_eq!(resolve_font(&base, "font.ttc"), in_base("font.ttc"));
+        assert_eq!(resolve_font(&base, "font.ttc#1"), in_base("font.ttc#1"));
+        assert_eq!(resolve_font(&base, "font.ttc#Noto Sans CJK SC"), in_base("font.ttc#Noto Sans CJK SC"));
+        // 目录下不存在的文件按原样作为字体名称或路径
+        assert_eq!(resolve_font(&base, "missing.ttc#1"), "missing.ttc#1");
+        assert_eq!(resolve_font(&base, "Noto Sans CJK SC"), "Noto Sans CJK SC");
+        assert_eq!(resolve_font(&base, "/fonts/a.ttc#2"), "/fonts/a.ttc#2");
+
+        let config_path = base.join("water_mark.toml");
+        std::fs::write(&config_path, "[profile.x]\nfont = [\"font.ttc#1\", \"Noto Sans\"]\n").unwrap();
+        let config = ProfileConfig::load(&config_path.to_string_lossy()).unwrap();
+        assert_eq!(config.profile("x").unwrap().fonts, [in_base("font.ttc#1"), "Noto Sans".to_string()]);
+
+        std::fs::write(&config_path, "[profile.x]\nopacity = 2\n").unwrap();
+        let err = ProfileConfig::load(&config_path.to_string_lossy()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "配置 {}: profile.x.opacity: 应为介于 0 和 1 之间的数值，实际为 2",
+                config_path.display()
+            )
+        );
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,913 @@
+//! 文本塑形 - 将字符串转换为带位置的字形序列
+//!
+//! 排版与测量共用同一个塑形结果，保证 XObject 中的轮廓位置与实测宽度一致。
+//! 塑形前先按 Unicode 双向算法（UAX #9）把文本切分为视觉顺序的方向片段，
+//! 希伯来文、阿拉伯文与混排文本（如 `致 محمد-2026`）按阅读顺序显示。
+//!
+//! 竖排（[`WritingMode::Vertical`]）使用字体的竖排度量（vhea/vmtx/VORG）
+//! 与竖排字形（GSUB `vert`/`vrt2`，或 Unicode 竖排标点形式）。
+//! - [`TextShaping::Kerning`]：逐字符映射字形，并应用 GPOS `kern` 特性（或旧式 `kern` 表）的字偶距
+//! - [`TextShaping::Full`]：启用 `shaping` 特性时使用 rustybuzz 完整塑形
+//!   （连字、上下文变形、组合符号定位），适用于阿拉伯文、天城文、泰文等
+
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont};
+use std::borrow::Cow;
+use std::ops::Range;
+use unicode_bidi::BidiInfo;
+
+/// 文本塑形方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextShaping {
+    /// 不做字偶距与塑形：逐字符按水平 advance 排列（仍进行双向文本重排）
+    None,
+    /// 逐字符映射字形，并应用字偶距（不启用 `shaping` 特性时的默认值）
+    Kerning,
+    /// 完整的 OpenType 塑形（启用 `shaping` 特性时的默认值）
+    ///
+    /// 未启用 `shaping` 特性编译时等同于 [`TextShaping::Kerning`]。
+    Full,
+}
+
+impl Default for TextShaping {
+    fn default() -> Self {
+        if cfg!(feature = "shaping") {
+            TextShaping::Full
+        } else {
+            TextShaping::Kerning
+        }
+    }
+}
+
+/// 文字书写方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WritingMode {
+    /// 横排（默认）
+    #[default]
+    Horizontal,
+    /// 竖排：字符自上而下排列，标点使用竖排形式
+    ///
+    /// 每个字形以列中心线水平居中；非 CJK 字符（拉丁字母、数字及 ASCII 标点）保持直立，
+    /// 全角与 CJK 标点（以及 `…`、`—` 等）使用竖排形式。
+    /// 竖排不做双向重排与复杂塑形。
+    Vertical,
+}
+
+/// 已加载的字体（保留字面索引，供 OpenType 表解析使用）
+pub(crate) struct LoadedFont {
+    pub(crate) font: FontVec,
+    /// 字面在字体集合中的索引
+    pub(crate) index: u32,
+}
+
+impl LoadedFont {
+    pub(crate) fn new(font: FontVec, index: u32) -> Self {
+        Self { font, index }
+    }
+
+    /// 解析 OpenType 表（GPOS / kern），用于查询字偶距
+    fn kerning(&self) -> Option<Kerning<'_>> {
+        let face = ttf_parser::Face::parse(self.font.as_slice(), self.index).ok()?;
+        let kern_tag = ttf_parser::Tag::from_bytes(b"kern");
+        let mut lookups: Vec<u16> = face
+            .tables()
+            .gpos
+            .map(|gpos| {
+                gpos.features
+                    .into_iter()
+                    .filter(|f| f.tag == kern_tag)
+                    .flat_map(|f| f.lookup_indices)
+                    .collect()
+            })
+            .unwrap_or_default();
+        lookups.sort_unstable();
+        lookups.dedup();
+        Some(Kerning { face, lookups })
+    }
+}
+
+/// 单个字体的字偶距数据
+struct Kerning<'a> {
+    face: ttf_parser::Face<'a>,
+    /// GPOS 中 `kern` 特性引用的查找表索引
+    lookups: Vec<u16>,
+}
+
+/// 排版后的单个字形
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PlacedGlyph {
+    /// 字体在回退链中的槽位
+    pub(crate) font_slot: usize,
+    pub(crate) glyph_id: GlyphId,
+    /// 字形原点相对文本起点的偏移（点数）
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+}
+
+/// 塑形结果：定位后的字形序列与总 advance
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ShapedText {
+    pub(crate) glyphs: Vec<PlacedGlyph>,
+    /// 沿书写方向的总长度（点数）：横排为宽度，竖排为自原点向下的高度
+    pub(crate) advance: f32,
+    /// 是否为竖排
+    pub(crate) vertical: bool,
+}
+
+/// 在字体回退链中为字符选择字形
+///
+/// # 返回
+/// - `Some((槽位, 字形ID))`: 第一个包含该字符（非 .notdef）的字体
+/// - `None`: 所有字体都缺失该字符
+pub(crate) fn resolve_glyph(fonts: &[LoadedFont], c: char) -> Option<(usize, GlyphId)> {
+    fonts
+        .iter()
+        .enumerate()
+        .map(|(slot, loaded)| (slot, loaded.font.glyph_id(c)))
+        .find(|(_, glyph_id)| glyph_id.0 != 0)
+}
+
+/// 选择字形，所有字体都缺失时退回主字体的 .notdef
+fn resolve_glyph_or_notdef(fonts: &[LoadedFont], c: char) -> (usize, GlyphId) {
+    resolve_glyph(fonts, c).unwrap_or((0, GlyphId(0)))
+}
+
+/// 返回所有字体都无法渲染的字符（去重，按出现顺序）
+pub(crate) fn missing_chars(fonts: &[LoadedFont], text: &str) -> Vec<char> {
+    let mut missing = Vec::new();
+    for c in text.chars() {
+        if resolve_glyph(fonts, c).is_none() && !missing.contains(&c) {
+            missing.push(c);
+        }
+    }
+    missing
+}
+
+/// 对文本进行塑形，得到每个字形及其位置
+///
+/// # 参数
+/// - `fonts`: 字体回退链
+/// - `text`: 文本内容（逻辑顺序）
+/// - `size`: 字体大小（点数）
+/// - `shaping`: 塑形方式（仅横排有效）
+/// - `mode`: 书写方向
+///
+/// # 说明
+/// 横排时按视觉顺序依次塑形每个方向片段并从左到右拼接；
+/// 竖排时原点位于第一个字的顶部中点，字形沿 -y 方向排列。
+pub(crate) fn shape_text(
+    fonts: &[LoadedFont],
+    text: &str,
+    size: f32,
+    shaping: TextShaping,
+    mode: WritingMode,
+) -> ShapedText {
+    if mode == WritingMode::Vertical {
+        return shape_vertical(fonts, text, size);
+    }
+    let mut shaped = ShapedText::default();
+    for (range, rtl) in visual_runs(text) {
+        let run = &text[range];
+        let run_shaped = match shaping {
+            TextShaping::None => shape_simple(fonts, &simple_visual_order(run, rtl), size, false),
+            #[cfg(feature = "shaping")]
+            TextShaping::Full => shape_full(fonts, run, size, rtl),
+            _ => shape_simple(fonts, &simple_visual_order(run, rtl), size, true),
+        };
+        append_shaped(&mut shaped, run_shaped);
+    }
+    shaped
+}
+
+/// 按 Unicode 双向算法将文本切分为方向片段
+///
+/// # 返回
+/// 按视觉顺序（从左到右）排列的 (字节范围, 是否从右到左) 列表；
+/// 段落方向由第一个强方向字符决定。
+fn visual_runs(text: &str) -> Vec<(Range<usize>, bool)> {
+    let bidi = BidiInfo::new(text, None);
+    if !bidi.has_rtl() {
+        return vec![(0..text.len(), false)];
+    }
+    let mut runs = Vec::new();
+    for para in &bidi.paragraphs {
+        let (levels, ranges) = bidi.visual_runs(para, para.range.clone());
+        for range in ranges {
+            let rtl = levels[range.start].is_rtl();
+            runs.push((range, rtl));
+        }
+    }
+    runs
+}
+
+/// 逐字符排版时，把从右到左片段转换为视觉顺序
+///
+/// # 说明
+/// 以字素簇（基础字符 + 后随的组合符号）为单位倒序，保持符号附着在基础字符上；
+/// 成对的括号等镜像字符替换为其镜像形式。
+fn simple_visual_order(run: &str, rtl: bool) -> Cow<'_, str> {
+    if !rtl {
+        return Cow::Borrowed(run);
+    }
+    let mut clusters: Vec<String> = Vec::new();
+    for c in run.chars() {
+        match clusters.last_mut() {
+            Some(cluster) if is_cluster_continuation(c) => cluster.push(c),
+            _ => clusters.push(mirror_char(c).to_string()),
+        }
+    }
+    clusters.reverse();
+    Cow::Owned(clusters.concat())
+}
+
+/// 双向文本中成对镜像字符的镜像形式（Bidi_Mirroring_Glyph 的常用子集）
+fn mirror_char(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        '‹' => '›',
+        '›' => '‹',
+        '（' => '）',
+        '）' => '（',
+        '【' => '】',
+        '】' => '【',
+        '《' => '》',
+        '》' => '《',
+        '〈' => '〉',
+        '〉' => '〈',
+        '「' => '」',
+        '」' => '「',
+        '『' => '』',
+        '』' => '『',
+        _ => c,
+    }
+}
+
+/// 逐字符排版：在回退链中选择字形，按 advance 排列，可选应用字偶距
+fn shape_simple(fonts: &[LoadedFont], text: &str, size: f32, kerning: bool) -> ShapedText {
+    // 字偶距表按需解析，每个字体槽位只解析一次
+    let mut kerning_tables: Vec<Option<Option<Kerning<'_>>>> = Vec::new();
+    kerning_tables.resize_with(fonts.len(), || None);
+
+    let mut glyphs: Vec<PlacedGlyph> = Vec::with_capacity(text.len());
+    let mut x_cursor = 0.0;
+    for c in text.chars() {
+        let (font_slot, glyph_id) = resolve_glyph_or_notdef(fonts, c);
+        let scaled = fonts[font_slot].font.as_scaled(PxScale::from(size));
+
+        // 与前一个字形同字体时，应用二者之间的字偶距
+        if kerning
+            && let Some(prev) = glyphs.last()
+            && prev.font_slot == font_slot
+        {
+            let table = kerning_tables[font_slot].get_or_insert_with(|| fonts[font_slot].kerning());
+            if let Some(table) = table {
+                x_cursor += pair_kerning(table, prev.glyph_id, glyph_id) * scaled.h_scale_factor();
+            }
+        }
+
+        glyphs.push(PlacedGlyph {
+            font_slot,
+            glyph_id,
+            x: x_cursor,
+            y: 0.0,
+        });
+        x_cursor += scaled.h_advance(glyph_id);
+    }
+
+    ShapedText {
+        glyphs,
+        advance: x_cursor,
+        vertical: false,
+    }
+}
+
+/// 竖排排版
+///
+/// # 说明
+/// - 字形替换：优先使用字体 GSUB 中 `vert`/`vrt2` 特性的单字替换，
+///   字体没有该特性时使用 Unicode 竖排标点形式（U+FE10–FE19、U+FE30–FE4F）
+/// - 竖排 advance 取自 vmtx，缺失时为 ascender - descender
+/// - 竖排原点（字形顶部）取自 VORG；否则为字形包围盒顶部 + vmtx 上边距；再否则为 ascender
+fn shape_vertical(fonts: &[LoadedFont], text: &str, size: f32) -> ShapedText {
+    let mut tables: Vec<Option<Option<VerticalTables<'_>>>> = Vec::new();
+    tables.resize_with(fonts.len(), || None);
+
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut y_cursor = 0.0;
+    for c in text.chars() {
+        let (font_slot, glyph_id) = resolve_glyph_or_notdef(fonts, c);
+        let loaded = &fonts[font_slot];
+        let scaled = loaded.font.as_scaled(PxScale::from(size));
+        let table = tables[font_slot].get_or_insert_with(|| VerticalTables::new(loaded));
+
+        let glyph_id = match table {
+            Some(t) if !t.lookups.is_empty() => t.substitute(glyph_id),
+            // 字体没有 GSUB 竖排特性：使用字体中存在的 Unicode 竖排标点形式
+            _ => vertical_form(c)
+                .map(|form| loaded.font.glyph_id(form))
+                .filter(|id| id.0 != 0)
+                .unwrap_or(glyph_id),
+        };
+        let (advance, origin_y) = match table {
+            Some(t) => t.metrics(glyph_id),
+            None => {
+                let f = &loaded.font;
+                (f.ascent_unscaled() - f.descent_unscaled(), f.ascent_unscaled())
+            }
+        };
+
+        glyphs.push(PlacedGlyph {
+            font_slot,
+            glyph_id,
+            x: -scaled.h_advance(glyph_id) / 2.0,
+            y: y_cursor - origin_y * scaled.v_scale_factor(),
+        });
+        y_cursor -= advance * scaled.v_scale_factor();
+    }
+
+    ShapedText {
+        glyphs,
+        advance: -y_cursor,
+        vertical: true,
+    }
+}
+
+/// 单个字体的竖排数据（GSUB 竖排替换与竖排度量）
+struct VerticalTables<'a> {
+    face: ttf_parser::Face<'a>,
+    /// GSUB 中 `vert`/`vrt2` 特性引用的查找表索引
+    lookups: Vec<u16>,
+}
+
+impl<'a> VerticalTables<'a> {
+    fn new(loaded: &'a LoadedFont) -> Option<Self> {
+        let face = ttf_parser::Face::parse(loaded.font.as_slice(), loaded.index).ok()?;
+        let tags = [
+            ttf_parser::Tag::from_bytes(b"vert"),
+            ttf_parser::Tag::from_bytes(b"vrt2"),
+        ];
+        let mut lookups: Vec<u16> = face
+            .tables()
+            .gsub
+            .map(|gsub| {
+                gsub.features
+                    .into_iter()
+                    .filter(|f| tags.contains(&f.tag))
+                    .flat_map(|f| f.lookup_indices)
+                    .collect()
+            })
+            .unwrap_or_default();
+        lookups.sort_unstable();
+        lookups.dedup();
+        Some(Self { face, lookups })
+    }
+
+    /// 应用竖排单字替换（未覆盖的字形保持不变）
+    fn substitute(&self, glyph_id: GlyphId) -> GlyphId {
+        use ttf_parser::gsub::{SingleSubstitution, SubstitutionSubtable};
+
+        let Some(gsub) = self.face.tables().gsub else {
+            return glyph_id;
+        };
+        let glyph = ttf_parser::GlyphId(glyph_id.0);
+        for &lookup_index in &self.lookups {
+            let Some(lookup) = gsub.lookups.get(lookup_index) else {
+                continue;
+            };
+            for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+                let SubstitutionSubtable::Single(single) = subtable else {
+                    continue;
+                };
+                let Some(coverage_index) = single.coverage().get(glyph) else {
+                    continue;
+                };
+                let substituted = match single {
+                    SingleSubstitution::Format1 { delta, .. } => {
+                        Some(glyph.0.wrapping_add(delta as u16))
+                    }
+                    SingleSubstitution::Format2 { substitutes, .. } => {
+                        substitutes.get(coverage_index).map(|g| g.0)
+                    }
+                };
+                if let Some(id) = substituted {
+                    return GlyphId(id);
+                }
+            }
+        }
+        glyph_id
+    }
+
+    /// 竖排度量：(竖排 advance, 竖排原点相对基线的高度)，单位为字体设计单位
+    fn metrics(&self, glyph_id: GlyphId) -> (f32, f32) {
+        let face = &self.face;
+        let glyph = ttf_parser::GlyphId(glyph_id.0);
+        let ascender = f32::from(face.ascender());
+        let advance = face
+            .glyph_ver_advance(glyph)
+            .map(f32::from)
+            .unwrap_or_else(|| ascender - f32::from(face.descender()));
+        let origin_y = face
+            .glyph_y_origin(glyph)
+            .map(f32::from)
+            .or_else(|| {
+                let tsb = face.glyph_ver_side_bearing(glyph)?;
+                let bbox = face.glyph_bounding_box(glyph)?;
+                Some(f32::from(bbox.y_max) + f32::from(tsb))
+            })
+            .unwrap_or(ascender);
+        (advance, origin_y)
+    }
+}
+
+/// 标点的 Unicode 竖排形式（CJK Compatibility Forms / Vertical Forms）
+///
+/// # 说明
+/// 只转换全角与 CJK 标点（以及 CJK 文本中常用的 `…`、`‥`、`—`、`–`）；
+/// ASCII 括号与下划线属于拉丁文字，与其他非 CJK 字符一样保持直立。
+fn vertical_form(c: char) -> Option<char> {
+    Some(match c {
+        '，' => '︐',
+        '、' => '︑',
+        '。' => '︒',
+        '：' => '︓',
+        '；' => '︔',
+        '！' => '︕',
+        '？' => '︖',
+        '〖' => '︗',
+        '〗' => '︘',
+        '…' => '︙',
+        '‥' => '︰',
+        '—' => '︱',
+        '–' => '︲',
+        '＿' => '︳',
+        '（' => '︵',
+        '）' => '︶',
+        '｛' => '︷',
+        '｝' => '︸',
+        '〔' => '︹',
+        '〕' => '︺',
+        '【' => '︻',
+        '】' => '︼',
+        '《' => '︽',
+        '》' => '︾',
+        '〈' => '︿',
+        '〉' => '﹀',
+        '「' => '﹁',
+        '」' => '﹂',
+        '『' => '﹃',
+        '』' => '﹄',
+        '［' => '﹇',
+        '］' => '﹈',
+        _ => return None,
+    })
+}
+
+/// 查询一对字形之间的水平字偶距（字体设计单位）
+///
+/// # 说明
+/// 字体有 GPOS `kern` 特性时只使用 GPOS：各查找表的结果累加，
+/// 同一查找表内第一个覆盖该字形对的子表生效，没有覆盖的字形对不做调整。
+/// 只有 GPOS 没有 `kern` 特性（许多 CJK 字体与旧式拉丁字体的 GPOS 只用于附加符号定位）时
+/// 才使用旧式 `kern` 表，两种来源不会混用。
+fn pair_kerning(kerning: &Kerning, first: GlyphId, second: GlyphId) -> f32 {
+    let first = ttf_parser::GlyphId(first.0);
+    let second = ttf_parser::GlyphId(second.0);
+    let value = if kerning.lookups.is_empty() {
+        legacy_pair_kerning(&kerning.face, first, second)
+    } else {
+        gpos_pair_kerning(kerning, first, second)
+    };
+    value.unwrap_or(0.0)
+}
+
+/// 在 GPOS `kern` 查找表中查询字形对；没有任何查找表覆盖该字形对时返回 `None`
+fn gpos_pair_kerning(
+    kerning: &Kerning,
+    first: ttf_parser::GlyphId,
+    second: ttf_parser::GlyphId,
+) -> Option<f32> {
+    use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+
+    let gpos = kerning.face.tables().gpos?;
+    let mut total = None;
+    for &lookup_index in &kerning.lookups {
+        let Some(lookup) = gpos.lookups.get(lookup_index) else {
+            continue;
+        };
+        for subtable in lookup.subtables.into_iter::<PositioningSubtable>() {
+            let PositioningSubtable::Pair(pair) = subtable else {
+                continue;
+            };
+            let Some(coverage_index) = pair.coverage().get(first) else {
+                continue;
+            };
+            let records = match pair {
+                PairAdjustment::Format1 { sets, .. } => {
+                    sets.get(coverage_index).and_then(|set| set.get(second))
+                }
+                PairAdjustment::Format2 { classes, matrix, .. } => {
+                    matrix.get((classes.0.get(first), classes.1.get(second)))
+                }
+            };
+            if let Some((first_value, _)) = records {
+                *total.get_or_insert(0.0) += f32::from(first_value.x_advance);
+                break;
+            }
+        }
+    }
+    total
+}
+
+/// 在旧式 `kern` 表的水平子表中查询字形对
+fn legacy_pair_kerning(
+    face: &ttf_parser::Face,
+    first: ttf_parser::GlyphId,
+    second: ttf_parser::GlyphId,
+) -> Option<f32> {
+    face.tables().kern.and_then(|kern| {
+        kern.subtables
+            .into_iter()
+            .filter(|st| st.horizontal && !st.variable)
+            .find_map(|st| st.glyphs_kerning(first, second))
+            .map(f32::from)
+    })
+}
+
+/// 使用 rustybuzz 完整塑形
+///
+/// # 说明
+/// 先按回退链把文本切分为同字体的连续片段，再对每个片段单独塑形并依次拼接。
+/// 书写方向由双向算法的片段方向给出（从右到左片段的字体片段按视觉顺序倒序处理），
+/// 文种与语言由 rustybuzz 根据字符自动推断。
+#[cfg(feature = "shaping")]
+fn shape_full(fonts: &[LoadedFont], text: &str, size: f32, rtl: bool) -> ShapedText {
+    let mut shaped = ShapedText::default();
+    let mut runs = font_runs(fonts, text);
+    if rtl {
+        runs.reverse();
+    }
+    for (font_slot, run) in runs {
+        let loaded = &fonts[font_slot];
+        let scaled = loaded.font.as_scaled(PxScale::from(size));
+        let Some(face) = rustybuzz::Face::from_slice(loaded.font.as_slice(), loaded.index) else {
+            // 理论上不会发生（字体已由 ab_glyph 成功解析），退回逐字符排版
+            let simple = shape_simple(fonts, &simple_visual_order(run, rtl), size, true);
+            append_shaped(&mut shaped, simple);
+            continue;
+        };
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(run);
+        buffer.set_direction(if rtl {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        let (h_factor, v_factor) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+        let mut x_cursor = 0.0;
+        let mut y_cursor = 0.0;
+        for (info, pos) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+            shaped.glyphs.push(PlacedGlyph {
+                font_slot,
+                glyph_id: GlyphId(info.glyph_id as u16),
+                x: shaped.advance + x_cursor + pos.x_offset as f32 * h_factor,
+                y: y_cursor + pos.y_offset as f32 * v_factor,
+            });
+            x_cursor += pos.x_advance as f32 * h_factor;
+            y_cursor += pos.y_advance as f32 * v_factor;
+        }
+        shaped.advance += x_cursor;
+    }
+    shaped
+}
+
+/// 将片段的塑形结果拼接到已有结果之后
+fn append_shaped(shaped: &mut ShapedText, run: ShapedText) {
+    let offset = shaped.advance;
+    shaped
+        .glyphs
+        .extend(run.glyphs.into_iter().map(|g| PlacedGlyph { x: g.x + offset, ..g }));
+    shaped.advance += run.advance;
+}
+
+/// 按回退链将文本切分为同字体的连续片段
+///
+/// # 说明
+/// 组合符号（如变音符、元音符号）与零宽连接符跟随前一个字符的字体，
+/// 避免同一字素簇被拆到不同字体中而无法正确定位。
+#[cfg(feature = "shaping")]
+fn font_runs<'t>(fonts: &[LoadedFont], text: &'t str) -> Vec<(usize, &'t str)> {
+    let mut runs: Vec<(usize, &'t str)> = Vec::new();
+    let mut run_start = 0;
+    let mut run_slot: Option<usize> = None;
+    for (i, c) in text.char_indices() {
+        let slot = match run_slot {
+            Some(current)
+                if is_cluster_continuation(c)
+                    && (fonts[current].font.glyph_id(c).0 != 0 || resolve_glyph(fonts, c).is_none()) =>
+            {
+                current
+            }
+            _ => resolve_glyph_or_notdef(fonts, c).0,
+        };
+        match run_slot {
+            Some(current) if current == slot => {}
+            Some(current) => {
+                runs.push((current, &text[run_start..i]));
+                run_start = i;
+                run_slot = Some(slot);
+            }
+            None => run_slot = Some(slot),
+        }
+    }
+    if let Some(slot) = run_slot {
+        runs.push((slot, &text[run_start..]));
+    }
+    runs
+}
+
+/// 是否为应与前一个字符同簇处理的字符（组合符号、连接控制符、变体选择符）
+fn is_cluster_continuation(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F // 组合变音符
+            | 0x0483..=0x0489
+            | 0x0591..=0x05C7 // 希伯来文点符
+            | 0x0610..=0x061A // 阿拉伯文符号
+            | 0x064B..=0x065F
+            | 0x0670
+            | 0x06D6..=0x06ED
+            | 0x0900..=0x0903 // 天城文元音符号等
+            | 0x093A..=0x094F
+            | 0x0951..=0x0957
+            | 0x0962..=0x0963
+            | 0x0E31 // 泰文元音与声调符号
+            | 0x0E34..=0x0E3A
+            | 0x0E47..=0x0E4E
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200C..=0x200D // ZWNJ / ZWJ
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F // 变体选择符
+            | 0xFE20..=0xFE2F
+            | 0xE0100..=0xE01EF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_font::{PairSubtable, TestFont};
+
+    // ------------------------------------------------------------------
+    // 双向文本
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn visual_runs_left_to_right_only() {
+        assert_eq!(visual_runs("abc"), [(0..3, false)]);
+        assert_eq!(visual_runs("致张三-2026"), [(0.."致张三-2026".len(), false)]);
+    }
+
+    #[test]
+    fn visual_runs_embedded_rtl() {
+        // 希伯来字母每个 2 字节；两侧的空格随段落方向（从左到右）
+        assert_eq!(
+            visual_runs("abc שלום def"),
+            [(0..4, false), (4..12, true), (12..16, false)]
+        );
+        // 括号包围从右到左文本时归入从右到左片段（稍后镜像）
+        assert_eq!(
+            visual_runs("abc (שלום) def"),
+            [(0..5, false), (5..13, true), (13..18, false)]
+        );
+    }
+
+    #[test]
+    fn visual_runs_rtl_paragraph() {
+        // 段落方向由第一个强方向字符决定：从右到左段落中拉丁文字显示在左侧
+        assert_eq!(visual_runs("שלום abc"), [(9..12, false), (0..9, true)]);
+        // 末尾的中性标点随段落方向，显示在最左侧；数字保持从左到右
+        assert_eq!(
+            visual_runs("שלום 123!"),
+            [(12..13, true), (9..12, false), (0..9, true)]
+        );
+        assert_eq!(visual_runs("مرحبا"), [(0..10, true)]);
+    }
+
+    #[test]
+    fn simple_visual_order_keeps_ltr() {
+        assert!(matches!(simple_visual_order("(abc)", false), Cow::Borrowed("(abc)")));
+    }
+
+    #[test]
+    fn simple_visual_order_reverses_and_mirrors() {
+        assert_eq!(simple_visual_order("אבג", true), "גבא");
+        assert_eq!(simple_visual_order("(אב)", true), "(בא)");
+        assert_eq!(simple_visual_order("«א» [ב] {ג} <ד>", true), "<ד> {ג} [ב] «א»");
+        assert_eq!(simple_visual_order("（א）【ב】《ג》", true), "《ג》【ב】（א）");
+    }
+
+    #[test]
+    fn simple_visual_order_keeps_marks_with_base() {
+        // שָׁלוֹם：点符（U+05B8、U+05C1、U+05B9）随基础字符一起移动
+        assert_eq!(
+            simple_visual_order("ש\u{5B8}\u{5C1}לו\u{5B9}ם", true),
+            "םו\u{5B9}לש\u{5B8}\u{5C1}"
+        );
+        // 阿拉伯文：فَتْح
+        assert_eq!(
+            simple_visual_order("ف\u{64E}ت\u{652}ح", true),
+            "حت\u{652}ف\u{64E}"
+        );
+        // 片段开头的孤立组合符号自成一簇
+        assert_eq!(simple_visual_order("\u{5B8}א", true), "א\u{5B8}");
+    }
+
+    #[test]
+    fn mirror_char_pairs_are_symmetric() {
+        for c in "()[]{}<>«»‹›（）【】《》〈〉「」『』".chars() {
+            assert_ne!(mirror_char(c), c, "{}", c);
+            assert_eq!(mirror_char(mirror_char(c)), c, "{}", c);
+        }
+        assert_eq!(mirror_char('a'), 'a');
+        assert_eq!(mirror_char('-'), '-');
+    }
+
+    // ------------------------------------------------------------------
+    // 竖排标点
+    // ------------------------------------------------------------------
+
+    #[test]
+    fn vertical_form_converts_cjk_punctuation() {
+        assert_eq!(vertical_form('，'), Some('︐'));
+        assert_eq!(vertical_form('。'), Some('︒'));
+        assert_eq!(vertical_form('（'), Some('︵'));
+        assert_eq!(vertical_form('］'), Some('﹈'));
+        assert_eq!(vertical_form('＿'), Some('︳'));
+        assert_eq!(vertical_form('「'), Some('﹁'));
+        assert_eq!(vertical_form('…'), Some('︙'));
+        assert_eq!(vertical_form('—'), Some('︱'));
+    }
+
+    #[test]
+    fn vertical_form_keeps_ascii_upright() {
+        for c in "()[]{}_-.,:;!?<>\"'aZ0".chars() {
+            assert_eq!(vertical_form(c), None, "{:?}", c);
+        }
+        assert_eq!(vertical_form('张'), None);
+    }
+
+    // ------------------------------------------------------------------
+    // 字偶距
+    // ------------------------------------------------------------------
+
+    fn load(font: &TestFont) -> LoadedFont {
+        LoadedFont::new(FontVec::try_from_vec(font.build()).unwrap(), 0)
+    }
+
+    fn glyph_pair(font: &TestFont, pair: &str) -> (ttf_parser::GlyphId, ttf_parser::GlyphId) {
+        let mut chars = pair.chars().map(|c| ttf_parser::GlyphId(font.glyph(c)));
+        (chars.next().unwrap(), chars.next().unwrap())
+    }
+
+    fn gpos_value(font: &TestFont, pair: &str) -> Option<f32> {
+        let loaded = load(font);
+        let kerning = loaded.kerning().unwrap();
+        let (first, second) = glyph_pair(font, pair);
+        gpos_pair_kerning(&kerning, first, second)
+    }
+
+    fn pair_value(font: &TestFont, pair: &str) -> f32 {
+        let loaded = load(font);
+        let kerning = loaded.kerning().unwrap();
+        let (first, second) = glyph_pair(font, pair);
+        pair_kerning(&kerning, GlyphId(first.0), GlyphId(second.0))
+    }
+
+    #[test]
+    fn gpos_kerning_reads_pair_sets() {
+        let font = TestFont::new("AVTo")
+            .feature(b"kern", &[0])
+            .lookup(vec![PairSubtable::Pairs(vec![('A', 'V', -80), ('T', 'o', -60), ('T', 'A', 0)])]);
+        assert_eq!(gpos_value(&font, "AV"), Some(-80.0));
+        assert_eq!(gpos_value(&font, "To"), Some(-60.0));
+        // 显式列出的 0 与未覆盖的字形对不同
+        assert_eq!(gpos_value(&font, "TA"), Some(0.0));
+        assert_eq!(gpos_value(&font, "VA"), None);
+        assert_eq!(gpos_value(&font, "AT"), None);
+    }
+
+    #[test]
+    fn gpos_kerning_reads_class_matrix() {
+        let font = TestFont::new("AVTo").feature(b"kern", &[0]).lookup(vec![PairSubtable::Classes {
+            first: vec![vec!['A', 'T']],
+            second: vec![vec!['V'], vec!['o']],
+            values: vec![(1, 1, -50), (1, 2, -30)],
+        }]);
+        assert_eq!(gpos_value(&font, "AV"), Some(-50.0));
+        assert_eq!(gpos_value(&font, "TV"), Some(-50.0));
+        assert_eq!(gpos_value(&font, "To"), Some(-30.0));
+        // 覆盖的前字形与类 0 的后字形：矩阵中的值为 0
+        assert_eq!(gpos_value(&font, "AT"), Some(0.0));
+        assert_eq!(gpos_value(&font, "VA"), None);
+    }
+
+    #[test]
+    fn gpos_kerning_sums_lookups_and_stops_at_first_subtable() {
+        let font = TestFont::new("AVTo")
+            .feature(b"kern", &[0, 1])
+            .feature(b"mark", &[2])
+            .lookup(vec![
+                PairSubtable::Pairs(vec![('A', 'V', -80)]),
+                PairSubtable::Pairs(vec![('A', 'V', -999), ('T', 'o', -40)]),
+            ])
+            .lookup(vec![PairSubtable::Classes {
+                first: vec![vec!['A']],
+                second: vec![vec!['V']],
+                values: vec![(1, 1, -20)],
+            }])
+            .lookup(vec![PairSubtable::Pairs(vec![('A', 'V', -500), ('V', 'A', -500)])]);
+        assert_eq!(gpos_value(&font, "AV"), Some(-100.0));
+        assert_eq!(gpos_value(&font, "To"), Some(-40.0));
+        // 只被非 kern 特性引用的查找表不参与
+        assert_eq!(gpos_value(&font, "VA"), None);
+    }
+
+    #[test]
+    fn legacy_kerning_reads_kern_table() {
+        let font = TestFont::new("AVTo").kern(&[('A', 'V', -70), ('T', 'o', -40)]);
+        let loaded = load(&font);
+        let face = ttf_parser::Face::parse(loaded.font.as_slice(), 0).unwrap();
+        let value = |pair: &str| {
+            let (first, second) = glyph_pair(&font, pair);
+            legacy_pair_kerning(&face, first, second)
+        };
+        assert_eq!(value("AV"), Some(-70.0));
+        assert_eq!(value("To"), Some(-40.0));
+        assert_eq!(value("VA"), None);
+
+        let plain = TestFont::new("AV");
+        let loaded = load(&plain);
+        let face = ttf_parser::Face::parse(loaded.font.as_slice(), 0).unwrap();
+        let (first, second) = glyph_pair(&plain, "AV");
+        assert_eq!(legacy_pair_kerning(&face, first, second), None);
+    }
+
+    #[test]
+    fn legacy_kerning_used_only_without_gpos_kern_feature() {
+        let legacy = [('A', 'V', -70), ('T', 'o', -40)];
+
+        // 没有 GPOS
+        let font = TestFont::new("AVTo").kern(&legacy);
+        assert_eq!(pair_value(&font, "AV"), -70.0);
+
+        // GPOS 只有非 kern 特性
+        let font = TestFont::new("AVTo")
+            .feature(b"mark", &[0])
+            .lookup(vec![PairSubtable::Pairs(vec![('A', 'V', -500)])])
+            .kern(&legacy);
+        assert_eq!(pair_value(&font, "AV"), -70.0);
+        assert_eq!(pair_value(&font, "VA"), 0.0);
+
+        // 有 GPOS kern：GPOS 的结果（或 0）为最终值，不回退到 kern 表
+        let font = TestFont::new("AVTo")
+            .feature(b"kern", &[0])
+            .lookup(vec![PairSubtable::Pairs(vec![('A', 'V', -80)])])
+            .kern(&legacy);
+        assert_eq!(pair_value(&font, "AV"), -80.0);
+        assert_eq!(pair_value(&font, "To"), 0.0);
+    }
+
+    #[test]
+    fn shape_text_applies_kerning() {
+        let font = TestFont::new("AVo")
+            .feature(b"kern", &[0])
+            .lookup(vec![PairSubtable::Pairs(vec![('A', 'V', -80)])]);
+        let fonts = [load(&font)];
+        let shape = |text: &str, shaping: TextShaping| {
+            shape_text(&fonts, text, 10.0, shaping, WritingMode::Horizontal)
+        };
+        // 1000 单位/em，advance 600：10pt 时每个字形 6pt，字偶距 -80 为 -0.8pt
+        let plain = shape("AV", TextShaping::None);
+        let kerned = shape("AV", TextShaping::Kerning);
+        assert!((plain.advance - 12.0).abs() < 1e-4, "{}", plain.advance);
+        assert!((kerned.advance - 11.2).abs() < 1e-4, "{}", kerned.advance);
+        assert!(kerned.advance < plain.advance);
+        assert!((kerned.glyphs[1].x - 5.2).abs() < 1e-4, "{}", kerned.glyphs[1].x);
+        // 没有字偶距的字形对宽度不变
+        assert!((shape("Ao", TextShaping::Kerning).advance - 12.0).abs() < 1e-4);
+        assert!((shape("VA", TextShaping::Kerning).advance - 12.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,501 @@
+//! 测试用字体 - 在内存中生成最小的 TrueType 字体
+//!
+//! 单元测试不依赖系统中安装的字体：每个字符对应一个矩形轮廓的字形（字形 0 为空的 .notdef），
+//! 可选附带 GPOS `kern` 成对调整查找表与旧式 `kern` 表，也可以把多个字体打包为字体集合。
+//! 字体设计单位为 1000/em，所有字形 advance 为 [`ADVANCE`]。
+
+/// 每个字形的水平 advance（字体设计单位）
+pub(crate) const ADVANCE: u16 = 600;
+
+/// GPOS 成对调整子表
+pub(crate) enum PairSubtable {
+    /// Format 1：逐对列出 (前字符, 后字符, x advance 调整)
+    Pairs(Vec<(char, char, i16)>),
+    /// Format 2：字形类矩阵
+    ///
+    /// `first`/`second` 依次为类 1、2……的字符（其余字形为类 0），
+    /// `values` 为 (前字形类, 后字形类, x advance 调整)，未列出的组合为 0。
+    /// 覆盖表为 `first` 中的所有字符。
+    Classes {
+        first: Vec<Vec<char>>,
+        second: Vec<Vec<char>>,
+        values: Vec<(u16, u16, i16)>,
+    },
+}
+
+/// 测试字体构造器
+pub(crate) struct TestFont {
+    family: String,
+    style: String,
+    /// 字符按顺序映射到字形 1、2……
+    chars: Vec<char>,
+    /// GPOS 特性：(标签, 引用的查找表索引)
+    features: Vec<([u8; 4], Vec<u16>)>,
+    /// GPOS 查找表（每个查找表由若干成对调整子表组成）
+    lookups: Vec<Vec<PairSubtable>>,
+    /// 旧式 `kern` 表的字形对
+    kern: Vec<(char, char, i16)>,
+}
+
+impl TestFont {
+    /// 包含给定字符的字体（族名 `Test Sans`，样式 `Regular`）
+    pub(crate) fn new(chars: &str) -> Self {
+        let mut unique: Vec<char> = Vec::new();
+        for c in chars.chars() {
+            if !unique.contains(&c) {
+                unique.push(c);
+            }
+        }
+        Self {
+            family: "Test Sans".to_string(),
+            style: "Regular".to_string(),
+            chars: unique,
+            features: Vec::new(),
+            lookups: Vec::new(),
+            kern: Vec::new(),
+        }
+    }
+
+    /// 设置 `name` 表中的族名与样式名
+    pub(crate) fn named(mut self, family: &str, style: &str) -> Self {
+        self.family = family.to_string();
+        self.style = style.to_string();
+        self
+    }
+
+    /// 添加 GPOS 特性，引用给定索引的查找表
+    pub(crate) fn feature(mut self, tag: &[u8; 4], lookups: &[u16]) -> Self {
+        self.features.push((*tag, lookups.to_vec()));
+        self
+    }
+
+    /// 添加 GPOS 成对调整查找表（索引按添加顺序从 0 开始）
+    pub(crate) fn lookup(mut self, subtables: Vec<PairSubtable>) -> Self {
+        self.lookups.push(subtables);
+        self
+    }
+
+    /// 设置旧式 `kern` 表的字形对
+    pub(crate) fn kern(mut self, pairs: &[(char, char, i16)]) -> Self {
+        self.kern = pairs.to_vec();
+        self
+    }
+
+    /// 字符对应的字形 ID（未包含的字符为 0）
+    pub(crate) fn glyph(&self, c: char) -> u16 {
+        self.chars
+            .iter()
+            .position(|&x| x == c)
+            .map_or(0, |i| i as u16 + 1)
+    }
+
+    /// 生成字体文件数据
+    pub(crate) fn build(&self) -> Vec<u8> {
+        write_fonts(&[self.tables()], false)
+    }
+
+    fn tables(&self) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut tables = vec![
+            (*b"cmap", self.cmap()),
+            (*b"head", self.head()),
+            (*b"hhea", self.hhea()),
+            (*b"hmtx", self.hmtx()),
+            (*b"maxp", self.maxp()),
+            (*b"name", self.name()),
+        ];
+        let (glyf, loca) = self.glyf_loca();
+        tables.push((*b"glyf", glyf));
+        tables.push((*b"loca", loca));
+        if !self.features.is_empty() || !self.lookups.is_empty() {
+            tables.push((*b"GPOS", self.gpos()));
+        }
+        if !self.kern.is_empty() {
+            tables.push((*b"kern", self.kern_table()));
+        }
+        tables.sort_by_key(|(tag, _)| *tag);
+        tables
+    }
+
+    fn num_glyphs(&self) -> u16 {
+        self.chars.len() as u16 + 1
+    }
+
+    fn head(&self) -> Vec<u8> {
+        let mut t = Vec::new();
+        put32(&mut t, 0x0001_0000); // version
+        put32(&mut t, 0x0001_0000); // fontRevision
+        put32(&mut t, 0); // checksumAdjustment
+        put32(&mut t, 0x5F0F_3CF5); // magicNumber
+        put16(&mut t, 0); // flags
+        put16(&mut t, 1000); // unitsPerEm
+        t.extend([0; 16]); // created, modified
+        for v in [0i16, 0, ADVANCE as i16, 700] {
+            put16(&mut t, v as u16); // xMin, yMin, xMax, yMax
+        }
+        put16(&mut t, 0); // macStyle
+        put16(&mut t, 8); // lowestRecPPEM
+        put16(&mut t, 2); // fontDirectionHint
+        put16(&mut t, 1); // indexToLocFormat: long
+        put16(&mut t, 0); // glyphDataFormat
+        t
+    }
+
+    fn hhea(&self) -> Vec<u8> {
+        let mut t = Vec::new();
+        put32(&mut t, 0x0001_0000);
+        for v in [800i16, -200, 0] {
+            put16(&mut t, v as u16); // ascender, descender, lineGap
+        }
+        put16(&mut t, ADVANCE); // advanceWidthMax
+        for v in [0i16, 0, ADVANCE as i16, 1, 0, 0, 0, 0, 0, 0, 0] {
+            put16(&mut t, v as u16);
+        }
+        put16(&mut t, self.num_glyphs()); // numberOfHMetrics
+        t
+    }
+
+    fn hmtx(&self) -> Vec<u8> {
+        let mut t = Vec::new();
+        for _ in 0..self.num_glyphs() {
+            put16(&mut t, ADVANCE);
+            put16(&mut t, 0);
+        }
+        t
+    }
+
+    fn maxp(&self) -> Vec<u8> {
+        let mut t = Vec::new();
+        put32(&mut t, 0x0000_5000);
+        put16(&mut t, self.num_glyphs());
+        t
+    }
+
+    /// 每个字形为 (50, 0)-(550, 700) 的矩形，.notdef 为空字形
+    fn glyf_loca(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        put32(&mut loca, 0);
+        put32(&mut loca, 0);
+        for _ in &self.chars {
+            put16(&mut glyf, 1); // numberOfContours
+            for v in [50u16, 0, 550, 700] {
+                put16(&mut glyf, v);
+            }
+            put16(&mut glyf, 3); // endPtsOfContours
+            put16(&mut glyf, 0); // instructionLength
+            glyf.extend([0x01; 4]); // 在曲线上，坐标为 16 位
+            for dx in [50i16, 500, 0, -500] {
+                put16(&mut glyf, dx as u16);
+            }
+            for dy in [0i16, 0, 700, 0] {
+                put16(&mut glyf, dy as u16);
+            }
+            while glyf.len() % 4 != 0 {
+                glyf.push(0);
+            }
+            put32(&mut loca, glyf.len() as u32);
+        }
+        (glyf, loca)
+    }
+
+    /// cmap 格式 12（Windows Unicode 全字符集）
+    fn cmap(&self) -> Vec<u8> {
+        let mut groups: Vec<(u32, u16)> = self
+            .chars
+            .iter()
+            .map(|&c| (c as u32, self.glyph(c)))
+            .collect();
+        groups.sort_unstable();
+
+        let mut t = Vec::new();
+        put16(&mut t, 0);
+        put16(&mut t, 1);
+        put16(&mut t, 3);
+        put16(&mut t, 10);
+        put32(&mut t, 12);
+        put16(&mut t, 12);
+        put16(&mut t, 0);
+        put32(&mut t, 16 + 12 * groups.len() as u32);
+        put32(&mut t, 0);
+        put32(&mut t, groups.len() as u32);
+        for (code, glyph) in groups {
+            put32(&mut t, code);
+            put32(&mut t, code);
+            put32(&mut t, u32::from(glyph));
+        }
+        t
+    }
+
+    /// name 表：族名、样式名、全名与 PostScript 名（Windows 英文）
+    fn name(&self) -> Vec<u8> {
+        let full = format!("{} {}", self.family, self.style);
+        let postscript = format!("{}-{}", self.family, self.style).replace(' ', "");
+        let names = [
+            (1u16, self.family.as_str()),
+            (2, self.style.as_str()),
+            (4, full.as_str()),
+            (6, postscript.as_str()),
+        ];
+        let mut t = Vec::new();
+        let mut strings = Vec::new();
+        put16(&mut t, 0);
+        put16(&mut t, names.len() as u16);
+        put16(&mut t, 6 + 12 * names.len() as u16);
+        for (id, value) in names {
+            let encoded: Vec<u8> = value.encode_utf16().flat_map(u16::to_be_bytes).collect();
+            for v in [3, 1, 0x0409, id, encoded.len() as u16, strings.len() as u16] {
+                put16(&mut t, v);
+            }
+            strings.extend(encoded);
+        }
+        t.extend(strings);
+        t
+    }
+
+    /// 旧式 `kern` 表（版本 0，一个水平格式 0 子表）
+    fn kern_table(&self) -> Vec<u8> {
+        let mut pairs: Vec<(u16, u16, i16)> = self
+            .kern
+            .iter()
+            .map(|&(a, b, v)| (self.glyph(a), self.glyph(b), v))
+            .collect();
+        pairs.sort_unstable();
+
+        let mut t = Vec::new();
+        put16(&mut t, 0); // version
+        put16(&mut t, 1); // nTables
+        put16(&mut t, 0); // subtable version
+        put16(&mut t, 14 + 6 * pairs.len() as u16); // length
+        t.extend([0, 0x01]); // format 0，水平
+        put16(&mut t, pairs.len() as u16);
+        t.extend([0; 6]); // searchRange, entrySelector, rangeShift
+        for (left, right, value) in pairs {
+            put16(&mut t, left);
+            put16(&mut t, right);
+            put16(&mut t, value as u16);
+        }
+        t
+    }
+
+    /// GPOS 表：空脚本列表、特性列表与成对调整（类型 2）查找表
+    fn gpos(&self) -> Vec<u8> {
+        let mut features = Vec::new();
+        put16(&mut features, self.features.len() as u16);
+        let mut offset = 2 + 6 * self.features.len();
+        let mut bodies = Vec::new();
+        for (tag, lookups) in &self.features {
+            features.extend(tag);
+            put16(&mut features, (offset + bodies.len()) as u16);
+            put16(&mut bodies, 0);
+            put16(&mut bodies, lookups.len() as u16);
+            for &index in lookups {
+                put16(&mut bodies, index);
+            }
+        }
+        features.extend(bodies);
+
+        let lookups: Vec<Vec<u8>> = self.lookups.iter().map(|l| self.pair_lookup(l)).collect();
+        let mut lookup_list = Vec::new();
+        put16(&mut lookup_list, lookups.len() as u16);
+        offset = 2 + 2 * lookups.len();
+        for lookup in &lookups {
+            put16(&mut lookup_list, offset as u16);
+            offset += lookup.len();
+        }
+        lookup_list.extend(lookups.concat());
+
+        let mut t = Vec::new();
+        put32(&mut t, 0x0001_0000);
+        put16(&mut t, 10); // scriptList
+        put16(&mut t, 12); // featureList
+        put16(&mut t, (12 + features.len()) as u16); // lookupList
+        put16(&mut t, 0); // scriptCount
+        t.extend(features);
+        t.extend(lookup_list);
+        t
+    }
+
+    fn pair_lookup(&self, subtables: &[PairSubtable]) -> Vec<u8> {
+        let encoded: Vec<Vec<u8>> = subtables.iter().map(|s| self.pair_subtable(s)).collect();
+        let mut t = Vec::new();
+        put16(&mut t, 2); // lookupType: pair adjustment
+        put16(&mut t, 0); // lookupFlag
+        put16(&mut t, encoded.len() as u16);
+        let mut offset = 6 + 2 * encoded.len();
+        for subtable in &encoded {
+            put16(&mut t, offset as u16);
+            offset += subtable.len();
+        }
+        t.extend(encoded.concat());
+        t
+    }
+
+    fn pair_subtable(&self, subtable: &PairSubtable) -> Vec<u8> {
+        let mut t = Vec::new();
+        match subtable {
+            PairSubtable::Pairs(pairs) => {
+                let mut firsts: Vec<u16> = pairs.iter().map(|&(a, _, _)| self.glyph(a)).collect();
+                firsts.sort_unstable();
+                firsts.dedup();
+                let sets: Vec<Vec<u8>> = firsts
+                    .iter()
+                    .map(|&first| {
+                        let mut records: Vec<(u16, i16)> = pairs
+                            .iter()
+                            .filter(|&&(a, _, _)| self.glyph(a) == first)
+                            .map(|&(_, b, v)| (self.glyph(b), v))
+                            .collect();
+                        records.sort_unstable();
+                        let mut set = Vec::new();
+                        put16(&mut set, records.len() as u16);
+                        for (second, value) in records {
+                            put16(&mut set, second);
+                            put16(&mut set, value as u16);
+                        }
+                        set
+                    })
+                    .collect();
+                let header = 10 + 2 * sets.len();
+                let coverage_offset = header + sets.iter().map(Vec::len).sum::<usize>();
+                put16(&mut t, 1); // posFormat
+                put16(&mut t, coverage_offset as u16);
+                put16(&mut t, 0x0004); // valueFormat1: XAdvance
+                put16(&mut t, 0); // valueFormat2
+                put16(&mut t, sets.len() as u16);
+                let mut offset = header;
+                for set in &sets {
+                    put16(&mut t, offset as u16);
+                    offset += set.len();
+                }
+                t.extend(sets.concat());
+                t.extend(coverage(&firsts));
+            }
+            PairSubtable::Classes { first, second, values } => {
+                let mut covered: Vec<u16> =
+                    first.iter().flatten().map(|&c| self.glyph(c)).collect();
+                covered.sort_unstable();
+                let class1_count = first.len() + 1;
+                let class2_count = second.len() + 1;
+                let header = 16 + 2 * class1_count * class2_count;
+                let class_def1 = self.class_def(first);
+                let class_def2 = self.class_def(second);
+                put16(&mut t, 2); // posFormat
+                put16(&mut t, (header + class_def1.len() + class_def2.len()) as u16);
+                put16(&mut t, 0x0004);
+                put16(&mut t, 0);
+                put16(&mut t, header as u16);
+                put16(&mut t, (header + class_def1.len()) as u16);
+                put16(&mut t, class1_count as u16);
+                put16(&mut t, class2_count as u16);
+                for c1 in 0..class1_count as u16 {
+                    for c2 in 0..class2_count as u16 {
+                        let value = values
+                            .iter()
+                            .find(|&&(a, b, _)| a == c1 && b == c2)
+                            .map_or(0, |&(_, _, v)| v);
+                        put16(&mut t, value as u16);
+                    }
+                }
+                t.extend(class_def1);
+                t.extend(class_def2);
+                t.extend(coverage(&covered));
+            }
+        }
+        t
+    }
+
+    /// 类定义表（格式 2，每个字形一个范围）
+    fn class_def(&self, classes: &[Vec<char>]) -> Vec<u8> {
+        let mut ranges: Vec<(u16, u16)> = classes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, chars)| chars.iter().map(move |&c| (c, i as u16 + 1)))
+            .map(|(c, class)| (self.glyph(c), class))
+            .collect();
+        ranges.sort_unstable();
+        let mut t = Vec::new();
+        put16(&mut t, 2);
+        put16(&mut t, ranges.len() as u16);
+        for (glyph, class) in ranges {
+            put16(&mut t, glyph);
+            put16(&mut t, glyph);
+            put16(&mut t, class);
+        }
+        t
+    }
+}
+
+/// 把多个字体打包为字体集合（`.ttc`）
+pub(crate) fn collection(fonts: &[TestFont]) -> Vec<u8> {
+    let tables: Vec<_> = fonts.iter().map(TestFont::tables).collect();
+    write_fonts(&tables, true)
+}
+
+/// 覆盖表（格式 1）
+fn coverage(glyphs: &[u16]) -> Vec<u8> {
+    let mut t = Vec::new();
+    put16(&mut t, 1);
+    put16(&mut t, glyphs.len() as u16);
+    for &glyph in glyphs {
+        put16(&mut t, glyph);
+    }
+    t
+}
+
+/// 写出字体文件：单个字体为普通 sfnt，`collection` 为 true 时写为 TTC
+fn write_fonts(fonts: &[Vec<([u8; 4], Vec<u8>)>], collection: bool) -> Vec<u8> {
+    let header_len = if collection { 12 + 4 * fonts.len() } else { 0 };
+    let dir_lens: Vec<usize> = fonts.iter().map(|tables| 12 + 16 * tables.len()).collect();
+    let mut table_offset = header_len + dir_lens.iter().sum::<usize>();
+
+    let mut out = Vec::new();
+    if collection {
+        out.extend(b"ttcf");
+        put32(&mut out, 0x0001_0000);
+        put32(&mut out, fonts.len() as u32);
+        let mut dir_offset = header_len;
+        for len in &dir_lens {
+            put32(&mut out, dir_offset as u32);
+            dir_offset += len;
+        }
+    }
+    let mut data = Vec::new();
+    for tables in fonts {
+        let count = tables.len() as u16;
+        let entry_selector = 15 - count.leading_zeros() as u16;
+        put32(&mut out, 0x0001_0000);
+        put16(&mut out, count);
+        put16(&mut out, 16 << entry_selector);
+        put16(&mut out, entry_selector);
+        put16(&mut out, count * 16 - (16 << entry_selector));
+        for (tag, table) in tables {
+            out.extend(tag);
+            put32(&mut out, checksum(table));
+            put32(&mut out, table_offset as u32);
+            put32(&mut out, table.len() as u32);
+            data.extend(table);
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            table_offset = header_len + dir_lens.iter().sum::<usize>() + data.len();
+        }
+    }
+    out.extend(data);
+    out
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn put16(out: &mut Vec<u8>, value: u16) {
+    out.extend(value.to_be_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, value: u32) {
+    out.extend(value.to_be_bytes());
+}